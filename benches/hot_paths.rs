@@ -0,0 +1,89 @@
+//! Benchmarks of the hot paths of encoding, i.e., the distances between sequences, the LSH instances, and the RQ encoding under the DNA constraints.
+//! Run them with `cargo bench` (or `cargo bench -- <filter>` for a subset). The inputs are synthetic and seeded, so the numbers of different commits are comparable.
+use std::sync::Arc;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rqpap::base_sequence::BaseSequence;
+use rqpap::dna_rules::GcHpRules;
+use rqpap::lsh::LSH;
+use rqpap::raptor::{RaptorQ, EncodeLimits, EncodeRules};
+
+static SEED: u64                              = 42_u64;              // the seed of every synthetic input
+static SEQ_LENS: [usize; 3]                   = [100, 300, 1000];    // the lengths of the sequences whose distances are benchmarked
+static JACCARD_K: usize                       = 5_usize;             // the k-mer length of the Jaccard distance (the default of lsh_k_seqs)
+static LSH_SIZES: [usize; 3]                  = [1000, 10000, 50000]; // the numbers of sequences stored in the LSH instance that is queried
+static LSH_SEQ_LEN: usize                     = 150_usize;           // the length of the sequences stored in the LSH instances
+static LSH_INSERT_BATCH: usize                = 1000_usize;          // the number of sequences inserted into an empty LSH instance per iteration
+static LSH_PARAMS: (usize, usize, usize)      = (5, 200, 20);        // k, r, and b of the LSH instances (the defaults of the Info-DNAs' LSH instance)
+static DATA_LENS: [usize; 3]                  = [16, 64, 256];       // the lengths in bytes of the data objects encoded by RQ
+static PACKETS_PER_BLOCK: usize               = 5_usize;             // the number of packets initially generated by RQ (as in the encoder)
+static MAX_ENCODE_LOOPS: usize                = 200_usize;           // the number of loops of RQ (as in the encoder)
+
+/// Returns `n` random sequences of length `len` drawn from `rng`.
+fn random_seqs(n: usize, len: usize, rng: &mut StdRng) -> Vec<Arc<BaseSequence>> {
+    (0..n).map(|_| Arc::new(BaseSequence::random_with_rng(len, rng))).collect()
+}
+
+/// Returns an empty LSH instance with the parameters `LSH_PARAMS` drawn from `rng`.
+fn new_lsh(rng: &mut StdRng) -> LSH {
+    let (k, r, b) = LSH_PARAMS;
+    LSH::new_with_rng(k, r, b, rng)
+}
+
+fn bench_distances(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut group = c.benchmark_group("distance");
+    for &len in SEQ_LENS.iter() {
+        let seqs = random_seqs(2, len, &mut rng);
+        group.bench_with_input(BenchmarkId::new("jaccard_distance_arc", len), &seqs, |b, seqs| b.iter(|| seqs[0].jaccard_distance_arc(black_box(&seqs[1]), JACCARD_K)));
+        group.bench_with_input(BenchmarkId::new("edit_distance_arc", len), &seqs, |b, seqs| b.iter(|| seqs[0].edit_distance_arc(black_box(&seqs[1]))));
+    }
+    group.finish();
+}
+
+fn bench_lsh(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let mut group = c.benchmark_group("lsh");
+
+    // every iteration inserts the same batch into a fresh instance, whose construction is not measured
+    let batch = random_seqs(LSH_INSERT_BATCH, LSH_SEQ_LEN, &mut rng);
+    let lsh_seed = SEED.wrapping_add(1_u64);
+    group.bench_function(BenchmarkId::new("insert", LSH_INSERT_BATCH), |b| b.iter_batched(
+        || new_lsh(&mut StdRng::seed_from_u64(lsh_seed)),
+        |lsh| {
+            batch.iter().for_each(|seq| { lsh.insert(seq); });
+            lsh
+        },
+        BatchSize::LargeInput));
+
+    for &size in LSH_SIZES.iter() {
+        let lsh = new_lsh(&mut rng);
+        random_seqs(size, LSH_SEQ_LEN, &mut rng).iter().for_each(|seq| { lsh.insert(seq); });
+        let queries = random_seqs(100, LSH_SEQ_LEN, &mut rng);
+        let mut next = 0_usize;
+        group.bench_with_input(BenchmarkId::new("similar_seqs", size), &lsh, |b, lsh| b.iter(|| {
+            next = (next + 1_usize) % queries.len();
+            lsh.similar_seqs(black_box(&queries[next]))
+        }));
+    }
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let raptor = RaptorQ::default();
+    let gc_hp_rules = GcHpRules::new(0.4_f64, 0.6_f64, 5_usize);
+    let rules = EncodeRules::accept_all()
+        .with_packet_check(|seq: &Arc<BaseSequence>| gc_hp_rules.satisfies(seq))
+        .with_strand_gc_hp_check(|gc, longest_hp| gc_hp_rules.satisfies_gc(gc) && gc_hp_rules.satisfies_hp(longest_hp));
+    let mut group = c.benchmark_group("rq");
+    for &len in DATA_LENS.iter() {
+        let data = (0..len).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        group.bench_with_input(BenchmarkId::new("encode_to_dna_with_rules", len), &data, |b, data| b.iter(|| raptor.encode_to_dna_with_rules(black_box(data), EncodeLimits::new(PACKETS_PER_BLOCK, MAX_ENCODE_LOOPS, 0_usize), &mut rng, &rules).is_ok()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_distances, bench_lsh, bench_encode);
+criterion_main!(benches);
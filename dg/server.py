@@ -11,11 +11,12 @@ if __name__ == "__main__":
         num_workers = int(sys.argv[1])
     except:
         num_workers = multiprocessing.cpu_count()
+    host = sys.argv[2] if len(sys.argv) > 2 else "localhost"
 
     start_port = 6000
     print("num_workers=", num_workers)
     pool = ProcessPoolExecutor(max_workers=num_workers)
-    workers = [Worker(port) for port in range(start_port, start_port + num_workers)]
+    workers = [Worker(port, host) for port in range(start_port, start_port + num_workers)]
     for w in workers:
         pool.submit(w.start)
 
@@ -8,9 +8,11 @@ import seqfold
 class Worker(object):
     DEFAULT_TEMP = 25
     MAX_BUFF_SIZE = 2 ** 12
+    BATCH_MARKER = 0
 
-    def __init__(self, port):
+    def __init__(self, port, host="localhost"):
         self.is_connected = False
+        self.host = host
         self.port = port
         self.channel: socket.socket = None
         self.listener = None
@@ -21,7 +23,7 @@ class Worker(object):
 
     def connect(self):
         self.listener = socket.socket(socket.AF_INET, socket.SOCK_STREAM)
-        self.listener.bind(("localhost", self.port))
+        self.listener.bind((self.host, self.port))
         self.listener.listen(1)
         #self.listener.settimeout(0.1)
         # print("listening..")
@@ -33,7 +35,11 @@ class Worker(object):
         try:
             while self.is_connected:
                 # print("looping")
-                seq, temp = self.parse_params()
+                received_bytes = self.channel.recv(Worker.MAX_BUFF_SIZE)
+                if received_bytes and received_bytes[0] == Worker.BATCH_MARKER:
+                    self.handle_batch_request(received_bytes)
+                    continue
+                seq, temp = self.parse_params(received_bytes)
                 if seq and temp:
                     self.handle_request(seq, temp)
                 else:
@@ -58,19 +64,44 @@ class Worker(object):
         print("sending dg=", dg, "process id=", os.getpid())
         self.channel.send(bytearray(struct.pack("f", dg)))
 
-    def handle_request(self, seq, temp):
+    def compute_dg(self, seq, temp):
         try:
             dg = sum([0.5 * s.e if s.desc.startswith("STACK") else s.e for s in seqfold.fold(seq, temp)])
             if math.isinf(dg):
-                self.send_dg(float(0))
-            else:
-                self.send_dg(dg)
+                return float(0)
+            return dg
         except:
-            self.send_dg(float(0))
+            return float(0)
+
+    def handle_request(self, seq, temp):
+        self.send_dg(self.compute_dg(seq, temp))
+
+    def recv_exact(self, buffer, size):
+        while len(buffer) < size:
+            chunk = self.channel.recv(Worker.MAX_BUFF_SIZE)
+            if not chunk:
+                raise ConnectionError("connection closed")
+            buffer += chunk
+        return buffer
+
+    # batch query: marker (1 byte), temp (float), count (uint32), and count x [len (uint32), seq (len bytes)] -> answered with count x dg (float), all little endian
+    def handle_batch_request(self, received_bytes):
+        buffer = self.recv_exact(bytearray(received_bytes), 9)
+        temp, count = struct.unpack("<fI", buffer[1:9])
+        offset = 9
+        dgs = []
+        for _ in range(count):
+            buffer = self.recv_exact(buffer, offset + 4)
+            length = struct.unpack("<I", buffer[offset: offset + 4])[0]
+            offset += 4
+            buffer = self.recv_exact(buffer, offset + length)
+            dgs.append(self.compute_dg(buffer[offset: offset + length].decode(), temp))
+            offset += length
+        print("sending", count, "dgs", "process id=", os.getpid())
+        self.channel.sendall(struct.pack("<%df" % count, *dgs))
 
-    def parse_params(self):
+    def parse_params(self, received_bytes):
         try:
-            received_bytes = self.channel.recv(Worker.MAX_BUFF_SIZE)
             received = str(received_bytes)
             index = -1
             try:
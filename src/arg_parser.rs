@@ -1,16 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::cell::RefCell;
 use std::num::{ParseIntError, ParseFloatError};
 use std::str::FromStr;
 
+static MAX_SUGGESTION_DISTANCE: usize = 3_usize; // maximum edit distance of a known argument to an unknown argument to be suggested instead
+
 pub struct ArgsParser {
-    mappings: HashMap<String, String>
+    mappings: HashMap<String, String>,
+    queried: RefCell<HashSet<String>> // the names of all arguments that were looked up
 }
 
 impl ArgsParser {
     /// Creates a new ArgsParser.
     pub fn new() -> Self {
         ArgsParser {
-            mappings: Default::default()
+            mappings: Default::default(),
+            queried: Default::default()
         }
     }
 
@@ -30,20 +35,21 @@ impl ArgsParser {
             }
         }
         ArgsParser {
-            mappings
+            mappings,
+            queried: Default::default()
         }
     }
 
-    /// Prints the parsed arguments.
-    pub fn print_params(&self) {
-        for (k, v) in &self.mappings {
-            println!("{}={}", k, v);
-        }
+    /// Returns the parsed arguments as (name, value) pairs sorted by name.
+    pub fn params(&self) -> Vec<(&str, &str)> {
+        let mut params = self.mappings.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect::<Vec<_>>();
+        params.sort_unstable();
+        params
     }
 
     /// Parses the given string as usize.
     pub fn get_as_usize(&self, name: &str, default: usize) -> usize {
-        match self.mappings.get(name) {
+        match self.lookup(name) {
             Some(v) => match v.parse() {
                 Ok(v) => v,
                 Err(_) => default
@@ -54,7 +60,7 @@ impl ArgsParser {
 
     /// Parses the given string as f64.
     pub fn get_as_f64(&self, name: &str, default: f64) -> f64 {
-        match self.mappings.get(name) {
+        match self.lookup(name) {
             Some(v) => match v.parse() {
                 Ok(v) => v,
                 Err(_) => default
@@ -65,7 +71,7 @@ impl ArgsParser {
 
     /// Parses the given string as T.
     pub fn get_as<T>(&self, name: &str, default: T) -> T where T: FromStr {
-        match self.mappings.get(name) {
+        match self.lookup(name) {
             Some(v) => match v.parse() {
                 Ok(v) => v,
                 Err(_) => default
@@ -76,7 +82,7 @@ impl ArgsParser {
 
     /// Parses the given string as f32.
     pub fn get_as_f32(&self, name: &str, default: f32) -> f32 {
-        match self.mappings.get(name) {
+        match self.lookup(name) {
             Some(v) => match v.parse() {
                 Ok(v) => v,
                 Err(_) => default
@@ -87,7 +93,7 @@ impl ArgsParser {
 
     /// Parses the given string as bool.
     pub fn get_as_bool(&self, name: &str, default: bool) -> bool {
-        match self.mappings.get(name) {
+        match self.lookup(name) {
             Some(v) => {
                 return if v.eq_ignore_ascii_case("1") || v.eq_ignore_ascii_case("true") || v.eq_ignore_ascii_case("yes") || v.eq_ignore_ascii_case("y") {
                     true
@@ -108,9 +114,86 @@ impl ArgsParser {
 
     /// returns the parameter with key `name`. If not present, returns `or_else`.
     pub fn get_or_else(&self, name: &str, or_else: &str) -> String {
-        match self.mappings.get(name) {
+        match self.lookup(name) {
             Some(v) => String::from(v),
             None => String::from(or_else)
         }
     }
+
+    /// Returns the value of the argument with key `name` and remembers that `name` is a known argument.
+    fn lookup(&self, name: &str) -> Option<&String> {
+        self.queried.borrow_mut().insert(name.to_owned());
+        self.mappings.get(name)
+    }
+
+    /// Returns the names of the given arguments that were never looked up, i.e., the unknown arguments, in sorted order.
+    pub fn unused(&self) -> Vec<&str> {
+        let queried = self.queried.borrow();
+        let mut unused = self.mappings.keys().filter(|k| !queried.contains(k.as_str())).map(|k| k.as_str()).collect::<Vec<_>>();
+        unused.sort_unstable();
+        unused
+    }
+
+    /// Returns a message for every unknown argument, i.e., the argument with its value together with the most similar known argument, if any, e.g., "lsh_kk=5 (did you mean lsh_k?)".
+    /// Returns no messages if every argument is known. Should be called after all arguments were looked up.
+    pub fn validate(&self) -> Vec<String> {
+        let queried = self.queried.borrow();
+        self.unused().iter().map(|k| {
+            let suggestion = queried.iter()
+                .map(|q| (edit_distance(k, q), q))
+                .filter(|(d, _)| *d <= MAX_SUGGESTION_DISTANCE)
+                .min();
+            match suggestion {
+                Some((_, q)) => format!("{}={} (did you mean {}?)", k, self.mappings.get(*k).unwrap(), q),
+                None => format!("{}={}", k, self.mappings.get(*k).unwrap())
+            }
+        }).collect()
+    }
+}
+
+impl Default for ArgsParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the edit distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let up = row[j + 1];
+            row[j + 1] = (up + 1).min(row[j] + 1).min(diag + if ca == *cb { 0 } else { 1 });
+            diag = up;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser(args: &[&str]) -> ArgsParser {
+        ArgsParser::from(args.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    #[test]
+    fn validate_returns_the_unknown_arguments_with_suggestions() {
+        let args = parser(&["lsh_k=5", "lsh_kk=6", "unrelated_argument=1"]);
+        assert_eq!(args.get_as("lsh_k", 0_usize), 5_usize);
+        assert_eq!(args.validate(), vec!["lsh_kk=6 (did you mean lsh_k?)".to_string(), "unrelated_argument=1".to_string()]);
+        assert_eq!(args.params(), vec![("lsh_k", "5"), ("lsh_kk", "6"), ("unrelated_argument", "1")]);
+    }
+
+    #[test]
+    fn validate_returns_nothing_if_every_argument_is_known() {
+        let args = parser(&["lsh_k=5"]);
+        assert_eq!(args.get_or_else("lsh_k", "3"), "5");
+        assert!(args.validate().is_empty());
+        assert!(ArgsParser::default().validate().is_empty());
+    }
 }
@@ -1,14 +1,21 @@
 use crate::base_sequence::Base::{A, C, G, T};
+use crate::packed_sequence::PackedSequence;
 use std::iter::FromIterator;
-use std::fs;
 use std::fs::{OpenOptions, File};
-use std::io::Write;
+use std::io::{Write, Read, BufRead, BufReader, Lines};
 use std::sync::{Mutex, Arc};
 use rand::Rng;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
 
-/// The Enum that represents a DNA base.
-#[derive(Eq, PartialEq, Clone, Copy, Debug, Hash)]
+pub static GZIP_EXTENSION: &str               = ".gz";               // files whose path ends with this extension are read and written gzip-compressed
+
+/// The Enum that represents a DNA base. Bases are ordered lexicographically, i.e., A < C < G < T.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug, Hash)]
 #[repr(u8)]
 pub enum Base {
     A = 0,
@@ -50,6 +57,50 @@ impl Base {
         }
     }
 
+    /// Parses an ascii byte into a DNA base. Lowercase bases are accepted as well. Returns None if `b` is not a DNA base.
+    pub fn try_from_byte(b: u8) -> Option<Self> {
+        match b {
+            b'A' | b'a' => Some(A),
+            b'C' | b'c' => Some(C),
+            b'G' | b'g' => Some(G),
+            b'T' | b't' => Some(T),
+            _ => None
+        }
+    }
+
+    /// Returns the DNA bases an ascii IUPAC nucleotide code `b` stands for, or None if `b` is not an IUPAC code. Lowercase codes are accepted as well.
+    /// The following mapping is used (U is treated as T):
+    /// | Code | Bases      | Code | Bases   |
+    /// |------|------------|------|---------|
+    /// | A    | A          | K    | G, T    |
+    /// | C    | C          | M    | A, C    |
+    /// | G    | G          | B    | C, G, T |
+    /// | T, U | T          | D    | A, G, T |
+    /// | R    | A, G       | H    | A, C, T |
+    /// | Y    | C, T       | V    | A, C, G |
+    /// | S    | C, G       | N    | A, C, G, T |
+    /// | W    | A, T       |      |         |
+    pub fn iupac_bases(b: u8) -> Option<&'static [Base]> {
+        match b.to_ascii_uppercase() {
+            b'A' => Some(&[A]),
+            b'C' => Some(&[C]),
+            b'G' => Some(&[G]),
+            b'T' | b'U' => Some(&[T]),
+            b'R' => Some(&[A, G]),
+            b'Y' => Some(&[C, T]),
+            b'S' => Some(&[C, G]),
+            b'W' => Some(&[A, T]),
+            b'K' => Some(&[G, T]),
+            b'M' => Some(&[A, C]),
+            b'B' => Some(&[C, G, T]),
+            b'D' => Some(&[A, G, T]),
+            b'H' => Some(&[A, C, T]),
+            b'V' => Some(&[A, C, G]),
+            b'N' => Some(&[A, C, G, T]),
+            _ => None
+        }
+    }
+
     /// Returns true if the base is a C or a G.
     pub const fn is_c_or_g(&self) -> bool {
         match self {
@@ -60,7 +111,12 @@ impl Base {
 
     /// Returns a DNA base. `gc_content` is the probability of returning a C or a G.
     pub fn random_gc(gc_content: f64) -> Self {
-        let rand = rand::thread_rng().gen_range(0_f64..1_f64);
+        Self::random_gc_with_rng(gc_content, &mut rand::thread_rng())
+    }
+
+    /// Returns a DNA base drawn from `rng`. `gc_content` is the probability of returning a C or a G.
+    pub fn random_gc_with_rng(gc_content: f64, rng: &mut impl Rng) -> Self {
+        let rand = rng.gen_range(0_f64..1_f64);
         let gs = gc_content / 2 as f64;
         let a = gc_content + 0.5_f64 - gs;
 
@@ -80,7 +136,12 @@ impl Base {
 
     /// Returns a random DNA base.
     pub fn random() -> Self {
-        let rand = rand::thread_rng().gen_range(0_f64..1_f64);
+        Self::random_with_rng(&mut rand::thread_rng())
+    }
+
+    /// Returns a random DNA base drawn from `rng`.
+    pub fn random_with_rng(rng: &mut impl Rng) -> Self {
+        let rand = rng.gen_range(0_f64..1_f64);
         if rand <= 0.25_f64 {
             A
         }
@@ -110,16 +171,76 @@ impl BaseSequence {
         }
     }
 
+    /// Returns a random DNA sequence of `len` bases, e.g., for synthetic probes or spacers.
+    pub fn random(len: usize) -> Self {
+        Self::random_with_rng(len, &mut rand::thread_rng())
+    }
+
+    /// Returns a random DNA sequence of `len` bases drawn from `rng`.
+    pub fn random_with_rng(len: usize, rng: &mut impl Rng) -> Self {
+        Self::new((0..len).map(|_| Base::random_with_rng(rng)).collect())
+    }
+
+    /// Returns a random DNA sequence of `len` bases, each of which is a C or a G with the probability `gc_content`. Hence, the expected GC content of the sequence is `gc_content`.
+    pub fn random_gc(len: usize, gc_content: f64) -> Self {
+        Self::random_gc_with_rng(len, gc_content, &mut rand::thread_rng())
+    }
+
+    /// Returns a random DNA sequence of `len` bases drawn from `rng`, each of which is a C or a G with the probability `gc_content`.
+    pub fn random_gc_with_rng(len: usize, gc_content: f64, rng: &mut impl Rng) -> Self {
+        if !(0_f64..=1_f64).contains(&gc_content) {
+            panic!("gc_content must be between 0 and 1 (gc_content = {})", gc_content);
+        }
+        Self::new((0..len).map(|_| Base::random_gc_with_rng(gc_content, rng)).collect())
+    }
+
     /// Clears the content of the vector of bases, i.e., returns the sequence empty.
     pub fn clear(&mut self) {
         self.sequence.clear();
     }
 
+    /// Reverses the order of the DNA bases of the current BaseSequence in place.
+    #[inline]
+    pub fn reverse_in_place(&mut self) {
+        self.sequence.reverse();
+    }
+
+    /// Replaces every DNA base of the current BaseSequence by its complement in place.
+    #[inline]
+    pub fn complement_in_place(&mut self) {
+        self.sequence.iter_mut().for_each(|base| *base = base.complement());
+    }
+
+    /// Sets the DNA base at position `idx` to `base`.
+    #[inline]
+    pub fn set_base(&mut self, idx: usize, base: Base) {
+        if idx >= self.len() {
+            panic!("cannot set base at index {} for seq of len {}", idx, self.len());
+        }
+        self.sequence[idx] = base;
+    }
+
+    /// Shortens the current BaseSequence to `len` DNA bases. Has no effect if `len` is greater than or equal to the current length.
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.sequence.truncate(len);
+    }
+
     /// Creates a new BaseSequence by parsing a slice of DNA bases.
     pub fn from_slice(slice: &[Base]) -> Self {
         Self::new(slice.to_vec())
     }
 
+    /// Returns the bases packed into bytes with 4 bases per byte (see `PackedSequence`). The last byte is padded with zeros if the length is not a multiple of 4, i.e., the length has to be stored alongside the bytes.
+    pub fn to_packed_bytes(&self) -> Vec<u8> {
+        PackedSequence::from_base_sequence(self).as_bytes().to_vec()
+    }
+
+    /// Creates a new BaseSequence of the first `len` bases of the packed `bytes`, i.e., the inverse of `to_packed_bytes`. Panics if `bytes` contains less than `len` bases.
+    pub fn from_packed_bytes(bytes: &[u8], len: usize) -> Self {
+        PackedSequence::from_bytes(bytes, len).to_base_sequence()
+    }
+
     /// Appends the given BaseSequence `seq` to the current one.
     pub fn append_seq(&mut self, seq: &BaseSequence) {
         self.append_slice(seq.as_slice())
@@ -153,14 +274,86 @@ impl BaseSequence {
         (0..1 + self.len() - len).map(|i| self.sub_sequence_slice(i, i + len)).collect::<HashSet<_>>()
     }
 
-    /// Reads a fasta file with DNA sequences into a vector of BaseSequence.
+    /// Returns the k-mers as a multiset, i.e., every distinct k-mer mapped to the number of its occurrences. Unlike `k_mers_set`, repeated k-mers are kept apart from k-mers that occur once.
+    pub fn k_mer_counts(&self, len: usize) -> HashMap<Vec<Base>, usize> {
+        if len > self.len() {
+            panic!("cannot create kmers of k={} for seq of len {}", len, self.len());
+        }
+
+        let mut counts = HashMap::new();
+        for i in 0..1 + self.len() - len {
+            *counts.entry(self.sub_sequence_slice(i, i + len).to_vec()).or_insert(0_usize) += 1_usize;
+        }
+        counts
+    }
+
+    /// Returns the canonical k-mers (see `canonical_k_mers_set`) as a multiset, i.e., every distinct canonical k-mer mapped to the number of its occurrences.
+    pub fn canonical_k_mer_counts(&self, len: usize) -> HashMap<Vec<Base>, usize> {
+        if len > self.len() {
+            panic!("cannot create kmers of k={} for seq of len {}", len, self.len());
+        }
+
+        let mut counts = HashMap::new();
+        for i in 0..1 + self.len() - len {
+            let k_mer = self.sub_sequence_slice(i, i + len);
+            let rc = k_mer.iter().rev().map(|base| base.complement()).collect::<Vec<_>>();
+            *counts.entry(if rc.as_slice() < k_mer { rc } else { k_mer.to_vec() }).or_insert(0_usize) += 1_usize;
+        }
+        counts
+    }
+
+    /// Reads a fasta file with DNA sequences into a vector of BaseSequence. The lines of a wrapped record are concatenated into a single BaseSequence.
     pub fn read_fasta_arc(file_path: &str) -> Vec<Arc<BaseSequence>> {
-        fs::read_to_string(file_path).iter().flat_map(|s| s.split('\n')).filter(|l| !l.starts_with('>') && l.len() > 0).map(|s| Arc::new(BaseSequence::from_str(s))).collect()
+        Self::read_fasta_streaming(file_path).collect()
+    }
+
+    /// Reads a fasta file with DNA sequences lazily, i.e., line by line without loading the whole file into memory. The lines of a wrapped record are concatenated into a single BaseSequence.
+    pub fn read_fasta_streaming(file_path: &str) -> impl Iterator<Item = Arc<BaseSequence>> {
+        Self::read_fasta_iupac_streaming(file_path, 0)
+    }
+
+    /// Reads a fasta file with degenerate DNA sequences lazily and yields every concrete sequence of each record (see `expand_iupac`).
+    /// Panics if a record represents more than `max_iupac_expansions` concrete sequences. A `max_iupac_expansions` of 0 disables IUPAC codes, i.e., only A, C, G, and T are accepted.
+    pub fn read_fasta_iupac_streaming(file_path: &str, max_iupac_expansions: usize) -> impl Iterator<Item = Arc<BaseSequence>> {
+        Self::read_fasta_captioned_streaming(file_path, max_iupac_expansions).map(|(_, seq)| seq)
+    }
+
+    /// Reads a fasta file with DNA sequences into a vector of (caption, BaseSequence), where the caption is the header line of the record without the leading ">".
+    pub fn read_fasta_with_captions_arc(file_path: &str) -> Vec<(String, Arc<BaseSequence>)> {
+        Self::read_fasta_captioned_streaming(file_path, 0).collect()
+    }
+
+    /// Reads a fasta file lazily and yields (caption, BaseSequence) for every concrete sequence of each record (see `read_fasta_iupac_streaming`).
+    fn read_fasta_captioned_streaming(file_path: &str, max_iupac_expansions: usize) -> impl Iterator<Item = (String, Arc<BaseSequence>)> {
+        let file = Self::open_maybe_gzipped(file_path).unwrap_or_else(|e| panic!("failed to open fasta file {}: {}", file_path, e));
+        FastaStream {
+            path: file_path.to_owned(),
+            lines: BufReader::new(file).lines(),
+            line_number: 0,
+            max_iupac_expansions,
+            caption: String::new(),
+            record: String::new(),
+            record_line_number: 0,
+            expanded_caption: String::new(),
+            expanded: Vec::new().into_iter()
+        }
     }
 
 
+    /// Opens the file `file_path` for reading. The file is decompressed on the fly if `file_path` ends with `GZIP_EXTENSION`, where a truncated gzip stream, e.g., of an interrupted run, ends after its last complete block instead of failing.
+    pub fn open_maybe_gzipped(file_path: &str) -> io::Result<Box<dyn Read>> {
+        let file = File::open(file_path)?;
+        Ok(if file_path.ends_with(GZIP_EXTENSION) { Box::new(TruncatedGzReader(MultiGzDecoder::new(file))) } else { Box::new(file) })
+    }
+
+    /// Creates the file `file_path` for writing, i.e., an existing file is overridden. The file is compressed on the fly if `file_path` ends with `GZIP_EXTENSION`, where the gzip stream is completed when the returned writer is dropped.
+    pub fn create_maybe_gzipped(file_path: &str) -> io::Result<Box<dyn Write>> {
+        let file = OpenOptions::new().write(true).create(true).truncate(true).open(file_path)?;
+        Ok(if file_path.ends_with(GZIP_EXTENSION) { Box::new(GzEncoder::new(file, Compression::default())) } else { Box::new(file) })
+    }
+
     /// Appends a given sequence `seq` to the fasta file `file`. `is_first_entry` denotes whether or not `file` is empty.
-    pub fn append_to_fasta_file_with_caption_arc(file: &mut File, seq: &Arc<BaseSequence>, caption: &str, is_first_entry: bool) {
+    pub fn append_to_fasta_file_with_caption_arc(file: &mut impl Write, seq: &Arc<BaseSequence>, caption: &str, is_first_entry: bool) {
         let mut entry = if is_first_entry {
             String::with_capacity(caption.len() + 1 + seq.len())
         }
@@ -177,6 +370,35 @@ impl BaseSequence {
         file.flush();
     }
 
+    /// Appends a given sequence `seq` to the binary file `file` as a record consisting of `id` (4 bytes, big endian), the length of `seq` in bases (4 bytes, big endian), and the bases of `seq` packed into bytes (see `to_packed_bytes`).
+    pub fn append_to_binary_file_with_id_arc(file: &mut impl Write, seq: &Arc<BaseSequence>, id: u32) {
+        let packed = seq.to_packed_bytes();
+        let mut entry = Vec::with_capacity(8 + packed.len());
+        entry.extend_from_slice(&id.to_be_bytes());
+        entry.extend_from_slice(&(seq.len() as u32).to_be_bytes());
+        entry.extend_from_slice(&packed);
+        file.write_all(&entry).unwrap();
+        file.flush().unwrap();
+    }
+
+    /// Reads a binary file written by `append_to_binary_file_with_id_arc` and returns (id, BaseSequence) for every record in the order of the file. A truncated record at the end of the file, e.g., of an interrupted run, is skipped.
+    pub fn read_binary_with_ids_arc(file_path: &str) -> Vec<(u32, Arc<BaseSequence>)> {
+        let file = Self::open_maybe_gzipped(file_path).unwrap_or_else(|e| panic!("failed to open binary file {}: {}", file_path, e));
+        let mut reader = BufReader::new(file);
+        let mut records = Vec::new();
+        let mut id = [0_u8; 4];
+        let mut len = [0_u8; 4];
+        while reader.read_exact(&mut id).is_ok() && reader.read_exact(&mut len).is_ok() {
+            let len = u32::from_be_bytes(len) as usize;
+            let mut packed = vec![0_u8; len.div_ceil(4)];
+            if reader.read_exact(&mut packed).is_err() {
+                break;
+            }
+            records.push((u32::from_be_bytes(id), Arc::new(Self::from_packed_bytes(&packed, len))));
+        }
+        records
+    }
+
     /// Creates a new BaseSequence by concatinating the two given sloces of DNA bases together.
     pub fn concat_slice(slice_1: &[Base], slice_2: &[Base]) -> BaseSequence {
         let mut result_seq
@@ -194,6 +416,39 @@ impl BaseSequence {
         }
     }
 
+    /// Creates a new BaseSequence by parsing the given string `str`. Unlike `from_str`, fails on the first character that is not a DNA base (lowercase bases are accepted).
+    pub fn try_from_str(str: &str) -> Result<Self, ParseBaseError> {
+        str.as_bytes().iter().enumerate().map(|(position, &byte)| Base::try_from_byte(byte).ok_or(ParseBaseError { byte, position })).collect::<Result<Vec<_>, _>>().map(BaseSequence::new)
+    }
+
+    /// Enumerates all concrete sequences the degenerate DNA string `str` represents (see `Base::iupac_bases` for the IUPAC codes).
+    /// Fails if `str` contains a character that is not an IUPAC code or if it represents more than `max_expansions` concrete sequences.
+    pub fn expand_iupac(str: &str, max_expansions: usize) -> Result<Vec<BaseSequence>, ExpandIupacError> {
+        let options = str.as_bytes().iter().enumerate()
+            .map(|(position, &byte)| Base::iupac_bases(byte).ok_or(ExpandIupacError::InvalidCode(ParseBaseError { byte, position })))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let count = options.iter().fold(1_usize, |count, bases| count.saturating_mul(bases.len()));
+        if count > max_expansions {
+            return Err(ExpandIupacError::TooManyExpansions { count, limit: max_expansions });
+        }
+
+        let mut expansions = vec![Vec::with_capacity(options.len())];
+        for bases in options {
+            if bases.len() == 1 {
+                expansions.iter_mut().for_each(|seq| seq.push(bases[0]));
+            }
+            else {
+                expansions = expansions.into_iter().flat_map(|seq| bases.iter().map(move |&base| {
+                    let mut expanded = seq.clone();
+                    expanded.push(base);
+                    expanded
+                })).collect();
+            }
+        }
+        Ok(expansions.into_iter().map(BaseSequence::new).collect())
+    }
+
     /// Creates a new empty BaseSequence.
     pub fn empty() -> Self {
         BaseSequence {
@@ -207,6 +462,24 @@ impl BaseSequence {
         &self.sequence[start..end]
     }
 
+    /// Returns a slice of the current BaseSequence beginning at `start` and ending at `end`, or None if `start` > `end` or `end` exceeds the length.
+    #[inline(always)]
+    pub fn try_sub_sequence_slice(&self, start: usize, end: usize) -> Option<&[Base]> {
+        self.sequence.get(start..end)
+    }
+
+    /// Returns a new BaseSequence with the bases of the current BaseSequence beginning at `start` and ending at `end`, e.g., the payload after the header. Panics if the range is out of bounds.
+    #[inline]
+    pub fn sub_sequence(&self, start: usize, end: usize) -> BaseSequence {
+        Self::from_slice(self.sub_sequence_slice(start, end))
+    }
+
+    /// Returns a new BaseSequence with the bases of the current BaseSequence beginning at `start` and ending at `end`, or None if `start` > `end` or `end` exceeds the length.
+    #[inline]
+    pub fn try_sub_sequence(&self, start: usize, end: usize) -> Option<BaseSequence> {
+        self.try_sub_sequence_slice(start, end).map(Self::from_slice)
+    }
+
     /// Returns a slice of the current BaseSequence.
     #[inline]
     pub fn as_slice(&self) -> &[Base] {
@@ -220,6 +493,7 @@ impl BaseSequence {
     }
 
     /// Returns the count of `search_seq` in the `source`. If the parameter `consecutive` is set true, only consecutive repeats of `search_seq` will be counted.
+    /// Non-overlapping occurrences are counted from left to right, including an occurrence that ends at the last base of `source`.
     #[inline(always)]
     pub fn search_count_of(source: &[Base], search_seq: &[Base], consecutive: bool) -> usize {
         let m_slice = source;
@@ -235,7 +509,7 @@ impl BaseSequence {
         let mut max_consecutive_count = 0;
         let mut consecutive_count = 0;
 
-        while end < m_slice.len() {
+        while end <= m_slice.len() {
             if m_slice[start..end].eq(slice) {
                 count += 1;
                 consecutive_count += 1;
@@ -264,6 +538,19 @@ impl BaseSequence {
         self.sequence.iter().map(|b| b.to_string()).collect()
     }
 
+    /// Returns the canonical k-mers as a set, where the canonical k-mer is the lexicographically smaller of a k-mer and its reverse complement. Hence, a sequence and its reverse complement have the same canonical k-mers.
+    pub fn canonical_k_mers_set(&self, len: usize) -> HashSet<Vec<Base>> {
+        if len > self.len() {
+            panic!("cannot create kmers of k={} for seq of len {}", len, self.len());
+        }
+
+        (0..1 + self.len() - len).map(|i| {
+            let k_mer = self.sub_sequence_slice(i, i + len);
+            let rc = k_mer.iter().rev().map(|base| base.complement()).collect::<Vec<_>>();
+            if rc.as_slice() < k_mer { rc } else { k_mer.to_vec() }
+        }).collect::<HashSet<_>>()
+    }
+
     /// Returns the number of DNA bases in the current BaseSequence.
     #[inline(always)]
     pub fn len(&self) -> usize {
@@ -283,6 +570,30 @@ impl BaseSequence {
         1_f64 - (intersection_size as f64 / union_size as f64)
     }
 
+    /// Calculates the Jaccard distance of the canonical k-mers (see `canonical_k_mers_set`) of the current BaseSequence to `to` using the k-mer length of the specified `k`.
+    #[inline]
+    pub fn canonical_jaccard_distance_arc(&self, to: &Arc<BaseSequence>, k: usize) -> f64 {
+        let my_shingles = self.canonical_k_mers_set(k);
+        let that_shingles = to.canonical_k_mers_set(k);
+        let intersection_size = my_shingles.intersection(&that_shingles).count();
+        let union_size = my_shingles.union(&that_shingles).count();
+
+        1_f64 - (intersection_size as f64 / union_size as f64)
+    }
+
+    /// Calculates the weighted Jaccard distance of the k-mer multisets (see `k_mer_counts`) of the current BaseSequence to `to` using the k-mer length of the specified `k`, i.e., 1 - Σ min(counts) / Σ max(counts) over all k-mers.
+    /// Unlike the Jaccard distance, it tells a k-mer that is repeated apart from one that occurs once.
+    #[inline]
+    pub fn weighted_jaccard_distance_arc(&self, to: &Arc<BaseSequence>, k: usize) -> f64 {
+        weighted_jaccard_distance(&self.k_mer_counts(k), &to.k_mer_counts(k))
+    }
+
+    /// Calculates the weighted Jaccard distance of the canonical k-mer multisets (see `canonical_k_mer_counts`) of the current BaseSequence to `to` using the k-mer length of the specified `k`.
+    #[inline]
+    pub fn canonical_weighted_jaccard_distance_arc(&self, to: &Arc<BaseSequence>, k: usize) -> f64 {
+        weighted_jaccard_distance(&self.canonical_k_mer_counts(k), &to.canonical_k_mer_counts(k))
+    }
+
     /// Calculates the normalized Edit distance of the current BaseSequence to `to`.
     #[inline]
     pub fn edit_distance_arc(&self, to: &Arc<BaseSequence>) -> f64 {
@@ -290,6 +601,21 @@ impl BaseSequence {
         self.levenshtein_distance_arc(to, max_len) as f64 / max_len as f64
     }
 
+    /// Calculates the Hamming distance of the current BaseSequence to `to`. Returns None if the sequences differ in length.
+    #[inline]
+    pub fn hamming_distance_arc(&self, to: &Arc<BaseSequence>) -> Option<usize> {
+        if self.len() != to.len() {
+            return None;
+        }
+        Some(self.sequence.iter().zip(to.sequence.iter()).filter(|(a, b)| a != b).count())
+    }
+
+    /// Calculates the normalized Hamming distance of the current BaseSequence to `to`, i.e., the Hamming distance divided by the length. Returns None if the sequences differ in length.
+    #[inline]
+    pub fn normalized_hamming_distance_arc(&self, to: &Arc<BaseSequence>) -> Option<f64> {
+        self.hamming_distance_arc(to).map(|dist| if self.len() == 0 { 0_f64 } else { dist as f64 / self.len() as f64 })
+    }
+
     /// Calculates the Edit distance of the current BaseSequence to `to`.
     #[inline(always)]
     fn levenshtein_distance_arc(&self, seq: &Arc<BaseSequence>, max_len: usize) -> usize {
@@ -333,9 +659,38 @@ impl BaseSequence {
         }
     }
 
+    /// Returns a new BaseSequence that is the reverse complement of the current BaseSequence, i.e., the sequence of the opposite strand read in 5' to 3' direction.
+    #[inline]
+    pub fn reverse_complement(&self) -> Self {
+        Self {
+            sequence: self.sequence.iter().rev().map(|base| base.complement()).collect()
+        }
+    }
+
     #[inline(always)]
     pub fn gc_of(sequence: &[Base]) -> f64 {
-        sequence.iter().filter(|c| c.is_c_or_g()).count() as f64 / sequence.len() as f64
+        let counts = Self::count_bases_of(sequence);
+        (counts[C as usize] + counts[G as usize]) as f64 / sequence.len() as f64
+    }
+
+    /// Returns the number of occurrences of every base in `sequence`, indexed by `Base as usize` (i.e., A, C, G, T).
+    #[inline(always)]
+    pub fn count_bases_of(sequence: &[Base]) -> [usize; 4] {
+        let mut counts = [0_usize; 4];
+        sequence.iter().for_each(|&base| counts[base as usize] += 1);
+        counts
+    }
+
+    /// Returns the number of occurrences of every base in the current BaseSequence, indexed by `Base as usize` (i.e., A, C, G, T).
+    #[inline(always)]
+    pub fn count_bases(&self) -> [usize; 4] {
+        Self::count_bases_of(self.sequence.as_slice())
+    }
+
+    /// Returns the share of every base in the current BaseSequence, indexed by `Base as usize` (i.e., A, C, G, T). The shares sum up to 1, or are all 0 for an empty sequence.
+    pub fn base_frequencies(&self) -> [f64; 4] {
+        let len = self.sequence.len();
+        self.count_bases().map(|count| if len == 0 { 0_f64 } else { count as f64 / len as f64 })
     }
 
     #[inline(always)]
@@ -343,6 +698,81 @@ impl BaseSequence {
         Self::gc_of(self.sequence.as_slice())
     }
 
+    /// Returns the melting temperature (in °C) of the current BaseSequence.
+    /// Sequences shorter than 14 bases use the Wallace rule Tm = 2 * (A + T) + 4 * (G + C), and longer sequences use Tm = 64.9 + 41 * (G + C - 16.4) / N,
+    /// where A, C, G, and T are the counts of the bases and N is the length. Returns 0 for an empty sequence.
+    pub fn melting_temp(&self) -> f64 {
+        let n = self.sequence.len();
+        if n == 0 {
+            return 0_f64;
+        }
+        let gc = self.sequence.iter().filter(|b| b.is_c_or_g()).count() as f64;
+        if n < 14 {
+            2_f64 * (n as f64 - gc) + 4_f64 * gc
+        }
+        else {
+            64.9_f64 + 41_f64 * (gc - 16.4_f64) / n as f64
+        }
+    }
+
+    /// Returns the Shannon entropy (in bits) of the dinucleotide frequencies of the current BaseSequence, i.e., of its overlapping 2-mers.
+    /// The entropy ranges from 0 for a homopolymer to 4 if all 16 dinucleotides are equally frequent. Low values indicate low-complexity sequences, e.g., repeated dinucleotides. Returns 0 for sequences shorter than 2 bases.
+    pub fn dinucleotide_entropy(&self) -> f64 {
+        if self.sequence.len() < 2 {
+            return 0_f64;
+        }
+        let mut counts = [0_usize; 16];
+        for pair in self.sequence.windows(2) {
+            counts[(pair[0] as usize) << 2 | pair[1] as usize] += 1;
+        }
+        let total = (self.sequence.len() - 1) as f64;
+        counts.iter().filter(|&&count| count > 0).map(|&count| count as f64 / total * (total / count as f64).log2()).sum()
+    }
+
+    /// Returns the length of the longest stem a hairpin of the current BaseSequence can form, i.e., the longest run of consecutive Watson-Crick pairs (i, j), (i + 1, j - 1), ... whose innermost pair encloses a loop of at least `min_loop` bases.
+    /// This is a crude but fast local heuristic for secondary structures that ignores stacking energies and mismatches within a stem, e.g., a pre-filter before querying the dg server. Runs in O(n²) time for a sequence of length n.
+    pub fn max_self_complementary_run(&self, min_loop: usize) -> usize {
+        let n = self.sequence.len();
+        let mut longest = 0_usize;
+        // the pairs (i, j) with i + j = sum form a diagonal along which a stem extends inwards
+        for sum in 1..(2 * n).saturating_sub(2) {
+            let mut current = 0_usize;
+            let mut i = sum.saturating_sub(n - 1);
+            while i < sum - i && sum - i - i > min_loop {
+                if self.sequence[i].complement() == self.sequence[sum - i] {
+                    current += 1;
+                    longest = longest.max(current);
+                }
+                else {
+                    current = 0_usize;
+                }
+                i += 1;
+            }
+        }
+
+        longest
+    }
+
+    /// Returns the minimum and maximum GC content over all sliding windows of size `window` in the current BaseSequence. Falls back to the global GC content if `window` is 0 or greater than the sequence's length.
+    pub fn gc_window(&self, window: usize) -> (f64, f64) {
+        if window == 0 || window >= self.len() {
+            let gc = self.gc();
+            return (gc, gc);
+        }
+
+        let mut count = self.sequence[..window].iter().filter(|b| b.is_c_or_g()).count();
+        let mut min_count = count;
+        let mut max_count = count;
+        for i in window..self.len() {
+            count += self.sequence[i].is_c_or_g() as usize;
+            count -= self.sequence[i - window].is_c_or_g() as usize;
+            min_count = min_count.min(count);
+            max_count = max_count.max(count);
+        }
+
+        (min_count as f64 / window as f64, max_count as f64 / window as f64)
+    }
+
 
     /// Returns the length of the longest homopolymer in the current BaseSequence.
     #[inline(always)]
@@ -363,9 +793,425 @@ impl BaseSequence {
 
         usize::max(current, longest)
     }
+
+    /// Returns the length of the longest homopolymer of every base in the current BaseSequence, indexed by `Base as usize` (i.e., A, C, G, T). A base that does not occur has length 0.
+    pub fn longest_hp_per_base(&self) -> [usize; 4] {
+        let mut longest = [0_usize; 4];
+        let mut current = 0_usize;
+        let mut previous = None;
+
+        for &base in self.sequence.iter() {
+            current = if previous == Some(base) { current + 1 } else { 1 };
+            longest[base as usize] = usize::max(current, longest[base as usize]);
+            previous = Some(base);
+        }
+
+        longest
+    }
 }
 
+/// Returns the weighted Jaccard distance of the k-mer multisets `a` and `b`, i.e., 1 - Σ min(counts) / Σ max(counts) over all k-mers of both multisets.
+fn weighted_jaccard_distance(a: &HashMap<Vec<Base>, usize>, b: &HashMap<Vec<Base>, usize>) -> f64 {
+    let intersection_size = a.iter().map(|(k_mer, &count)| usize::min(count, b.get(k_mer).copied().unwrap_or(0_usize))).sum::<usize>();
+    let union_size = a.values().sum::<usize>() + b.values().sum::<usize>() - intersection_size; // Σ max = Σ a + Σ b - Σ min
 
+    1_f64 - (intersection_size as f64 / union_size as f64)
+}
 
+/// Builds a BaseSequence by appending slices of DNA bases while maintaining the GC count and the homopolymer runs, i.e., `gc` and `longest_hp` of the sequence built so far take constant time.
+/// The results are identical to `BaseSequence::gc` and `BaseSequence::longest_hp` of the built sequence.
+pub struct SequenceBuilder {
+    seq: BaseSequence,
+    gc_count: usize,   // the number of Cs and Gs
+    current_hp: usize, // the length of the homopolymer at the end of the sequence
+    longest_hp: usize  // the length of the longest homopolymer (1 for an empty sequence, as for `BaseSequence::longest_hp`)
+}
 
+impl SequenceBuilder {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates a new empty SequenceBuilder with space for `capacity` bases.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            seq: BaseSequence::new(Vec::with_capacity(capacity)),
+            gc_count: 0,
+            current_hp: 0,
+            longest_hp: 1
+        }
+    }
+
+    /// Appends `slice` to the sequence and updates the GC count and homopolymer runs.
+    pub fn append_slice(&mut self, slice: &[Base]) {
+        for &base in slice {
+            self.gc_count += base.is_c_or_g() as usize;
+            self.current_hp = match self.seq.sequence.last() {
+                Some(&last) if last == base => self.current_hp + 1,
+                _ => 1
+            };
+            self.longest_hp = self.longest_hp.max(self.current_hp);
+            self.seq.sequence.push(base);
+        }
+    }
+
+    /// Returns the GC content of the sequence built so far.
+    #[inline(always)]
+    pub fn gc(&self) -> f64 {
+        self.gc_count as f64 / self.seq.len() as f64
+    }
 
+    /// Returns the length of the longest homopolymer of the sequence built so far.
+    #[inline(always)]
+    pub fn longest_hp(&self) -> usize {
+        self.longest_hp
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.seq.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.seq.len() == 0
+    }
+
+    /// Returns the sequence built so far.
+    pub fn into_sequence(self) -> BaseSequence {
+        self.seq
+    }
+}
+
+impl Default for SequenceBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The error returned when a string contains a character that is not a DNA base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseBaseError {
+    pub byte: u8,
+    pub position: usize
+}
+
+impl fmt::Display for ParseBaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid base {:?} (0x{:02x}) at position {}", self.byte as char, self.byte, self.position)
+    }
+}
+
+impl std::error::Error for ParseBaseError {}
+
+/// The error returned when a degenerate DNA string cannot be expanded into concrete sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpandIupacError {
+    /// The string contains a character that is not an IUPAC nucleotide code.
+    InvalidCode(ParseBaseError),
+    /// The string represents more than `limit` concrete sequences (`count` saturates at usize::MAX).
+    TooManyExpansions { count: usize, limit: usize }
+}
+
+impl fmt::Display for ExpandIupacError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpandIupacError::InvalidCode(e) => write!(f, "invalid IUPAC code {:?} (0x{:02x}) at position {}", e.byte as char, e.byte, e.position),
+            ExpandIupacError::TooManyExpansions { count, limit } => write!(f, "the sequence expands to {} concrete sequences, which exceeds the limit of {}", count, limit)
+        }
+    }
+}
+
+impl std::error::Error for ExpandIupacError {}
+
+/// The reader of a gzip stream that treats a stream truncated after a complete block like the end of the stream.
+struct TruncatedGzReader<R: Read>(MultiGzDecoder<R>);
+
+impl<R: Read> Read for TruncatedGzReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(0_usize),
+            result => result
+        }
+    }
+}
+
+/// The iterator that yields the records of a fasta file one after another.
+/// If `max_iupac_expansions` is 0, every record must consist of the bases A, C, G, and T. Otherwise, records may contain IUPAC codes and every concrete sequence of a record is yielded.
+struct FastaStream<R: BufRead> {
+    path: String,
+    lines: Lines<R>,
+    line_number: usize,
+    max_iupac_expansions: usize,
+    caption: String,
+    record: String,
+    record_line_number: usize,
+    expanded_caption: String,
+    expanded: std::vec::IntoIter<BaseSequence>
+}
+
+impl<R: BufRead> FastaStream<R> {
+    /// Converts the accumulated record into its concrete sequences, which are yielded next together with the record's caption, and clears it.
+    fn finish_record(&mut self) {
+        let record = std::mem::take(&mut self.record);
+        let seqs = if self.max_iupac_expansions == 0 {
+            vec![BaseSequence::from_str(record.as_str())]
+        }
+        else {
+            BaseSequence::expand_iupac(record.as_str(), self.max_iupac_expansions)
+                .unwrap_or_else(|e| panic!("failed to expand the record of fasta file {} starting at line {}: {}", self.path, self.record_line_number, e))
+        };
+        self.expanded_caption = self.caption.clone();
+        self.expanded = seqs.into_iter();
+    }
+}
+
+impl<R: BufRead> Iterator for FastaStream<R> {
+    type Item = (String, Arc<BaseSequence>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(seq) = self.expanded.next() {
+                return Some((self.expanded_caption.clone(), Arc::new(seq)));
+            }
+            match self.lines.next() {
+                Some(line) => {
+                    self.line_number += 1;
+                    let line = line.unwrap_or_else(|e| panic!("failed to read fasta file {}: {}", self.path, e));
+                    let line = line.trim_end();
+                    if let Some(caption) = line.strip_prefix('>') {
+                        // a new record starts -> yield the previous one (records without bases are skipped)
+                        if !self.record.is_empty() {
+                            self.finish_record();
+                        }
+                        self.caption = caption.to_owned();
+                    }
+                    else if !line.is_empty() {
+                        let invalid = line.as_bytes().iter().enumerate().find(|(_, &byte)| match self.max_iupac_expansions {
+                            0 => Base::try_from_byte(byte).is_none(),
+                            _ => Base::iupac_bases(byte).is_none()
+                        });
+                        if let Some((position, &byte)) = invalid {
+                            panic!("failed to parse fasta file {} at line {}: {}", self.path, self.line_number, ParseBaseError { byte, position });
+                        }
+                        if self.record.is_empty() {
+                            self.record_line_number = self.line_number;
+                        }
+                        self.record.push_str(line.to_ascii_uppercase().as_str());
+                    }
+                }
+                None => {
+                    if self.record.is_empty() {
+                        return None;
+                    }
+                    self.finish_record();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bases(s: &str) -> Vec<Base> {
+        BaseSequence::try_from_str(s).unwrap().as_slice().to_vec()
+    }
+
+    #[test]
+    fn sequence_builder_maintains_gc_and_longest_hp() {
+        let seq = bases("GGGATTTTCAACGCCCCCTA");
+        for chunk_len in 1_usize..5 {
+            let mut builder = SequenceBuilder::new();
+            for chunk in seq.chunks(chunk_len) {
+                builder.append_slice(chunk);
+                let built = BaseSequence::new(seq[..builder.len()].to_vec());
+                assert_eq!(builder.gc(), built.gc());
+                assert_eq!(builder.longest_hp(), built.longest_hp());
+            }
+            assert_eq!(builder.into_sequence().as_slice(), seq.as_slice());
+        }
+        assert!(SequenceBuilder::default().is_empty());
+        assert_eq!(SequenceBuilder::default().longest_hp(), BaseSequence::new(vec![]).longest_hp());
+    }
+
+    #[test]
+    fn search_count_of_counts_occurrences_at_the_end() {
+        assert_eq!(BaseSequence::search_count_of(&bases("TTACG"), &bases("ACG"), false), 1_usize);
+        assert_eq!(BaseSequence::search_count_of(&bases("ACG"), &bases("ACG"), false), 1_usize);
+        assert_eq!(BaseSequence::search_count_of(&bases("ACGTACG"), &bases("ACG"), false), 2_usize);
+        assert_eq!(BaseSequence::search_count_of(&bases("AC"), &bases("ACG"), false), 0_usize);
+    }
+
+    #[test]
+    fn search_count_of_counts_consecutive_repeats() {
+        assert_eq!(BaseSequence::search_count_of(&bases("ACACTAC"), &bases("AC"), false), 3_usize);
+        assert_eq!(BaseSequence::search_count_of(&bases("ACACTAC"), &bases("AC"), true), 2_usize);
+        assert_eq!(BaseSequence::search_count_of(&bases("TACACAC"), &bases("AC"), true), 3_usize);
+        assert_eq!(BaseSequence::search_count_of(&bases("AAAA"), &bases("AA"), false), 2_usize);
+    }
+
+    fn seq(s: &str) -> BaseSequence {
+        BaseSequence::try_from_str(s).unwrap()
+    }
+
+    #[test]
+    fn mutators_match_the_non_mutating_variants() {
+        let original = seq("AACGTTTG");
+        let mut reversed = original.clone();
+        reversed.reverse_in_place();
+        assert_eq!(reversed, seq("GTTTGCAA"));
+        let mut complemented = original.clone();
+        complemented.complement_in_place();
+        assert_eq!(complemented, original.complement());
+        complemented.reverse_in_place();
+        assert_eq!(complemented, original.reverse_complement());
+
+        let mut mutated = original.clone();
+        mutated.set_base(0_usize, Base::G);
+        mutated.set_base(7_usize, Base::C);
+        assert_eq!(mutated, seq("GACGTTTC"));
+        mutated.truncate(3_usize);
+        assert_eq!(mutated, seq("GAC"));
+        mutated.truncate(10_usize);
+        assert_eq!(mutated, seq("GAC"));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot set base at index 3")]
+    fn set_base_rejects_indices_out_of_bounds() {
+        seq("ACG").set_base(3_usize, Base::A);
+    }
+
+    /// Returns a path in the temporary directory that is unique to this test process.
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("rqpap_base_sequence_{}_{}", std::process::id(), name)).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn wrapped_fasta_records_are_read_as_single_sequences() {
+        let path = temp_path("wrapped.fa");
+        std::fs::write(&path, ">probe 1\nACGTACGTAC\nGTTGCA\r\n\n>probe 2\nttgca\n>empty\n>probe 3\nGGGG\nCC\nA").unwrap();
+        let records = BaseSequence::read_fasta_with_captions_arc(&path);
+        let expected = [("probe 1", "ACGTACGTACGTTGCA"), ("probe 2", "TTGCA"), ("probe 3", "GGGGCCA")];
+        assert_eq!(records.len(), expected.len());
+        for ((caption, record), (expected_caption, expected_seq)) in records.iter().zip(expected.iter()) {
+            assert_eq!(caption, expected_caption);
+            assert_eq!(record.as_ref(), &seq(expected_seq));
+        }
+        assert_eq!(BaseSequence::read_fasta_arc(&path).len(), 3_usize);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "at line 3")]
+    fn fasta_reports_the_line_of_an_invalid_base() {
+        let path = temp_path("invalid.fa");
+        std::fs::write(&path, ">probe\nACGT\nACXT\n").unwrap();
+        let result = std::panic::catch_unwind(|| BaseSequence::read_fasta_arc(&path));
+        std::fs::remove_file(&path).unwrap();
+        std::panic::resume_unwind(result.unwrap_err());
+    }
+
+    #[test]
+    fn melting_temp_matches_hand_computed_values() {
+        // Wallace rule: 2 * (A + T) + 4 * (G + C)
+        assert_eq!(seq("ACGT").melting_temp(), 12_f64);
+        assert_eq!(seq("ACGTACGTACGTA").melting_temp(), 38_f64);
+        // 64.9 + 41 * (G + C - 16.4) / N
+        assert!((seq("ACGTACGTACGTACGTACGT").melting_temp() - 51.78_f64).abs() < 1e-9_f64);
+        assert!((seq("AAAAAAAAAAAAAA").melting_temp() - (64.9_f64 - 41_f64 * 16.4_f64 / 14_f64)).abs() < 1e-9_f64);
+        assert_eq!(BaseSequence::new(vec![]).melting_temp(), 0_f64);
+    }
+
+    #[test]
+    fn packed_bytes_round_trip_for_every_length() {
+        let full = seq("ACGTTGCAACG");
+        for len in 0..=full.len() {
+            let s = BaseSequence::from_slice(&full.as_slice()[..len]);
+            let bytes = s.to_packed_bytes();
+            assert_eq!(bytes.len(), len.div_ceil(4_usize));
+            assert_eq!(BaseSequence::from_packed_bytes(&bytes, len), s);
+        }
+    }
+
+    #[test]
+    fn gzipped_fasta_round_trip() {
+        let seqs = [seq("ACGTTGCAACG"), seq("TTGCA"), seq("GGGGCCCCAAAATTT")].iter().cloned().map(Arc::new).collect::<Vec<_>>();
+        for name in ["info_dna.fa", "info_dna.fa.gz"].iter() {
+            let path = temp_path(name);
+            {
+                let mut file = BaseSequence::create_maybe_gzipped(&path).unwrap();
+                for (i, s) in seqs.iter().enumerate() {
+                    BaseSequence::append_to_fasta_file_with_caption_arc(&mut file, s, format!(">{}", i + 1).as_str(), i == 0_usize);
+                }
+            }
+            let compressed = std::fs::read(&path).unwrap().starts_with(&[0x1F_u8, 0x8B_u8]);
+            assert_eq!(compressed, name.ends_with(GZIP_EXTENSION));
+            assert_eq!(BaseSequence::read_fasta_arc(&path), seqs);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn truncated_gzip_stream_ends_after_the_flushed_records() {
+        let seqs = [seq("ACGTTGCAACG"), seq("TTGCA")].iter().cloned().map(Arc::new).collect::<Vec<_>>();
+        let path = temp_path("interrupted.fa.gz");
+        {
+            let mut file = BaseSequence::create_maybe_gzipped(&path).unwrap();
+            for (i, s) in seqs.iter().enumerate() {
+                BaseSequence::append_to_fasta_file_with_caption_arc(&mut file, s, format!(">{}", i + 1).as_str(), i == 0_usize);
+            }
+        }
+        // cut off the trailer (CRC-32 and size), as if the run was interrupted before the gzip stream was completed
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 8_u64).unwrap();
+        assert_eq!(BaseSequence::read_fasta_arc(&path), seqs);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn dinucleotide_entropy_ranges_from_homopolymers_to_balanced_sequences() {
+        assert_eq!(seq("AAAAAAAAAA").dinucleotide_entropy(), 0_f64);
+        // a de Bruijn sequence contains each of the 16 dinucleotides exactly once
+        assert!((seq("AACAGATCCGCTGGTTA").dinucleotide_entropy() - 4_f64).abs() < 1e-9_f64);
+        // a repeated dinucleotide consists of 2 alternating dinucleotides, i.e., at most 1 bit
+        assert!(seq("ACACACACACAC").dinucleotide_entropy() <= 1_f64);
+        assert_eq!(seq("A").dinucleotide_entropy(), 0_f64);
+    }
+
+    #[test]
+    fn k_mer_counts_keep_repeated_k_mers_apart() {
+        let counts = seq("ACACACACAC").k_mer_counts(2_usize);
+        assert_eq!(counts.len(), 2_usize);
+        assert_eq!(counts[&vec![Base::A, Base::C]], 5_usize);
+        assert_eq!(counts[&vec![Base::C, Base::A]], 4_usize);
+        assert_eq!(seq("ACGTA").k_mer_counts(5_usize).values().copied().collect::<Vec<_>>(), vec![1_usize]);
+
+        let canonical = seq("AAAC").canonical_k_mer_counts(2_usize);
+        assert_eq!(canonical, seq("GTTT").canonical_k_mer_counts(2_usize));
+        assert_eq!(canonical[&vec![Base::A, Base::A]], 2_usize);
+        assert_eq!(canonical[&vec![Base::A, Base::C]], 1_usize);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot create kmers")]
+    fn k_mer_counts_panic_for_too_long_k_mers() {
+        seq("ACG").k_mer_counts(4_usize);
+    }
+
+    #[test]
+    fn weighted_jaccard_tells_repeated_k_mers_apart() {
+        let repetitive = seq("ACACACACAC");
+        let short = Arc::new(seq("ACAC"));
+        assert_eq!(repetitive.jaccard_distance_arc(&short, 2_usize), 0_f64);
+        assert!((repetitive.weighted_jaccard_distance_arc(&short, 2_usize) - 2_f64 / 3_f64).abs() < 1e-12_f64); // 1 - (2 + 1) / (5 + 4)
+        assert_eq!(repetitive.weighted_jaccard_distance_arc(&Arc::new(repetitive.clone()), 2_usize), 0_f64);
+        assert_eq!(repetitive.weighted_jaccard_distance_arc(&Arc::new(seq("GGTTGG")), 2_usize), 1_f64);
+
+        let revcomp = Arc::new(seq("GTTT"));
+        assert_eq!(seq("AAAC").weighted_jaccard_distance_arc(&revcomp, 2_usize), 1_f64);
+        assert_eq!(seq("AAAC").canonical_weighted_jaccard_distance_arc(&revcomp, 2_usize), 0_f64);
+    }
+}
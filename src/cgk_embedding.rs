@@ -0,0 +1,89 @@
+use crate::base_sequence::{Base, BaseSequence};
+use rand::Rng;
+
+pub static CGK_PAD_SYMBOL: usize              = 4_usize;             // the symbol of an embedding position after the end of the sequence (the bases are 0 to 3)
+static CGK_EXPANSION: usize                   = 3_usize;             // the length of an embedding relative to the length of the embedded sequence
+static CGK_SAMPLED_EXPANSION: usize           = 2_usize;             // the length of the sampled part of an embedding relative to the length of the embedded sequence (a walk covers about 2 steps per base)
+
+/// A randomized embedding of the edit distance into the Hamming distance (Chakraborty, Goldenberg, and Koucký, STOC 2016) restricted to a set of sampled positions, i.e., a hash function family for bit sampling LSH.
+/// The embedding walks over a sequence with one step per position: it outputs the current base and advances to the next base if the random bit of the step and the current base is set, i.e., with a probability of 1/2.
+/// Hence, two sequences that differ by an indel resynchronize after a few steps, and their embeddings differ in O(d²) positions for an edit distance d (w.h.p.), whereas the k-mers of MinHash only tolerate the edit operations as a side effect.
+#[derive(Eq, PartialEq, Clone, Debug, Hash)]
+pub struct CgkEmbedding {
+    bits: Vec<u8>,        // the random bits of every step, i.e., bit `b` of `bits[t]` determines whether step `t` advances over base `b`
+    positions: Vec<usize> // the sorted sampled positions of the embedding
+}
+
+impl CgkEmbedding {
+    /// Creates a CgkEmbedding instance whose random bits and sampled positions are drawn from `rng`. Use a seeded `rng` to obtain reproducible embeddings.
+    /// # Arguments
+    /// * `len` - The length of the sequences to embed. Positions are sampled from 0..2 * `len`, i.e., the part of the embedding a walk over `len` bases is expected to cover, since the padding after the end of two sequences agrees regardless of their similarity.
+    /// * `count` - The number of sampled positions, i.e., the number of hash functions.
+    /// * `rng` - The random number generator used to draw the bits and the positions.
+    pub fn new_with_rng(len: usize, count: usize, rng: &mut impl Rng) -> Self {
+        if len == 0_usize {
+            panic!("the length of the embedded sequences must be positive");
+        }
+
+        let steps = CGK_EXPANSION * len;
+        let bits = (0..steps).map(|_| rng.gen_range(0_u8..16_u8)).collect::<Vec<_>>();
+        let mut positions = (0..count).map(|_| rng.gen_range(0..CGK_SAMPLED_EXPANSION * len)).collect::<Vec<_>>();
+        positions.sort_unstable();
+        CgkEmbedding {
+            bits,
+            positions
+        }
+    }
+
+    /// Restores a CgkEmbedding instance from its random bits and sampled positions, e.g., those of a previously persisted instance. Panics if a position is out of bounds.
+    pub fn from_parts(bits: Vec<u8>, mut positions: Vec<usize>) -> Self {
+        if positions.iter().any(|&position| position >= bits.len()) {
+            panic!("a sampled position exceeds the embedding of {} steps", bits.len());
+        }
+        positions.sort_unstable();
+        CgkEmbedding {
+            bits,
+            positions
+        }
+    }
+
+    /// Returns the length of the sequences this embedding was created for.
+    pub fn len(&self) -> usize {
+        self.bits.len() / CGK_EXPANSION
+    }
+
+    /// Returns true if this embedding was created for empty sequences.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0_usize
+    }
+
+    pub fn bits(&self) -> &[u8] {
+        &self.bits
+    }
+
+    pub fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    /// Returns the symbols of the embedding of `seq` at the sampled positions, i.e., the base (0 to 3) at each position, or `CGK_PAD_SYMBOL` if the walk reached the end of `seq` before that position.
+    pub fn apply(&self, seq: &BaseSequence) -> Vec<usize> {
+        let bases = seq.as_slice();
+        let mut symbols = Vec::with_capacity(self.positions.len());
+        let mut i = 0_usize;
+        let mut step = 0_usize;
+        for &position in self.positions.iter() {
+            while step < position && i < bases.len() {
+                i += Self::advances(self.bits[step], bases[i]) as usize;
+                step += 1;
+            }
+            symbols.push(if i < bases.len() { bases[i] as usize } else { CGK_PAD_SYMBOL });
+        }
+        symbols
+    }
+
+    /// Returns true if a step with the random bits `bits` advances over `base`.
+    #[inline(always)]
+    fn advances(bits: u8, base: Base) -> bool {
+        bits >> (base as u8) & 1_u8 == 1_u8
+    }
+}
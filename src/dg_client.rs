@@ -1,64 +1,288 @@
-use std::net::{TcpStream, SocketAddr, IpAddr, Ipv4Addr};
-use std::io::{Read, Write, Error};
-use std::str;
+use std::net::{TcpStream, SocketAddr, Ipv4Addr, Shutdown, ToSocketAddrs};
+use std::io::{Read, Write, Error, ErrorKind};
+use std::{fmt, str};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use std::time::Duration;
 use crate::base_sequence::BaseSequence;
-use crate::safe_cell::SafeCell;
-use parking_lot::{Mutex, RawMutex};
+use parking_lot::{Mutex, RawMutex, Condvar};
 use parking_lot::lock_api::MutexGuard;
 
+pub(crate) static RETRY_BACKOFF_MS: u64 = 100_u64; // the waiting time before the first reconnect attempt (doubled for every further attempt)
+static BATCH_MARKER: u8       = 0_u8;    // the first byte of a batch query (a single query starts with a DNA base instead)
+
+pub static DEFAULT_CONNECT_TIMEOUT_MS: u64 = 3000_u64;  // default timeout in milliseconds for connecting to the dg server
+pub static DEFAULT_READ_TIMEOUT_MS: u64    = 30000_u64; // default timeout in milliseconds for receiving a dg energy (0 = no timeout)
+pub static DEFAULT_MAX_RETRIES: usize      = 3_usize;   // default number of reconnect attempts after the connection to the dg server failed
+pub static DEFAULT_MAX_INFLIGHT: usize     = 0_usize;   // default maximum number of concurrent dg queries (0 = limited by the number of channels only)
+
 pub struct DGClient {
     channels: Vec<ChannelHandler>,
+    inflight: InflightLimiter,
+    next_channel: AtomicUsize // the round-robin cursor of the channel a query starts probing at
 }
 /// The client used to communicate with the dg server.
 impl DGClient {
-    /// Creates a new DGClient instance.
+    /// Creates a new DGClient instance with a channel for every port of `config` that could be connected to. Ports that could not be connected to are skipped, i.e., the client may have fewer channels than ports (see `channel_count`).
+    /// Returns the error of the first port if no port could be connected to.
     /// # Arguments
     /// * The arguments `a`, `b`, `c`, and `d` represent the IP address of the dg server. For example, if the IP is 127.0.0.1, then `a` = 127, `b` = 0, `c` = 0, and `d` = 1.
-    /// * `start_port` - The starting port of the dg server.
-    /// * `count` - The number of ports (including `start_port`).
-    pub fn new(a: u8, b: u8, c: u8, d: u8, start_port: u16, count: u16) -> Option<DGClient> {
-        let channels = (start_port..start_port + count)
-            .map(|port| ChannelHandler::new(a, b, c, d, port))
-            .take_while(|c| c.is_some())
-            .map(|c| c.unwrap())
-            .collect::<Vec<_>>();
-        if channels.len() == count as usize {
-            Some(DGClient {
-                channels
-            })
+    /// * `config` - The ports of the dg server, the limit of the queries in flight, the timeouts, and the retries of every channel.
+    pub fn new(a: u8, b: u8, c: u8, d: u8, config: &DGClientConfig) -> Result<DGClient, DGClientError> {
+        Self::from_host(Ipv4Addr::new(a, b, c, d).to_string().as_str(), config)
+    }
+
+    /// Creates a new DGClient instance like `new`, but for a dg server given by its `host`, i.e., a hostname (e.g., "localhost") or an IPv4 or IPv6 address (e.g., "10.0.0.2" or "::1"). The host is resolved for every port, and a channel connects to the first resolved address that accepts the connection.
+    /// # Arguments
+    /// * `host` - The hostname or IP address of the dg server.
+    /// * `config` - The ports of the dg server, the limit of the queries in flight, the timeouts, and the retries of every channel.
+    pub fn from_host(host: &str, config: &DGClientConfig) -> Result<DGClient, DGClientError> {
+        let mut channels = Vec::with_capacity(config.ports().len());
+        let mut first_error = None;
+        for port in config.ports() {
+            match ChannelHandler::from_host(host, port, config.timeouts(), config.max_retries()) {
+                Ok(channel) => channels.push(channel),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if channels.is_empty() {
+            return Err(first_error.unwrap_or_else(|| no_channels_error(host, config.ports().start)));
         }
-        else {
-            None
+        Ok(DGClient {
+            channels,
+            inflight: InflightLimiter::new(config.max_inflight()),
+            next_channel: AtomicUsize::new(0_usize)
+        })
+    }
+
+    /// Returns the dg energy for a given `seq`. Will loop over all ports (channels) to send the query. Will start at port `from_id`. Returns an error if the dg server did not answer in time or the connection failed.
+    #[inline(always)]
+    pub fn dg_arc_from_id(&self, from_id: usize, seq: &Arc<BaseSequence>, temp: f32) -> Result<f32, Error> {
+        let _permit = self.inflight.acquire();
+        let (channel, locked) = self.lock_free_channel(from_id);
+        channel.query_with_retries(locked, |stream| ChannelHandler::send_seq_receive_dg_arc_lock_free(stream, seq, temp))
+    }
+
+    /// Returns the dg energies for all `seqs` (in the same order) by sending them to the dg server in a single round-trip. The wire format of a batch query is documented in the README.
+    pub fn dg_arc_batch(&self, seqs: &[Arc<BaseSequence>], temp: f32) -> Result<Vec<f32>, Error> {
+        if seqs.is_empty() {
+            return Ok(vec![]);
         }
+        let _permit = self.inflight.acquire();
+        let (channel, locked) = self.lock_free_channel(self.next_channel_id());
+        channel.query_with_retries(locked, |stream| ChannelHandler::send_seqs_receive_dgs_lock_free(stream, seqs, temp))
     }
 
-    /// Returns the dg energy for a given `seq`. Will loop over all ports (channels) to send the query. Will start at port `from_id`.
+    /// Returns the first channel that is not locked, starting at channel `from_id`, together with its locked stream.
     #[inline(always)]
-    pub fn dg_arc_from_id(&self, mut from_id: usize, seq: &Arc<BaseSequence>, temp: f32) -> f32 {
+    fn lock_free_channel(&self, from_id: usize) -> (&ChannelHandler, MutexGuard<'_, RawMutex, TcpStream>) {
         let mut safe_id = from_id % self.channels.len();
         loop {
-            match self.channels.get(safe_id).unwrap().stream.try_lock() {
+            let channel = self.channels.get(safe_id).unwrap();
+            match channel.stream.try_lock() {
                 None => {
                     safe_id = (safe_id + 1) % self.channels.len();
                 }
                 Some(ch) => {
-                    return ChannelHandler::send_seq_receive_dg_arc_lock_free(ch,seq, temp);
+                    return (channel, ch);
                 }
             };
         }
     }
 
-    /// Returns the dg energy for a given `seq`. Will loop over all ports (channels) to send the query. Will start at port `start_port`.
+    /// Returns the id of the channel the next query starts probing at. The ids are handed out round-robin, so that concurrent queries spread evenly across the channels instead of contending for the first one.
     #[inline(always)]
-    pub fn dg_arc(&self, seq: &Arc<BaseSequence>, temp: f32) -> f32 {
-        self.dg_arc_from_id(0_usize, seq, temp)
+    fn next_channel_id(&self) -> usize {
+        self.next_channel.fetch_add(1_usize, Ordering::Relaxed) % self.channels.len()
+    }
+
+    /// Returns the dg energy for a given `seq`. Will loop over all ports (channels) to send the query, starting at the next channel in round-robin order.
+    #[inline(always)]
+    pub fn dg_arc(&self, seq: &Arc<BaseSequence>, temp: f32) -> Result<f32, Error> {
+        self.dg_arc_from_id(self.next_channel_id(), seq, temp)
+    }
+
+    /// Returns the maximum number of concurrent in-flight queries (0 if unbounded).
+    #[inline]
+    pub fn max_inflight(&self) -> usize {
+        self.inflight.max
+    }
+
+    /// Returns the number of channels, i.e., the number of ports that could be connected to.
+    #[inline]
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+}
+
+/// Returns the error of a client for which no channel was requested, i.e., a `count` of 0.
+pub(crate) fn no_channels_error(host: &str, start_port: u16) -> DGClientError {
+    DGClientError {
+        addr: host_port(host, start_port),
+        source: Error::new(ErrorKind::InvalidInput, "no dg channels requested")
+    }
+}
+
+/// Returns the address `host`:`port` as displayed in errors. IPv6 addresses are enclosed in brackets.
+pub(crate) fn host_port(host: &str, port: u16) -> String {
+    match host.contains(':') && !host.starts_with('[') {
+        true  => format!("[{}]:{}", host, port),
+        false => format!("{}:{}", host, port)
+    }
+}
+
+/// A counting semaphore that bounds the number of dg queries in flight independent of the number of channels.
+struct InflightLimiter {
+    max: usize,
+    count: Mutex<usize>,
+    released: Condvar
+}
+
+impl InflightLimiter {
+    /// Creates a new limiter allowing `max` concurrent permits. `max` = 0 disables the limit.
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            count: Mutex::new(0_usize),
+            released: Condvar::new()
+        }
+    }
+
+    /// Blocks until a permit is available and returns it. The permit is released when dropped.
+    #[inline]
+    fn acquire(&self) -> InflightPermit<'_> {
+        if self.max != 0_usize {
+            let mut count = self.count.lock();
+            while *count >= self.max {
+                self.released.wait(&mut count);
+            }
+            *count += 1;
+        }
+        InflightPermit {
+            limiter: self
+        }
+    }
+}
+
+/// A permit handed out by the InflightLimiter.
+struct InflightPermit<'a> {
+    limiter: &'a InflightLimiter
+}
+
+impl Drop for InflightPermit<'_> {
+    fn drop(&mut self) {
+        if self.limiter.max != 0_usize {
+            *self.limiter.count.lock() -= 1;
+            self.limiter.released.notify_one();
+        }
+    }
+}
+
+/// The timeouts used by the channels of a DGClient.
+#[derive(Clone, Copy, Debug)]
+pub struct DGTimeouts {
+    pub connect: Duration,
+    pub read: Duration
+}
+
+impl DGTimeouts {
+    /// Creates new DGTimeouts from milliseconds. A `read_ms` of 0 disables the read timeout.
+    pub fn from_millis(connect_ms: u64, read_ms: u64) -> Self {
+        Self {
+            connect: Duration::from_millis(connect_ms),
+            read: Duration::from_millis(read_ms)
+        }
+    }
+}
+
+/// The configuration of the channels of a DGClient, i.e., the ports of the dg server, the limit of the queries in flight, and the timeouts and retries of every channel. Every parameter that is not set with a `with_*` method has its default value.
+#[derive(Clone, Copy, Debug)]
+pub struct DGClientConfig {
+    start_port: u16,     // the first port of the dg server
+    count: u16,          // the number of ports (including `start_port`)
+    max_inflight: usize, // the maximum number of queries in flight at the same time across all channels (0 = only the number of channels limits the queries)
+    timeouts: DGTimeouts,
+    max_retries: usize   // the maximum number of times a channel reconnects and resends a query after the connection failed
+}
+
+impl DGClientConfig {
+    /// Creates a new DGClientConfig for the `count` ports from `start_port` to `start_port` + `count` - 1.
+    pub fn new(start_port: u16, count: u16) -> Self {
+        Self {
+            start_port,
+            count,
+            max_inflight: DEFAULT_MAX_INFLIGHT,
+            timeouts: DGTimeouts::from_millis(DEFAULT_CONNECT_TIMEOUT_MS, DEFAULT_READ_TIMEOUT_MS),
+            max_retries: DEFAULT_MAX_RETRIES
+        }
+    }
+
+    /// Returns this configuration with the maximum number of queries that may be in flight at the same time across all channels. 0 means that only the number of channels limits the queries.
+    pub fn with_max_inflight(mut self, max_inflight: usize) -> Self {
+        self.max_inflight = max_inflight;
+        self
+    }
+
+    /// Returns this configuration with the connect and read timeouts of every channel.
+    pub fn with_timeouts(mut self, timeouts: DGTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Returns this configuration with the maximum number of times a channel reconnects and resends a query after the connection failed.
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Returns the ports of the dg server.
+    pub fn ports(&self) -> std::ops::Range<u16> {
+        self.start_port..self.start_port + self.count
+    }
+
+    /// Returns the maximum number of queries in flight at the same time (0 if only the number of channels limits them).
+    pub fn max_inflight(&self) -> usize {
+        self.max_inflight
+    }
+
+    /// Returns the connect and read timeouts of every channel.
+    pub fn timeouts(&self) -> DGTimeouts {
+        self.timeouts
+    }
+
+    /// Returns the maximum number of times a channel reconnects and resends a query.
+    pub fn max_retries(&self) -> usize {
+        self.max_retries
+    }
+}
+
+/// The error returned when a channel to the dg server could not be established, i.e., the address could not be resolved or connected to.
+#[derive(Debug)]
+pub struct DGClientError {
+    pub addr: String, // the address (host and port) of the channel
+    pub source: Error
+}
+
+impl fmt::Display for DGClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to connect to dg server at {}: {}", self.addr, self.source)
+    }
+}
+
+impl std::error::Error for DGClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
     }
 }
 
 pub struct ChannelHandler {
-    stream: Mutex<TcpStream>
+    stream: Mutex<TcpStream>,
+    addr: SocketAddr,
+    timeouts: DGTimeouts,
+    max_retries: usize
 }
 
 impl ChannelHandler {
@@ -66,72 +290,247 @@ impl ChannelHandler {
     /// # Arguments
     /// * The arguments `a`, `b`, `c`, and `d` represent the IP address. For example, if the IP is 127.0.0.1, then `a` = 127, `b` = 0, `c` = 0, and `d` = 1.
     /// * `port` - The port of this channel.
-    pub fn new(a: u8, b: u8, c: u8, d: u8, port: u16) -> Option<ChannelHandler> {
-        let socket = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(a, b, c, d)), port);
-        match TcpStream::connect_timeout(&socket, Duration::from_secs(3)) {
-            Ok(st) => Some(
-                ChannelHandler {
-                    stream: Mutex::new(st)
-            }),
-            Err(_) => None
+    /// * `timeouts` - The connect and read timeouts of this channel.
+    /// * `max_retries` - The maximum number of times this channel reconnects and resends a query after the connection failed.
+    pub fn new(a: u8, b: u8, c: u8, d: u8, port: u16, timeouts: DGTimeouts, max_retries: usize) -> Result<ChannelHandler, DGClientError> {
+        Self::from_host(Ipv4Addr::new(a, b, c, d).to_string().as_str(), port, timeouts, max_retries)
+    }
+
+    /// Creates a single channel (port) like `new`, but for a dg server given by its `host`, i.e., a hostname or an IP address. The channel connects to the first resolved address that accepts the connection and reconnects to that address.
+    /// # Arguments
+    /// * `host` - The hostname or IP address of the dg server.
+    /// * `port` - The port of this channel.
+    /// * `timeouts` - The connect and read timeouts of this channel.
+    /// * `max_retries` - The maximum number of times this channel reconnects and resends a query after the connection failed.
+    pub fn from_host(host: &str, port: u16, timeouts: DGTimeouts, max_retries: usize) -> Result<ChannelHandler, DGClientError> {
+        let to_error = |e| DGClientError {
+            addr: host_port(host, port),
+            source: e
+        };
+        let mut last_error = Error::new(ErrorKind::NotFound, "the host did not resolve to any address");
+        for socket in (host, port).to_socket_addrs().map_err(to_error)? {
+            match Self::connect(&socket, timeouts) {
+                Ok(st) => return Ok(
+                    ChannelHandler {
+                        stream: Mutex::new(st),
+                        addr: socket,
+                        timeouts,
+                        max_retries
+                }),
+                Err(e) => last_error = e
+            }
         }
+        Err(to_error(last_error))
     }
 
-    fn send_seq_receive_dg(&mut self, seq: &BaseSequence, temp: f32) -> f32 {
-        let mut locked = self.stream.lock();
-        let mut packet_data: Vec<u8> = Vec::with_capacity(seq.len() + 4 + 1);
-        packet_data.extend_from_slice(seq.to_string().as_bytes());
-        packet_data.push(b',');
-        packet_data.extend_from_slice((temp.to_string()).as_ref());
-        locked.write_all(packet_data.as_slice());
-        locked.flush().unwrap();
+    /// Connects to `addr` and sets the read timeout given by `timeouts`.
+    fn connect(addr: &SocketAddr, timeouts: DGTimeouts) -> Result<TcpStream, Error> {
+        let read_timeout = if timeouts.read.as_millis() > 0 { Some(timeouts.read) } else { None };
+        let st = TcpStream::connect_timeout(addr, timeouts.connect)?;
+        st.set_read_timeout(read_timeout)?;
+        Ok(st)
+    }
+
+    /// Runs `query` on the locked stream. If the query fails (e.g., broken pipe, EOF, or timeout), the channel re-dials the dg server with an exponential backoff and reruns the query up to `max_retries` times before returning the last error.
+    fn query_with_retries<T>(&self, mut locked: MutexGuard<RawMutex, TcpStream>, query: impl Fn(&mut TcpStream) -> Result<T, Error>) -> Result<T, Error> {
+        let mut retries = 0_usize;
+        loop {
+            match query(&mut locked) {
+                Ok(result) => return Ok(result),
+                Err(e) if retries >= self.max_retries => return Err(e),
+                Err(_) => {
+                    thread::sleep(Duration::from_millis(RETRY_BACKOFF_MS << retries.min(16)));
+                    retries += 1;
+                    if let Ok(st) = Self::connect(&self.addr, self.timeouts) {
+                        *locked = st;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends `seq` through the locked stream and returns the received dg energy. On an error (e.g., a read timeout), the stream is shut down, since a late answer would otherwise be read as the answer to the next query.
+    #[inline]
+    fn send_seq_receive_dg_arc_lock_free(locked: &mut TcpStream, seq: &Arc<BaseSequence>, temp: f32) -> Result<f32, Error> {
+        let packet_data = single_query_packet(seq, temp);
         let mut buffer = [0u8; 4];
-        match locked.read_exact(&mut buffer) {
+        match locked.write_all(packet_data.as_slice()).and_then(|_| locked.flush()).and_then(|_| locked.read_exact(&mut buffer)) {
             Ok(_) => {
-                f32::from_le_bytes(buffer)
+                Ok(f32::from_le_bytes(buffer))
             }
-            Err(_) => {
-                0_f32
+            Err(e) => {
+                let _ = locked.shutdown(Shutdown::Both);
+                Err(e)
             }
         }
     }
 
-
-    fn send_seq_receive_dg_arc(&mut self, seq: Arc<BaseSequence>, temp: f32) -> f32 {
-        let mut locked = self.stream.lock();
-        let mut packet_data: Vec<u8> = Vec::with_capacity(seq.len() + 4 + 1);
-        packet_data.extend_from_slice(seq.to_string().as_bytes());
-        packet_data.push(b',');
-        packet_data.extend_from_slice((temp.to_string()).as_ref());
-        locked.write_all(packet_data.as_slice());
-        locked.flush().unwrap();
-        let mut buffer = [0u8; 4];
-        match locked.read_exact(&mut buffer) {
+    /// Sends all `seqs` as a single batch query through the locked stream and returns the received dg energies. On an error, the stream is shut down (see `send_seq_receive_dg_arc_lock_free`).
+    fn send_seqs_receive_dgs_lock_free(locked: &mut TcpStream, seqs: &[Arc<BaseSequence>], temp: f32) -> Result<Vec<f32>, Error> {
+        let packet_data = batch_query_packet(seqs, temp);
+        let mut buffer = vec![0u8; 4 * seqs.len()];
+        match locked.write_all(packet_data.as_slice()).and_then(|_| locked.flush()).and_then(|_| locked.read_exact(&mut buffer)) {
             Ok(_) => {
-                f32::from_le_bytes(buffer)
+                Ok(dgs_from_bytes(&buffer))
             }
-            Err(_) => {
-                0_f32
+            Err(e) => {
+                let _ = locked.shutdown(Shutdown::Both);
+                Err(e)
             }
         }
     }
+}
 
-    #[inline]
-    fn send_seq_receive_dg_arc_lock_free(mut locked: MutexGuard<RawMutex, TcpStream>, seq: &Arc<BaseSequence>, temp: f32) -> f32 {
-        let mut packet_data: Vec<u8> = Vec::with_capacity(seq.len() + 4 + 1);
+/// Returns the bytes of a single query of `seq` at the temperature `temp`, i.e., the sequence followed by a comma and the temperature as ASCII text. The answer is the dg energy as a 4-byte float (little endian).
+pub(crate) fn single_query_packet(seq: &BaseSequence, temp: f32) -> Vec<u8> {
+    let mut packet_data: Vec<u8> = Vec::with_capacity(seq.len() + 4 + 1);
+    packet_data.extend_from_slice(seq.to_string().as_bytes());
+    packet_data.push(b',');
+    packet_data.extend_from_slice((temp.to_string()).as_ref());
+    packet_data
+}
+
+/// Returns the bytes of a batch query of all `seqs` at the temperature `temp` (see the README for the wire format). The answer consists of a dg energy per sequence (see `dgs_from_bytes`).
+pub(crate) fn batch_query_packet(seqs: &[Arc<BaseSequence>], temp: f32) -> Vec<u8> {
+    let mut packet_data: Vec<u8> = Vec::with_capacity(1 + 4 + 4 + seqs.iter().map(|seq| 4 + seq.len()).sum::<usize>());
+    packet_data.push(BATCH_MARKER);
+    packet_data.extend_from_slice(&temp.to_le_bytes());
+    packet_data.extend_from_slice(&(seqs.len() as u32).to_le_bytes());
+    for seq in seqs.iter() {
+        packet_data.extend_from_slice(&(seq.len() as u32).to_le_bytes());
         packet_data.extend_from_slice(seq.to_string().as_bytes());
-        packet_data.push(b',');
-        packet_data.extend_from_slice((temp.to_string()).as_ref());
-        locked.write_all(packet_data.as_slice());
-        locked.flush().unwrap();
-        let mut buffer = [0u8; 4];
-        match locked.read_exact(&mut buffer) {
-            Ok(_) => {
-                f32::from_le_bytes(buffer)
-            }
-            Err(_) => {
-                0_f32
+    }
+    packet_data
+}
+
+/// Returns the dg energies of the answer `bytes` to a batch query, i.e., a 4-byte float (little endian) per sequence.
+pub(crate) fn dgs_from_bytes(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    static DG: f32 = -4.5_f32;
+
+    fn seq() -> Arc<BaseSequence> {
+        Arc::new(BaseSequence::try_from_str("ACGTTGCAACGG").unwrap())
+    }
+
+    /// Starts a fake dg server on a free local port that hands every accepted connection together with its 0-based index to `serve`, and returns the port.
+    fn fake_dg_server(serve: impl Fn(TcpStream, usize) + Send + 'static) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for (i, stream) in listener.incoming().enumerate() {
+                serve(stream.unwrap(), i);
             }
+        });
+        port
+    }
+
+    /// Reads a single query from `stream` and answers it with `DG`. Returns false if the connection was closed.
+    fn answer(stream: &mut TcpStream) -> bool {
+        let mut buffer = [0_u8; 256];
+        stream.read(&mut buffer).unwrap_or(0_usize) > 0_usize && stream.write_all(&DG.to_le_bytes()).is_ok()
+    }
+
+    fn config(port: u16, max_retries: usize, read_ms: u64) -> DGClientConfig {
+        DGClientConfig::new(port, 1_u16).with_timeouts(DGTimeouts::from_millis(1000_u64, read_ms)).with_max_retries(max_retries)
+    }
+
+    #[test]
+    fn inflight_limiter_bounds_concurrent_permits() {
+        let limiter = Arc::new(InflightLimiter::new(2_usize));
+        let current = Arc::new(AtomicUsize::new(0_usize));
+        let peak = Arc::new(AtomicUsize::new(0_usize));
+        let workers = (0..8).map(|_| {
+            let (limiter, current, peak) = (limiter.clone(), current.clone(), peak.clone());
+            thread::spawn(move || {
+                let _permit = limiter.acquire();
+                let now = current.fetch_add(1_usize, Ordering::SeqCst) + 1_usize;
+                peak.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20_u64));
+                current.fetch_sub(1_usize, Ordering::SeqCst);
+            })
+        }).collect::<Vec<_>>();
+        workers.into_iter().for_each(|worker| worker.join().unwrap());
+        assert_eq!(peak.load(Ordering::SeqCst), 2_usize);
+        assert_eq!(*limiter.count.lock(), 0_usize);
+    }
+
+    #[test]
+    fn inflight_limiter_of_zero_is_unbounded() {
+        let limiter = InflightLimiter::new(0_usize);
+        let permits = (0..64).map(|_| limiter.acquire()).collect::<Vec<_>>();
+        assert_eq!(permits.len(), 64_usize);
+    }
+
+    #[test]
+    fn query_is_answered() {
+        let port = fake_dg_server(|mut stream, _| while answer(&mut stream) {});
+        let client = DGClient::from_host("127.0.0.1", &config(port, 0_usize, 1000_u64)).unwrap();
+        assert_eq!(client.channel_count(), 1_usize);
+        assert_eq!(client.dg_arc(&seq(), 37_f32).unwrap(), DG);
+        assert_eq!(client.dg_arc(&seq(), 37_f32).unwrap(), DG);
+    }
+
+    #[test]
+    fn failed_query_is_retried_on_a_new_connection() {
+        // the first connection is closed without an answer, later connections answer
+        let port = fake_dg_server(|mut stream, i| if i > 0_usize { answer(&mut stream); });
+        let client = DGClient::from_host("127.0.0.1", &config(port, 1_usize, 1000_u64)).unwrap();
+        assert_eq!(client.dg_arc(&seq(), 37_f32).unwrap(), DG);
+
+        let port = fake_dg_server(|mut stream, i| if i > 0_usize { answer(&mut stream); });
+        let client = DGClient::from_host("127.0.0.1", &config(port, 0_usize, 1000_u64)).unwrap();
+        assert!(client.dg_arc(&seq(), 37_f32).is_err());
+    }
+
+    #[test]
+    fn unanswered_query_times_out() {
+        // every connection is kept open without an answer
+        let port = fake_dg_server(|stream, _| {
+            thread::spawn(move || {
+                let _stream = stream;
+                thread::sleep(Duration::from_secs(10_u64));
+            });
+        });
+        let client = DGClient::from_host("127.0.0.1", &config(port, 0_usize, 200_u64)).unwrap();
+        let start = Instant::now();
+        let e = client.dg_arc(&seq(), 37_f32).unwrap_err();
+        assert!(matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut), "{:?}", e);
+        assert!(start.elapsed() >= Duration::from_millis(200_u64));
+        assert!(start.elapsed() < Duration::from_secs(5_u64));
+    }
+
+    #[test]
+    fn unreachable_port_is_an_error() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port(); // the listener is dropped, so the port is closed
+        let e = DGClient::from_host("127.0.0.1", &config(port, 0_usize, 200_u64)).err().unwrap();
+        assert_eq!(e.addr, format!("127.0.0.1:{}", port));
+    }
+
+    #[test]
+    fn channel_reconnects_after_the_server_dropped_the_connection() {
+        // every connection answers a single query and is closed afterwards, e.g., by a restarting dg server
+        let port = fake_dg_server(|mut stream, _| { answer(&mut stream); });
+        let client = DGClient::from_host("127.0.0.1", &config(port, 1_usize, 1000_u64)).unwrap();
+        for _ in 0..3 {
+            assert_eq!(client.dg_arc(&seq(), 37_f32).unwrap(), DG);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn retries_back_off_exponentially() {
+        // the first two connections are closed without an answer
+        let port = fake_dg_server(|mut stream, i| if i > 1_usize { answer(&mut stream); });
+        let client = DGClient::from_host("127.0.0.1", &config(port, 2_usize, 1000_u64)).unwrap();
+        let start = Instant::now();
+        assert_eq!(client.dg_arc(&seq(), 37_f32).unwrap(), DG);
+        assert!(start.elapsed() >= Duration::from_millis(RETRY_BACKOFF_MS + 2_u64 * RETRY_BACKOFF_MS));
+    }
+}
@@ -0,0 +1,181 @@
+use std::io::{Error, ErrorKind};
+use std::net::{SocketAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::sync::{Mutex, MutexGuard, Semaphore};
+use tokio::time::{sleep, timeout};
+use crate::base_sequence::BaseSequence;
+use crate::dg_client::{self, DGClientConfig, DGClientError, DGTimeouts, RETRY_BACKOFF_MS};
+
+/// The asynchronous client used to communicate with the dg server from a tokio runtime. It speaks the same protocol as `DGClient`, but a query waits for a free channel without blocking the thread.
+pub struct DGClientAsync {
+    channels: Vec<AsyncChannelHandler>,
+    inflight: Option<Semaphore>, // bounds the number of queries in flight (None if unbounded)
+    next_channel: AtomicUsize    // the round-robin cursor of the channel a query starts probing at
+}
+
+impl DGClientAsync {
+    /// Creates a new DGClientAsync instance with a channel for every port of `config` that could be connected to. Ports that could not be connected to are skipped, i.e., the client may have fewer channels than ports (see `channel_count`).
+    /// Returns the error of the first port if no port could be connected to.
+    /// # Arguments
+    /// * The arguments `a`, `b`, `c`, and `d` represent the IP address of the dg server. For example, if the IP is 127.0.0.1, then `a` = 127, `b` = 0, `c` = 0, and `d` = 1.
+    /// * `config` - The ports of the dg server, the limit of the queries in flight, the timeouts, and the retries of every channel.
+    pub async fn new(a: u8, b: u8, c: u8, d: u8, config: &DGClientConfig) -> Result<DGClientAsync, DGClientError> {
+        Self::from_host(Ipv4Addr::new(a, b, c, d).to_string().as_str(), config).await
+    }
+
+    /// Creates a new DGClientAsync instance like `new`, but for a dg server given by its `host`, i.e., a hostname (e.g., "localhost") or an IPv4 or IPv6 address (e.g., "10.0.0.2" or "::1"). The host is resolved for every port, and a channel connects to the first resolved address that accepts the connection.
+    /// # Arguments
+    /// * `host` - The hostname or IP address of the dg server.
+    /// * `config` - The ports of the dg server, the limit of the queries in flight, the timeouts, and the retries of every channel.
+    pub async fn from_host(host: &str, config: &DGClientConfig) -> Result<DGClientAsync, DGClientError> {
+        let mut channels = Vec::with_capacity(config.ports().len());
+        let mut first_error = None;
+        for port in config.ports() {
+            match AsyncChannelHandler::new(host, port, config.timeouts(), config.max_retries()).await {
+                Ok(channel) => channels.push(channel),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if channels.is_empty() {
+            return Err(first_error.unwrap_or_else(|| dg_client::no_channels_error(host, config.ports().start)));
+        }
+        Ok(DGClientAsync {
+            channels,
+            inflight: if config.max_inflight() == 0_usize { None } else { Some(Semaphore::new(config.max_inflight())) },
+            next_channel: AtomicUsize::new(0_usize)
+        })
+    }
+
+    /// Returns the dg energy for a given `seq` at the temperature `temp`. Returns an error if the dg server did not answer in time or the connection failed.
+    pub async fn dg_arc(&self, seq: &Arc<BaseSequence>, temp: f32) -> Result<f32, Error> {
+        let mut buffer = [0u8; 4];
+        self.query(dg_client::single_query_packet(seq, temp).as_slice(), &mut buffer).await?;
+        Ok(f32::from_le_bytes(buffer))
+    }
+
+    /// Returns the dg energies for all `seqs` (in the same order) by sending them to the dg server in a single round-trip.
+    pub async fn dg_arc_batch(&self, seqs: &[Arc<BaseSequence>], temp: f32) -> Result<Vec<f32>, Error> {
+        if seqs.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut buffer = vec![0u8; 4 * seqs.len()];
+        self.query(dg_client::batch_query_packet(seqs, temp).as_slice(), &mut buffer).await?;
+        Ok(dg_client::dgs_from_bytes(&buffer))
+    }
+
+    /// Sends `packet` through a free channel and reads the answer into `buffer`.
+    async fn query(&self, packet: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        let _permit = match self.inflight.as_ref() {
+            Some(inflight) => Some(inflight.acquire().await.expect("the in-flight semaphore is never closed")),
+            None => None
+        };
+        let (channel, mut locked) = self.lock_free_channel().await;
+        channel.query_with_retries(&mut locked, packet, buffer).await
+    }
+
+    /// Returns the first channel that is not locked, starting at the next channel in round-robin order, together with its locked stream. If every channel is locked, waits for the starting channel.
+    async fn lock_free_channel(&self) -> (&AsyncChannelHandler, MutexGuard<'_, Option<TcpStream>>) {
+        let from_id = self.next_channel.fetch_add(1_usize, Ordering::Relaxed) % self.channels.len();
+        for offset in 0..self.channels.len() {
+            let channel = &self.channels[(from_id + offset) % self.channels.len()];
+            if let Ok(locked) = channel.stream.try_lock() {
+                return (channel, locked);
+            }
+        }
+        let channel = &self.channels[from_id];
+        (channel, channel.stream.lock().await)
+    }
+
+    /// Returns the number of channels, i.e., the number of ports that could be connected to.
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+}
+
+/// A single channel (port) of a DGClientAsync.
+struct AsyncChannelHandler {
+    stream: Mutex<Option<TcpStream>>, // None while the channel is disconnected, e.g., after a failed or cancelled query
+    addr: SocketAddr,
+    timeouts: DGTimeouts,
+    max_retries: usize
+}
+
+impl AsyncChannelHandler {
+    /// Creates a channel connected to the first resolved address of `host`:`port` that accepts the connection.
+    async fn new(host: &str, port: u16, timeouts: DGTimeouts, max_retries: usize) -> Result<AsyncChannelHandler, DGClientError> {
+        let to_error = |e| DGClientError {
+            addr: dg_client::host_port(host, port),
+            source: e
+        };
+        let mut last_error = Error::new(ErrorKind::NotFound, "the host did not resolve to any address");
+        for addr in lookup_host((host, port)).await.map_err(to_error)? {
+            match Self::connect(&addr, timeouts).await {
+                Ok(st) => return Ok(AsyncChannelHandler {
+                    stream: Mutex::new(Some(st)),
+                    addr,
+                    timeouts,
+                    max_retries
+                }),
+                Err(e) => last_error = e
+            }
+        }
+        Err(to_error(last_error))
+    }
+
+    /// Connects to `addr` within the connect timeout given by `timeouts`.
+    async fn connect(addr: &SocketAddr, timeouts: DGTimeouts) -> Result<TcpStream, Error> {
+        match timeout(timeouts.connect, TcpStream::connect(addr)).await {
+            Ok(connected) => connected,
+            Err(_) => Err(Error::new(ErrorKind::TimedOut, format!("connecting to {} timed out", addr)))
+        }
+    }
+
+    /// Sends `packet` through `stream` and reads the answer into `buffer` within the read timeout `read` (no timeout if `read` is zero).
+    async fn send_receive(stream: &mut TcpStream, packet: &[u8], buffer: &mut [u8], read: Duration) -> Result<(), Error> {
+        stream.write_all(packet).await?;
+        stream.flush().await?;
+        if read.is_zero() {
+            stream.read_exact(buffer).await?;
+        }
+        else {
+            match timeout(read, stream.read_exact(buffer)).await {
+                Ok(received) => { received?; }
+                Err(_) => return Err(Error::new(ErrorKind::TimedOut, "the dg server did not answer in time"))
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the query of `packet` on the locked stream. The stream is taken out of the channel while the query is in flight, so that a failed or cancelled query (i.e., a dropped future) never leaves a late answer
+    /// on the channel that would be read as the answer to the next query. A disconnected channel re-dials the dg server, and a failed query is rerun with an exponential backoff up to `max_retries` times before the last error is returned.
+    async fn query_with_retries(&self, locked: &mut Option<TcpStream>, packet: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        let mut retries = 0_usize;
+        loop {
+            let connected = match locked.take() {
+                Some(st) => Ok(st),
+                None => Self::connect(&self.addr, self.timeouts).await
+            };
+            let result = match connected {
+                Ok(mut st) => Self::send_receive(&mut st, packet, buffer, self.timeouts.read).await.map(|_| st),
+                Err(e) => Err(e)
+            };
+            match result {
+                Ok(st) => {
+                    *locked = Some(st);
+                    return Ok(());
+                }
+                Err(e) if retries >= self.max_retries => return Err(e),
+                Err(_) => {
+                    sleep(Duration::from_millis(RETRY_BACKOFF_MS << retries.min(16))).await;
+                    retries += 1;
+                }
+            }
+        }
+    }
+}
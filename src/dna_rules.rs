@@ -1,12 +1,445 @@
-use crate::base_sequence::BaseSequence;
+use crate::base_sequence::{BaseSequence, ParseBaseError};
 use std::sync::Arc;
+use std::str::FromStr;
+use std::fmt;
 
 
-const MIN_GC_CONTENT: f64 = 0.40;
-const MAX_GC_CONTENT: f64 = 0.60;
+pub const MIN_GC_CONTENT: f64 = 0.40;
+pub const MAX_GC_CONTENT: f64 = 0.60;
+pub const DG_ERROR_OFFSET: f32 = 4.0;
+pub const DG_ERROR_STEEPNESS: f32 = 1.0;
+pub const MAX_DG_ERROR: f32 = 0.5;
+
+/// A constraint on a DNA strand. The constraints of this module implement it, and so does every closure `Fn(&Arc<BaseSequence>) -> bool`, e.g., to add a constraint to an encoder with `EncoderBuilder::with_rule`.
+pub trait Rule {
+    /// Returns true if the sequence `seq` satisfies this constraint.
+    fn check(&self, seq: &Arc<BaseSequence>) -> bool;
+}
+
+impl<F> Rule for F where F: Fn(&Arc<BaseSequence>) -> bool {
+    #[inline(always)]
+    fn check(&self, seq: &Arc<BaseSequence>) -> bool {
+        self(seq)
+    }
+}
+
+/// Checks if a sequence `seq` satisfies all `rules` in their order, i.e., stops at the first rule it fails.
+#[inline(always)]
+pub fn satisfies_all(rules: &[Box<dyn Rule + Send + Sync>], seq: &Arc<BaseSequence>) -> bool {
+    rules.iter().all(|rule| rule.check(seq))
+}
+
+/// The constraints on the GC content and the maximum homopolymer length of a sequence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GcHpRules {
+    pub min_gc: f64,
+    pub max_gc: f64,
+    pub max_hp_len: usize
+}
+
+impl GcHpRules {
+    pub fn new(min_gc: f64, max_gc: f64, max_hp_len: usize) -> Self {
+        if min_gc > max_gc {
+            panic!("min_gc ({}) must not be greater than max_gc ({})", min_gc, max_gc);
+        }
+        Self {
+            min_gc,
+            max_gc,
+            max_hp_len
+        }
+    }
+
+    /// Creates a new GcHpRules with the default GC content bounds and the given maximum homopolymer length `max_hp_len`.
+    pub fn with_max_hp_len(max_hp_len: usize) -> Self {
+        Self::new(MIN_GC_CONTENT, MAX_GC_CONTENT, max_hp_len)
+    }
+
+    /// Checks if a sequence `seq` satisfies the constraints on the GC content and maximum homopolymer length.
+    #[inline(always)]
+    pub fn satisfies(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.satisfies_stats(seq.gc(), seq.longest_hp())
+    }
+
+    /// Checks if a sequence with the GC content `gc` and the longest homopolymer of length `longest_hp` satisfies the constraints, e.g., for the statistics maintained by a `SequenceBuilder`.
+    #[inline(always)]
+    pub fn satisfies_stats(&self, gc: f64, longest_hp: usize) -> bool {
+        self.satisfies_gc(gc) && self.satisfies_hp(longest_hp)
+    }
+
+    /// Checks if the GC content `gc` is within the bounds.
+    #[inline(always)]
+    pub fn satisfies_gc(&self, gc: f64) -> bool {
+        gc >= self.min_gc && gc <= self.max_gc
+    }
+
+    /// Checks if the longest homopolymer of length `longest_hp` does not exceed the maximum homopolymer length.
+    #[inline(always)]
+    pub fn satisfies_hp(&self, longest_hp: usize) -> bool {
+        longest_hp <= self.max_hp_len
+    }
+}
+
+/// The constraint on the longest homopolymer of every base of a sequence, i.e., the longest run of a base `b` must not exceed `max_hp_lens[b as usize]` (A, C, G, T).
+/// A limit of 0 disables the constraint for that base, so that only the scalar `max_hp_len` of `GcHpRules` applies to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BaseHpRules {
+    pub max_hp_lens: [usize; 4]
+}
+
+impl BaseHpRules {
+    pub fn new(max_hp_lens: [usize; 4]) -> Self {
+        Self {
+            max_hp_lens
+        }
+    }
+
+    /// Checks if the longest homopolymer of every base of a sequence `seq` is at most its limit. Always true if every limit is 0 (disabled).
+    #[inline(always)]
+    pub fn satisfies(&self, seq: &Arc<BaseSequence>) -> bool {
+        if self.max_hp_lens.iter().all(|&max_hp_len| max_hp_len == 0) {
+            return true;
+        }
+        seq.longest_hp_per_base().iter().zip(self.max_hp_lens.iter()).all(|(&longest_hp, &max_hp_len)| max_hp_len == 0 || longest_hp <= max_hp_len)
+    }
+}
+
+/// The constraint on the local GC content of a sequence, i.e., the GC content of every sliding window of size `window` must lie within [`min_gc`, `max_gc`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GcWindowRules {
+    pub window: usize,
+    pub min_gc: f64,
+    pub max_gc: f64
+}
+
+impl GcWindowRules {
+    pub fn new(window: usize, min_gc: f64, max_gc: f64) -> Self {
+        if min_gc > max_gc {
+            panic!("min_window_gc ({}) must not be greater than max_window_gc ({})", min_gc, max_gc);
+        }
+        Self {
+            window,
+            min_gc,
+            max_gc
+        }
+    }
+
+    /// Checks if every window of a sequence `seq` satisfies the constraint on the local GC content. Always true if `window` is 0 (disabled).
+    #[inline(always)]
+    pub fn satisfies(&self, seq: &Arc<BaseSequence>) -> bool {
+        if self.window == 0 {
+            return true;
+        }
+        let (min, max) = seq.gc_window(self.window);
+        min >= self.min_gc && max <= self.max_gc
+    }
+}
+
+/// The constraint on the melting temperature of a sequence, i.e., it must lie within [`min_tm`, `max_tm`] (see `BaseSequence::melting_temp`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TmRules {
+    pub min_tm: f64,
+    pub max_tm: f64
+}
+
+impl TmRules {
+    pub fn new(min_tm: f64, max_tm: f64) -> Self {
+        if min_tm > max_tm {
+            panic!("min_tm ({}) must not be greater than max_tm ({})", min_tm, max_tm);
+        }
+        Self {
+            min_tm,
+            max_tm
+        }
+    }
+
+    /// Checks if the melting temperature of a sequence `seq` lies within the bounds.
+    #[inline(always)]
+    pub fn satisfies(&self, seq: &Arc<BaseSequence>) -> bool {
+        let tm = seq.melting_temp();
+        tm >= self.min_tm && tm <= self.max_tm
+    }
+}
+
+/// The constraint on the complexity of a sequence, i.e., its dinucleotide entropy (see `BaseSequence::dinucleotide_entropy`) must be at least `min_entropy`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EntropyRules {
+    pub min_entropy: f64
+}
+
+impl EntropyRules {
+    pub fn new(min_entropy: f64) -> Self {
+        Self {
+            min_entropy
+        }
+    }
+
+    /// Checks if the dinucleotide entropy of a sequence `seq` is at least `min_entropy`. Always true if `min_entropy` is not positive (disabled).
+    #[inline(always)]
+    pub fn satisfies(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.min_entropy <= 0_f64 || seq.dinucleotide_entropy() >= self.min_entropy
+    }
+}
+
+/// The local constraint on hairpins of a sequence, i.e., the longest stem enclosing a loop of at least `min_loop` bases (see `BaseSequence::max_self_complementary_run`) must not exceed `max_stem`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HairpinRules {
+    pub max_stem: usize,
+    pub min_loop: usize
+}
+
+impl HairpinRules {
+    pub fn new(max_stem: usize, min_loop: usize) -> Self {
+        Self {
+            max_stem,
+            min_loop
+        }
+    }
+
+    /// Checks if the longest hairpin stem of a sequence `seq` is at most `max_stem` long. Always true if `max_stem` is 0 (disabled).
+    #[inline(always)]
+    pub fn satisfies(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.max_stem == 0 || seq.max_self_complementary_run(self.min_loop) <= self.max_stem
+    }
+}
+
+/// The constraint on the dg energy of a sequence obtained from the dg server. The dg energy is converted into an error score between 0 (lowest) and 1 (highest) by the sigmoid
+/// 1 / (1 + exp(`steepness` * (dg + `offset`))), which must not exceed `max_error`. The defaults assume the scale of the dg energies (in kcal/mol) of the bundled dg server.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DgRules {
+    pub offset: f32,
+    pub steepness: f32,
+    pub max_error: f32
+}
+
+impl DgRules {
+    pub fn new(offset: f32, steepness: f32, max_error: f32) -> Self {
+        if steepness.is_nan() || steepness <= 0_f32 {
+            panic!("dg_error_steepness ({}) must be positive", steepness);
+        }
+        Self {
+            offset,
+            steepness,
+            max_error
+        }
+    }
+
+    /// Returns the error score of the dg energy `dg`, i.e., a value between 0 (lowest) and 1 (highest) that decreases with `dg`.
+    #[inline(always)]
+    pub fn error(&self, dg: f32) -> f32 {
+        let err = 1_f32 / (1_f32 + f32::exp(self.steepness * (dg + self.offset)));
+        if err.is_normal() {
+            err
+        }
+        else {
+            0_f32
+        }
+    }
+
+    /// Checks if the error score of the dg energy `dg` is at most `max_error`.
+    #[inline(always)]
+    pub fn satisfies(&self, dg: f32) -> bool {
+        self.error(dg) <= self.max_error
+    }
+}
 
 /// Checks if a sequence `seq` satisfies the given constraints on the GC content and maximum homopolymer length.
 pub fn satisfy_gc_hp_rules(seq: &Arc<BaseSequence>, max_hp_len: usize) -> bool {
-    let gc = seq.gc();
-    gc >= MIN_GC_CONTENT && gc <= MAX_GC_CONTENT && seq.longest_hp() <= max_hp_len
-}
\ No newline at end of file
+    GcHpRules::with_max_hp_len(max_hp_len).satisfies(seq)
+}
+
+/// The filter that rejects sequences containing a forbidden motif (e.g., a restriction site) on either strand.
+#[derive(Clone, Debug)]
+pub struct MotifFilter {
+    motifs: Vec<BaseSequence>
+}
+
+impl MotifFilter {
+    /// Creates a new MotifFilter from the forbidden `motifs`. The reverse complement of every motif is added as well, so that motifs on the opposite strand are rejected, too.
+    pub fn new(motifs: Vec<BaseSequence>) -> Self {
+        let mut all_motifs = Vec::with_capacity(2 * motifs.len());
+        for motif in motifs.into_iter().filter(|m| m.len() > 0) {
+            let rev_comp = motif.reverse_complement();
+            if rev_comp != motif {
+                all_motifs.push(rev_comp);
+            }
+            all_motifs.push(motif);
+        }
+        Self {
+            motifs: all_motifs
+        }
+    }
+
+    /// Returns true if `seq` or its reverse complement contains at least one forbidden motif.
+    #[inline(always)]
+    pub fn contains_forbidden(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.motifs.iter().any(|motif| BaseSequence::search_count_of(seq.as_slice(), motif.as_slice(), false) > 0)
+    }
+}
+
+/// Parses the forbidden motifs separated by `;`, e.g., "GAATTC;GGTACC". Fails on the first motif that contains a character that is not a DNA base.
+impl FromStr for MotifFilter {
+    type Err = ParseMotifError;
+
+    fn from_str(motifs: &str) -> Result<Self, Self::Err> {
+        motifs.split(';').map(|m| m.trim()).filter(|m| !m.is_empty())
+            .map(|motif| BaseSequence::try_from_str(motif).map_err(|source| ParseMotifError { motif: motif.to_string(), source }))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self::new)
+    }
+}
+
+/// The error returned when a forbidden motif of a `MotifFilter` cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMotifError {
+    pub motif: String,        // the motif that cannot be parsed
+    pub source: ParseBaseError // the first invalid character of the motif
+}
+
+impl fmt::Display for ParseMotifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid motif {}: {}", self.motif, self.source)
+    }
+}
+
+impl std::error::Error for ParseMotifError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl Rule for GcHpRules {
+    #[inline(always)]
+    fn check(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.satisfies(seq)
+    }
+}
+
+impl Rule for BaseHpRules {
+    #[inline(always)]
+    fn check(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.satisfies(seq)
+    }
+}
+
+impl Rule for GcWindowRules {
+    #[inline(always)]
+    fn check(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.satisfies(seq)
+    }
+}
+
+impl Rule for TmRules {
+    #[inline(always)]
+    fn check(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.satisfies(seq)
+    }
+}
+
+impl Rule for EntropyRules {
+    #[inline(always)]
+    fn check(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.satisfies(seq)
+    }
+}
+
+impl Rule for HairpinRules {
+    #[inline(always)]
+    fn check(&self, seq: &Arc<BaseSequence>) -> bool {
+        self.satisfies(seq)
+    }
+}
+
+/// A sequence satisfies a MotifFilter if neither it nor its reverse complement contains a forbidden motif.
+impl Rule for MotifFilter {
+    #[inline(always)]
+    fn check(&self, seq: &Arc<BaseSequence>) -> bool {
+        !self.contains_forbidden(seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seq(s: &str) -> Arc<BaseSequence> {
+        Arc::new(BaseSequence::try_from_str(s).unwrap())
+    }
+
+    #[test]
+    fn motif_filter_rejects_motifs_on_either_strand() {
+        let filter = "GAATTC; GGTACC;".parse::<MotifFilter>().unwrap();
+        assert!(filter.contains_forbidden(&seq("ACGAATTCAC")));
+        assert!(filter.contains_forbidden(&seq("ACGGTACCAC")));
+        assert!(!filter.contains_forbidden(&seq("ACTTTTCCAC")));
+        // the reverse complement of GGTACA is TGTACC
+        let filter = "GGTACA".parse::<MotifFilter>().unwrap();
+        assert!(filter.contains_forbidden(&seq("ATGTACCA")));
+        assert!(filter.check(&seq("ATGTAGCA")));
+    }
+
+    #[test]
+    fn motif_filter_finds_motifs_at_both_ends() {
+        let filter = "GGTACA".parse::<MotifFilter>().unwrap();
+        assert!(filter.contains_forbidden(&seq("GGTACATT")));
+        assert!(filter.contains_forbidden(&seq("TTGGTACA")));
+        assert!(filter.contains_forbidden(&seq("GGTACA")));
+    }
+
+    #[test]
+    fn motif_filter_reports_the_invalid_motif() {
+        let e = "GAATTC;GGXACC".parse::<MotifFilter>().unwrap_err();
+        assert_eq!(e.motif, "GGXACC");
+        assert_eq!((e.source.byte, e.source.position), (b'X', 2_usize));
+        assert!("".parse::<MotifFilter>().unwrap().check(&seq("GAATTC")));
+    }
+
+    #[test]
+    fn tm_rules_accept_the_closed_band() {
+        let rules = TmRules::new(12_f64, 38_f64);
+        assert!(rules.check(&seq("ACGT")));
+        assert!(rules.check(&seq("ACGTACGTACGTA")));
+        assert!(!rules.check(&seq("ACG")));
+        assert!(!rules.check(&seq("GCGCGCGCGCGC")));
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be greater than max_tm")]
+    fn tm_rules_reject_an_empty_band() {
+        TmRules::new(60_f64, 50_f64);
+    }
+
+    #[test]
+    fn entropy_rules_reject_low_complexity_sequences() {
+        let rules = EntropyRules::new(3_f64);
+        assert!(rules.check(&seq("AACAGATCCGCTGGTTA")));
+        assert!(!rules.check(&seq("ACACACACACAC")));
+        assert!(!rules.check(&seq("AAAAAAAAAA")));
+        assert!(EntropyRules::new(0_f64).check(&seq("AAAAAAAAAA")));
+    }
+
+    #[test]
+    fn dg_error_is_a_decreasing_sigmoid_centered_at_the_offset() {
+        for &(offset, steepness) in [(DG_ERROR_OFFSET, DG_ERROR_STEEPNESS), (0_f32, 0.5_f32), (10_f32, 3_f32)].iter() {
+            let rules = DgRules::new(offset, steepness, MAX_DG_ERROR);
+            assert!((rules.error(-offset) - 0.5_f32).abs() < 1e-6_f32);
+            let errors = (-100..=100).map(|dg| rules.error(dg as f32)).collect::<Vec<_>>();
+            assert!(errors.iter().all(|&error| (0_f32..=1_f32).contains(&error)));
+            assert!(errors.windows(2).all(|pair| pair[1] <= pair[0]));
+        }
+    }
+
+    #[test]
+    fn dg_rules_reject_errors_above_the_maximum() {
+        let rules = DgRules::new(4_f32, 1_f32, 0.5_f32);
+        assert!(rules.satisfies(-3_f32));
+        assert!(rules.satisfies(-4_f32));
+        assert!(!rules.satisfies(-5_f32));
+        assert!(DgRules::new(4_f32, 1_f32, 1_f32).satisfies(-100_f32));
+    }
+
+    #[test]
+    #[should_panic(expected = "dg_error_steepness (0) must be positive")]
+    fn dg_rules_reject_a_steepness_of_0() {
+        DgRules::new(4_f32, 0_f32, 0.5_f32);
+    }
+}
@@ -0,0 +1,985 @@
+use std::cell::Cell;
+use std::fmt;
+use std::collections::HashSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, Duration};
+use crossbeam_channel::{Sender, bounded};
+use parking_lot::Mutex;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use crate::base_sequence::BaseSequence;
+use crate::dg_client::DGClient;
+use crate::dna_rules::{self, BaseHpRules, DgRules, EntropyRules, GcHpRules, GcWindowRules, HairpinRules, MotifFilter, Rule, TmRules};
+use crate::lsh::{self, LSH, SeqsFingerprint};
+use crate::pseudo_permutation;
+use crate::raptor::{RaptorQ, EncodeError, EncodeLimits, EncodeRules};
+use crate::similarity::{self, SimilarityBackend, ExactBackend, ApproxBackend, SIMILARITY_BACKEND_APPROX};
+
+static INITIAL_PACKETS_PER_BLOCK: usize       = 5_usize;             // default starting number of packets that are generated by RQ
+pub const MAX_ENCODE_LOOPS: usize             = 200_usize;           // number of loops in RQ attempting to find packets that fulfill the given constraints
+
+static RNG_STREAM_PROBES_LSH: u64             = 0_u64;               // offset of the seed for the probes' LSH instance
+static RNG_STREAM_SEQS_LSH: u64               = 1_u64;               // offset of the seed for the Info-DNAs' LSH instance
+static RNG_STREAM_LINES: u64                  = 2_u64;               // offset of the seed for the first data object (the i-th data object uses this offset + i)
+
+pub const DEFAULT_MAX_HP_LEN: usize           = 5_usize;             // default maximum homopolymer length
+pub const DEFAULT_GC_WINDOW: usize            = 0_usize;             // default window size for the local GC content (0 = disabled)
+pub const DEFAULT_MIN_WINDOW_GC: f64          = 0.3_f64;             // default minimum GC content of every window
+pub const DEFAULT_MAX_WINDOW_GC: f64          = 0.7_f64;             // default maximum GC content of every window
+pub const DEFAULT_MIN_TM: f64                 = 0_f64;               // default minimum melting temperature in °C
+pub const DEFAULT_MAX_TM: f64                 = f64::INFINITY;       // default maximum melting temperature in °C
+pub const DEFAULT_MIN_ENTROPY: f64            = 0_f64;               // default minimum dinucleotide entropy in bits (0 = disabled)
+pub const DEFAULT_MAX_HAIRPIN_STEM: usize     = 0_usize;             // default maximum length of a local hairpin stem (0 = disabled)
+pub const DEFAULT_HAIRPIN_MIN_LOOP: usize     = 3_usize;             // default minimum loop length of a local hairpin
+pub const DEFAULT_OVERHEAD: usize             = 0_usize;             // default RQ overhead
+pub const DEFAULT_SECONDARY_STRUCT_TEMP: f32  = 25_f32;              // default temperature for the dg energy
+pub const DEFAULT_DG_MIN_LEN: usize           = 0_usize;             // default minimum length of a strand to query its dg energy (shorter strands pass without a query)
+pub const DEFAULT_PER_LINE_TIMEOUT_MS: u64    = 0_u64;               // default time limit in ms for encoding a single data object (0 = no limit)
+pub const DEFAULT_MIN_DIST_TO_PROBES: f64     = 0.4_f64;             // default minimum distance to probes
+pub const DEFAULT_MIN_DIST_TO_SEQS: f64       = 0.4_f64;             // default minimum distance to Info-DNAs
+pub const DEFAULT_MIN_DIST_TO_SEQS_DECAY_START: usize = 0_usize;     // default number of Info-DNAs after which the minimum distance to Info-DNAs starts to decay
+pub const DEFAULT_MIN_DIST_TO_SEQS_DECAY_END: usize = 0_usize;       // default number of Info-DNAs at which the minimum distance to Info-DNAs reaches its floor (0 = no decay)
+pub const DEFAULT_MIN_DIST_TO_SEQS_FLOOR: f64  = 0_f64;               // default smallest minimum distance to Info-DNAs the decay reaches
+pub const DEFAULT_CHECK_REVCOMP: bool         = false;               // default value for whether or not the reverse complement of an Info-DNA must be far enough from the probes and Info-DNAs, too
+
+pub const DEFAULT_LSH_K_PROBES: usize         = 4_usize;             // default k for the probes' LSH instance
+pub const DEFAULT_LSH_R_PROBES: usize         = 200_usize;           // default r for the probes' LSH instance
+pub const DEFAULT_LSH_B_PROBES: usize         = 20_usize;            // default b for the probes' LSH instance
+pub const DEFAULT_LSH_K_SEQS: usize           = 5_usize;             // default k for the Info-DNAs' LSH instance
+pub const DEFAULT_LSH_R_SEQS: usize           = 200_usize;           // default r for the Info-DNAs' LSH instance
+pub const DEFAULT_LSH_B_SEQS: usize           = 20_usize;            // default b for the Info-DNAs' LSH instance
+pub const DEFAULT_LSH_CGK_LEN: usize          = 150_usize;           // default length of the sequences embedded by the CGK bucketing
+pub const DEFAULT_MINHASH_PREFILTER_MARGIN: f64 = f64::INFINITY;     // default margin of the MinHash pre-filter of the Jaccard distance (inf = disabled)
+pub const DEFAULT_CANONICAL_K_MERS: bool      = false;               // default value for whether or not the LSH instances and the Jaccard distance use canonical k-mers
+
+/// The Info-DNA of a single data object together with the statistics of its encoding, as sent by `encode_file`.
+/// Like the captions of the Info-DNA file and the reports, `line_id` is 1-based, i.e., the data object `lines[i]` has the line id i + 1.
+#[derive(Clone, Debug)]
+pub struct EncodedStrand {
+    pub line_id: usize,                 // the 1-based id of the data object, i.e., its index in the encoded lines + 1
+    pub sequence: Arc<BaseSequence>,    // the encoded Info-DNA of the data object
+    pub trials: usize,                  // the number of loops that were needed to successfully encode the data object
+    pub bytes: usize,                   // the number of bytes of the data object
+    pub rq_time: u128,                  // the total time in ms RQ needed to encode the data object
+    pub dg_time: Option<u128>,          // the total time in ms the dg server needed to return the dg energies of the suggested sequences (None if no dg server is used)
+    pub total_time: u128,               // the total time in ms needed to finish encoding the data object
+    pub dg: Option<f32>,                // the dg energy of the Info-DNA (None if it was not computed, e.g., skipped because of `dg_min_len`)
+    pub overhead: usize,                // the overhead ε used to encode the data object (derived from the target redundancy if set)
+    pub packets_used: usize,            // the number of RQ packets combined into the Info-DNA
+    pub achieved_overhead: usize,       // the number of packets beyond the source symbols of the data object
+    pub min_dist_to_seqs: f64,          // the minimum distance to the other Info-DNAs the Info-DNA was checked against, i.e., after the decay of `MinDistSchedule`
+    pub rejections: Rejections          // the number of candidate Info-DNAs that were rejected before the Info-DNA was accepted, by the reason of their rejection
+}
+
+/// The reason why a candidate Info-DNA was rejected, i.e., the first check it failed. The checks run in the order of `RejectReason::ALL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RejectReason {
+    Gc,             // the GC content of the strand or of a window is out of bounds
+    Homopolymer,    // a homopolymer exceeds the maximum length (of its base)
+    Constraint,     // another sequence constraint failed, i.e., a forbidden motif, the melting temperature, the entropy, or a local hairpin
+    ProbeDistance,  // too close to a probe
+    Duplicate,      // an exact duplicate of an Info-DNA emitted before (or being emitted by another worker)
+    SeqDistance,    // too close to an Info-DNA encoded before
+    Dg              // failed the dg check (or the dg server did not answer)
+}
+
+impl RejectReason {
+    pub const ALL: [RejectReason; 7] = [RejectReason::Gc, RejectReason::Homopolymer, RejectReason::Constraint, RejectReason::ProbeDistance, RejectReason::Duplicate, RejectReason::SeqDistance, RejectReason::Dg];
+
+    /// Returns the name of this reason as used in the reports, e.g., "probe_distance".
+    pub fn name(&self) -> &'static str {
+        match self {
+            RejectReason::Gc => "gc",
+            RejectReason::Homopolymer => "homopolymer",
+            RejectReason::Constraint => "constraint",
+            RejectReason::ProbeDistance => "probe_distance",
+            RejectReason::Duplicate => "duplicate",
+            RejectReason::SeqDistance => "seq_distance",
+            RejectReason::Dg => "dg"
+        }
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The tally of the candidate Info-DNAs of a data object that were rejected, counted by the first check they failed (see `RejectReason`). A candidate is a decodable combination of RQ packets, i.e., packets that are rejected on their own are not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rejections {
+    counts: [usize; RejectReason::ALL.len()] // the number of rejections indexed by `RejectReason as usize`
+}
+
+impl Rejections {
+    /// Returns the number of candidates rejected for `reason`.
+    pub fn get(&self, reason: RejectReason) -> usize {
+        self.counts[reason as usize]
+    }
+
+    /// Counts a candidate rejected for `reason`.
+    pub fn record(&mut self, reason: RejectReason) {
+        self.counts[reason as usize] += 1_usize;
+    }
+
+    /// Returns the total number of rejected candidates.
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Returns every reason together with the number of candidates rejected for it in the order of `RejectReason::ALL`.
+    pub fn iter(&self) -> impl Iterator<Item = (RejectReason, usize)> + '_ {
+        RejectReason::ALL.iter().map(move |&reason| (reason, self.get(reason)))
+    }
+
+    /// Returns the reason most candidates were rejected for, or None if no candidate was rejected.
+    pub fn most_frequent(&self) -> Option<RejectReason> {
+        self.iter().filter(|(_, count)| *count > 0).max_by_key(|(_, count)| *count).map(|(reason, _)| reason)
+    }
+
+    /// Returns the sum of these rejections and `other`, e.g., to accumulate the rejections of a run.
+    pub fn add(&self, other: &Rejections) -> Rejections {
+        let mut sum = *self;
+        for (count, other_count) in sum.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+        sum
+    }
+}
+
+/// Encodes data objects into Info-DNAs that satisfy the DNA constraints and keep a minimum distance to the probes and to each other.
+/// The Info-DNAs of all `encode` calls of the same encoder keep their distance to each other. Create an encoder with `Encoder::builder`.
+///
+/// # Concurrency
+///
+/// The workers share the probes, the Info-DNAs, and the emitted set without any unsafe aliasing. The following invariants hold:
+///
+/// * The probes are only inserted while the encoder is built (in parallel, see `LSH::insert`), and only read by the workers afterwards.
+/// * An Info-DNA is only added through `SimilarityBackend::insert_if_far_enough`, which computes the distances under a read lock and inserts under the write lock only if no other Info-DNA was inserted in the meantime. Hence, two workers never both insert Info-DNAs that are too close to each other.
+/// * The emitted set is guarded by its own mutex and only rejects exact duplicates. An Info-DNA is reserved in it under a single lock before it is inserted, and removed again if it is too close to the Info-DNAs, so two workers never both emit the same Info-DNA, even with a minimum distance of 0.
+/// * `insert` adds the Info-DNAs of a resumed run to both without checks, and is meant to be called before encoding.
+pub struct Encoder {
+    line_encoder: Arc<LineEncoder>,                     // the parameters and the state shared by the workers that encode the data objects
+    pool: ThreadPool,                                   // the thread pool that encodes the data objects each in a thread
+    cancelled: Arc<AtomicBool>,                         // set to skip the data objects that have not started encoding yet (see `cancel`)
+    seed: Option<u64>
+}
+
+/// The callback that is called whenever a data object failed to encode within `MAX_ENCODE_LOOPS` loops of RQ, with the line id of the data object, the error, and the number of packets per block it is retried with (see `EncoderBuilder::with_on_retry`).
+pub type RetryCallback = Box<dyn Fn(usize, &EncodeError, usize) + Send + Sync>;
+
+/// The parameters and the state of an `Encoder` that `encode_file` needs to encode a single data object, shared by all workers.
+struct LineEncoder {
+    raptor: Arc<RaptorQ>,
+    probes_similarity: Arc<dyn SimilarityBackend>,      // the probes, read-only after `EncoderBuilder::build`
+    seqs_similarity: Arc<dyn SimilarityBackend>,        // the Info-DNAs emitted so far, only added through `insert_if_far_enough` (or `insert`)
+    emitted: Arc<Mutex<HashSet<Arc<BaseSequence>>>>,    // the set of emitted Info-DNAs that guards against exact duplicates
+    overhead: usize,
+    target_redundancy: Option<f64>,                     // the ratio of packets to source symbols that overrides `overhead` per data object (None = `overhead` is used)
+    gc_hp_rules: GcHpRules,
+    base_hp_rules: BaseHpRules,
+    gc_window_rules: GcWindowRules,
+    entropy_rules: EntropyRules,
+    strand_rules: Arc<Vec<Box<dyn Rule + Send + Sync>>>,    // the forbidden motifs, the melting temperature, the entropy, local hairpins, and the rules added by `EncoderBuilder::with_rule`, checked in this order on every strand
+    min_dist_to_probes: f64,
+    min_dist_to_seqs: f64,
+    min_dist_to_seqs_schedule: MinDistSchedule,
+    check_revcomp: bool,
+    dg_client: Option<DGClient>,
+    dg_rules: DgRules,
+    dg_min_len: usize,
+    sec_struct_temp: f32,
+    per_line_timeout: Option<Duration>,                 // the time after which a data object is abandoned (None = no limit)
+    on_retry: Option<RetryCallback>                     // called whenever a data object is retried with more packets per block
+}
+
+impl Encoder {
+    /// Returns a builder of an encoder that keeps the Info-DNAs away from `probes`. Every parameter that is not set on the builder has its default value.
+    pub fn builder(probes: Vec<Arc<BaseSequence>>) -> EncoderBuilder {
+        EncoderBuilder::new(probes)
+    }
+
+    /// Returns the RQ configuration used to encode the data objects.
+    pub fn raptor(&self) -> &RaptorQ {
+        &self.line_encoder.raptor
+    }
+
+    /// Cancels the running and all later calls of `encode_with`: the data objects that have not started encoding yet are passed to the collector as a `LineError` of the kind `LineErrorKind::Cancelled`, while the data objects in flight are encoded to the end.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if `cancel` was called, or the flag returned by `cancel_flag` was set.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns the flag that cancels this encoder when it is set to true, e.g., from a signal handler that cannot borrow the encoder (see `cancel`).
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    /// Inserts the Info-DNA `seq` without checking it, e.g., an Info-DNA of a resumed run. The Info-DNAs encoded afterwards keep their distance to `seq`.
+    pub fn insert(&self, seq: &Arc<BaseSequence>) {
+        self.line_encoder.seqs_similarity.insert(seq);
+        self.line_encoder.emitted.lock().insert(seq.clone());
+    }
+
+    /// Encodes every data object in `lines` and returns the Info-DNAs in the order of `lines`. Panics if a data object cannot be encoded, e.g., because it is empty or timed out.
+    pub fn encode(&self, lines: &[Vec<u8>]) -> Vec<Arc<BaseSequence>> {
+        self.encode_strands(lines).into_iter().map(|strand| strand.sequence).collect()
+    }
+
+    /// Encodes every data object in `lines` and returns the Info-DNAs with the statistics of their encoding in the order of `lines`, i.e., sorted by line id. Panics if a data object cannot be encoded, e.g., because it is empty or timed out.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use rqpap::Encoder;
+    /// use rqpap::base_sequence::BaseSequence;
+    /// use rqpap::raptor::RaptorQ;
+    ///
+    /// let probes = vec![Arc::new(BaseSequence::try_from_str("ACGTTGCAACGGTTCCAAGGTTAACCGGTT").unwrap())];
+    /// let encoder = Encoder::builder(probes).with_workers(2).with_seed(Some(7)).build();
+    /// let lines = vec![b"hello".to_vec(), b"DNA storage".to_vec()];
+    /// let strands = encoder.encode_strands(&lines);
+    ///
+    /// assert_eq!(strands.len(), lines.len());
+    /// for (strand, line) in strands.iter().zip(lines.iter()) {
+    ///     assert!(strand.trials >= 1 && strand.packets_used >= 1);
+    ///     assert_eq!(strand.bytes, line.len());
+    ///     assert_eq!(RaptorQ::default().decode_from_dna(&strand.sequence).as_ref(), Some(line));
+    /// }
+    /// ```
+    pub fn encode_strands(&self, lines: &[Vec<u8>]) -> Vec<EncodedStrand> {
+        let lines = lines.iter().map(|line| Arc::new(line.clone())).collect::<Vec<_>>();
+        let mut strands = Vec::with_capacity(lines.len());
+        self.encode_with(&lines, &(0..lines.len()).collect::<Vec<_>>(), |encoded| match encoded {
+            Ok(strand) => strands.push(strand),
+            Err(e) => panic!("{}", e)
+        });
+        strands.sort_unstable_by_key(|strand| strand.line_id);
+        strands
+    }
+
+    /// Encodes the data objects `lines[line_id]` of all `line_ids` in parallel and calls `on_encoded` with the result of every data object in the order in which they finish.
+    /// The indices in `line_ids` are 0-based, while the line id of a result is 1-based, i.e., the index + 1 (see `EncodedStrand`). A panic while encoding a data object, a data object that exceeded the per-line timeout, or a data object skipped after `cancel`, is passed to `on_encoded` as a `LineError` instead of aborting the other data objects.
+    /// If the encoder is seeded, the data objects are encoded one after another to obtain a deterministic output.
+    pub fn encode_with(&self, lines: &[Arc<Vec<u8>>], line_ids: &[usize], mut on_encoded: impl FnMut(Result<EncodedStrand, LineError>)) {
+        let (sender, receiver) = bounded(line_ids.len());
+        let seed = self.seed;
+
+        for &line_id in line_ids.iter() {
+            let sender_cloned = sender.clone();
+            let line = lines.get(line_id).unwrap().clone();
+            let line_encoder_cloned = self.line_encoder.clone();
+            let cancelled_cloned = self.cancelled.clone();
+            self.pool.spawn(move|| {
+                if cancelled_cloned.load(Ordering::SeqCst) {
+                    let _ = sender_cloned.send(Err(LineError { line_id: line_id + 1_usize, message: String::from("cancelled before it was started"), kind: LineErrorKind::Cancelled }));
+                    return;
+                }
+                let error_sender = sender_cloned.clone();
+                // a panic while encoding this data object is reported to the collector instead of aborting the whole run
+                let encoded = catch_unwind(AssertUnwindSafe(|| encode_file(
+                    (line_id + 1_usize, line),
+                    &line_encoder_cloned,
+                    sender_cloned,
+                    INITIAL_PACKETS_PER_BLOCK,
+                    seeded_rng(seed, RNG_STREAM_LINES.wrapping_add(line_id as u64))
+                )));
+                if let Err(payload) = encoded {
+                    let message = payload.downcast_ref::<&str>().map(|m| m.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| String::from("unknown panic"));
+                    let _ = error_sender.send(Err(LineError { line_id: line_id + 1_usize, message, kind: LineErrorKind::Panicked }));
+                }
+            });
+        }
+
+        for done in 0..line_ids.len() {
+            match receiver.recv() {
+                Ok(encoded) => on_encoded(encoded),
+                Err(_) => panic!("the channel of the encoding workers disconnected after {} of {} lines", done, line_ids.len())
+            }
+        }
+    }
+}
+
+/// The builder of an `Encoder`. Every `with_*` method returns the builder with the given parameter, and `build` creates the encoder.
+pub struct EncoderBuilder {
+    probes: Vec<Arc<BaseSequence>>,
+    probes_lsh: Option<LSH>,                            // a prebuilt LSH instance of the probes, e.g., loaded from a cache
+    n_workers: usize,
+    raptor: RaptorQ,
+    overhead: usize,
+    target_redundancy: Option<f64>,
+    gc_hp_rules: GcHpRules,
+    base_hp_rules: BaseHpRules,
+    gc_window_rules: GcWindowRules,
+    motif_filter: MotifFilter,
+    tm_rules: TmRules,
+    entropy_rules: EntropyRules,
+    hairpin_rules: HairpinRules,
+    rules: Vec<Box<dyn Rule + Send + Sync>>,            // the constraints added by `with_rule`
+    min_dist_to_probes: f64,
+    min_dist_to_seqs: f64,
+    min_dist_to_seqs_schedule: MinDistSchedule,
+    check_revcomp: bool,
+    probes_backend: usize,
+    seqs_backend: usize,
+    distance_metric: usize,
+    lsh_k_probes: usize,
+    lsh_r_probes: usize,
+    lsh_b_probes: usize,
+    lsh_k_seqs: usize,
+    lsh_r_seqs: usize,
+    lsh_b_seqs: usize,
+    lsh_permutation: usize,
+    lsh_bucketing: usize,
+    lsh_cgk_len: usize,
+    canonical_k_mers: bool,
+    minhash_prefilter_margin: f64,
+    dg_client: Option<DGClient>,
+    dg_rules: DgRules,
+    dg_min_len: usize,
+    sec_struct_temp: f32,
+    per_line_timeout_ms: u64,
+    on_retry: Option<RetryCallback>,
+    seed: Option<u64>
+}
+
+impl EncoderBuilder {
+    /// Creates a builder of an encoder that keeps the Info-DNAs away from `probes` with the default parameters, i.e., approximate similarity backends (LSH) for the probes and the Info-DNAs, no dg server, and no seed.
+    pub fn new(probes: Vec<Arc<BaseSequence>>) -> Self {
+        Self {
+            probes,
+            probes_lsh: None,
+            n_workers: num_cpus::get(),
+            raptor: RaptorQ::default(),
+            overhead: DEFAULT_OVERHEAD,
+            target_redundancy: None,
+            gc_hp_rules: GcHpRules::new(dna_rules::MIN_GC_CONTENT, dna_rules::MAX_GC_CONTENT, DEFAULT_MAX_HP_LEN),
+            base_hp_rules: BaseHpRules::new([0_usize; 4]),
+            gc_window_rules: GcWindowRules::new(DEFAULT_GC_WINDOW, DEFAULT_MIN_WINDOW_GC, DEFAULT_MAX_WINDOW_GC),
+            motif_filter: MotifFilter::new(Vec::new()),
+            tm_rules: TmRules::new(DEFAULT_MIN_TM, DEFAULT_MAX_TM),
+            entropy_rules: EntropyRules::new(DEFAULT_MIN_ENTROPY),
+            hairpin_rules: HairpinRules::new(DEFAULT_MAX_HAIRPIN_STEM, DEFAULT_HAIRPIN_MIN_LOOP),
+            rules: Vec::new(),
+            min_dist_to_probes: DEFAULT_MIN_DIST_TO_PROBES,
+            min_dist_to_seqs: DEFAULT_MIN_DIST_TO_SEQS,
+            min_dist_to_seqs_schedule: MinDistSchedule::new(DEFAULT_MIN_DIST_TO_SEQS_DECAY_START, DEFAULT_MIN_DIST_TO_SEQS_DECAY_END, DEFAULT_MIN_DIST_TO_SEQS_FLOOR),
+            check_revcomp: DEFAULT_CHECK_REVCOMP,
+            probes_backend: SIMILARITY_BACKEND_APPROX,
+            seqs_backend: SIMILARITY_BACKEND_APPROX,
+            distance_metric: similarity::DISTANCE_METRIC_JACCARD,
+            lsh_k_probes: DEFAULT_LSH_K_PROBES,
+            lsh_r_probes: DEFAULT_LSH_R_PROBES,
+            lsh_b_probes: DEFAULT_LSH_B_PROBES,
+            lsh_k_seqs: DEFAULT_LSH_K_SEQS,
+            lsh_r_seqs: DEFAULT_LSH_R_SEQS,
+            lsh_b_seqs: DEFAULT_LSH_B_SEQS,
+            lsh_permutation: pseudo_permutation::PERMUTATION_PSEUDO,
+            lsh_bucketing: lsh::LSH_BUCKETING_MINHASH,
+            lsh_cgk_len: DEFAULT_LSH_CGK_LEN,
+            canonical_k_mers: DEFAULT_CANONICAL_K_MERS,
+            minhash_prefilter_margin: DEFAULT_MINHASH_PREFILTER_MARGIN,
+            dg_client: None,
+            dg_rules: DgRules::new(dna_rules::DG_ERROR_OFFSET, dna_rules::DG_ERROR_STEEPNESS, dna_rules::MAX_DG_ERROR),
+            dg_min_len: DEFAULT_DG_MIN_LEN,
+            sec_struct_temp: DEFAULT_SECONDARY_STRUCT_TEMP,
+            per_line_timeout_ms: DEFAULT_PER_LINE_TIMEOUT_MS,
+            on_retry: None,
+            seed: None
+        }
+    }
+
+    /// Returns the probes of the encoder.
+    pub fn probes(&self) -> &[Arc<BaseSequence>] {
+        &self.probes
+    }
+
+    /// Returns this builder with `n_workers` threads for encoding, building the probes' LSH instance, generating packets, and checking distances.
+    pub fn with_workers(mut self, n_workers: usize) -> Self {
+        self.n_workers = n_workers;
+        self
+    }
+
+    /// Returns this builder with the RQ configuration `raptor`.
+    pub fn with_raptor(mut self, raptor: RaptorQ) -> Self {
+        self.raptor = raptor;
+        self
+    }
+
+    /// Returns this builder with the overhead ε for RQ.
+    pub fn with_overhead(mut self, overhead: usize) -> Self {
+        self.overhead = overhead;
+        self
+    }
+
+    /// Returns this builder with the target redundancy, i.e., the ratio of the packets in an Info-DNA to the source symbols of its data object (e.g., 1.2).
+    /// If set, the overhead ε of every data object is derived from its size and overrides `with_overhead`. Panics if the redundancy is less than 1.
+    pub fn with_target_redundancy(mut self, target_redundancy: Option<f64>) -> Self {
+        if let Some(redundancy) = target_redundancy.filter(|redundancy| redundancy.is_nan() || *redundancy < 1_f64) {
+            panic!("target_redundancy ({}) must be at least 1", redundancy);
+        }
+        self.target_redundancy = target_redundancy;
+        self
+    }
+
+    /// Returns this builder with the constraints on the GC content and the maximum homopolymer length.
+    pub fn with_gc_hp_rules(mut self, gc_hp_rules: GcHpRules) -> Self {
+        self.gc_hp_rules = gc_hp_rules;
+        self
+    }
+
+    /// Returns this builder with the constraint on the longest homopolymer of every base. It applies in addition to the maximum homopolymer length of `with_gc_hp_rules`.
+    pub fn with_base_hp_rules(mut self, base_hp_rules: BaseHpRules) -> Self {
+        self.base_hp_rules = base_hp_rules;
+        self
+    }
+
+    /// Returns this builder with the constraint on the local GC content.
+    pub fn with_gc_window_rules(mut self, gc_window_rules: GcWindowRules) -> Self {
+        self.gc_window_rules = gc_window_rules;
+        self
+    }
+
+    /// Returns this builder with the filter that rejects Info-DNAs containing a forbidden motif.
+    pub fn with_motif_filter(mut self, motif_filter: MotifFilter) -> Self {
+        self.motif_filter = motif_filter;
+        self
+    }
+
+    /// Returns this builder with the constraint on the melting temperature.
+    pub fn with_tm_rules(mut self, tm_rules: TmRules) -> Self {
+        self.tm_rules = tm_rules;
+        self
+    }
+
+    /// Returns this builder with the constraint on the dinucleotide entropy.
+    pub fn with_entropy_rules(mut self, entropy_rules: EntropyRules) -> Self {
+        self.entropy_rules = entropy_rules;
+        self
+    }
+
+    /// Returns this builder with the local constraint on hairpins.
+    pub fn with_hairpin_rules(mut self, hairpin_rules: HairpinRules) -> Self {
+        self.hairpin_rules = hairpin_rules;
+        self
+    }
+
+    /// Returns this builder with the additional constraint `rule` on every Info-DNA, e.g., a closure `|seq: &Arc<BaseSequence>| ...`. The added rules are checked in the order in which they were added,
+    /// after the built-in constraints and before the distance to the probes, and a strand that fails one of them is rejected as a `RejectReason::Constraint`. They do not apply to single packets.
+    pub fn with_rule(mut self, rule: impl Rule + Send + Sync + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Returns this builder with the minimum required distance of an Info-DNA to a probe.
+    pub fn with_min_dist_to_probes(mut self, min_dist_to_probes: f64) -> Self {
+        self.min_dist_to_probes = min_dist_to_probes;
+        self
+    }
+
+    /// Returns this builder with the minimum required distance of an Info-DNA to another Info-DNA.
+    pub fn with_min_dist_to_seqs(mut self, min_dist_to_seqs: f64) -> Self {
+        self.min_dist_to_seqs = min_dist_to_seqs;
+        self
+    }
+
+    /// Returns this builder with the schedule that relaxes the minimum distance to the other Info-DNAs as their pool grows.
+    pub fn with_min_dist_to_seqs_schedule(mut self, min_dist_to_seqs_schedule: MinDistSchedule) -> Self {
+        self.min_dist_to_seqs_schedule = min_dist_to_seqs_schedule;
+        self
+    }
+
+    /// Returns this builder that requires the minimum distances for the reverse complement of an Info-DNA, too, if `check_revcomp` is true.
+    pub fn with_check_revcomp(mut self, check_revcomp: bool) -> Self {
+        self.check_revcomp = check_revcomp;
+        self
+    }
+
+    /// Returns this builder with the similarity backends of the probes and the Info-DNAs, respectively (see `similarity::SIMILARITY_BACKEND_EXACT` and `similarity::SIMILARITY_BACKEND_APPROX`).
+    pub fn with_similarity_backends(mut self, probes_backend: usize, seqs_backend: usize) -> Self {
+        self.probes_backend = probes_backend;
+        self.seqs_backend = seqs_backend;
+        self
+    }
+
+    /// Returns this builder with the distance metric of the similarity backends (see `similarity::extract_distance_metric`).
+    pub fn with_distance_metric(mut self, distance_metric: usize) -> Self {
+        self.distance_metric = distance_metric;
+        self
+    }
+
+    /// Returns this builder with the k-mer length `k`, the number of hash functions `r`, and the number of bands `b` of the probes' LSH instance.
+    pub fn with_probes_lsh_params(mut self, k: usize, r: usize, b: usize) -> Self {
+        self.lsh_k_probes = k;
+        self.lsh_r_probes = r;
+        self.lsh_b_probes = b;
+        self
+    }
+
+    /// Returns this builder with the k-mer length `k`, the number of hash functions `r`, and the number of bands `b` of the Info-DNAs' LSH instance.
+    pub fn with_seqs_lsh_params(mut self, k: usize, r: usize, b: usize) -> Self {
+        self.lsh_k_seqs = k;
+        self.lsh_r_seqs = r;
+        self.lsh_b_seqs = b;
+        self
+    }
+
+    /// Returns this builder with the kind of the permutations of the LSH instances (see `pseudo_permutation::extract_permutation_kind`).
+    pub fn with_lsh_permutation(mut self, permutation_kind: usize) -> Self {
+        self.lsh_permutation = permutation_kind;
+        self
+    }
+
+    /// Returns this builder with the bucketing of the LSH instances (see `lsh::extract_lsh_bucketing`) and the length of the sequences embedded by the CGK bucketing.
+    pub fn with_lsh_bucketing(mut self, bucketing: usize, cgk_len: usize) -> Self {
+        self.lsh_bucketing = bucketing;
+        self.lsh_cgk_len = cgk_len;
+        self
+    }
+
+    /// Returns this builder whose LSH instances and Jaccard distance use canonical k-mers if `canonical` is true.
+    pub fn with_canonical_k_mers(mut self, canonical: bool) -> Self {
+        self.canonical_k_mers = canonical;
+        self
+    }
+
+    /// Returns this builder with the margin of the MinHash pre-filter of the Jaccard distance (infinity disables the pre-filter).
+    pub fn with_min_hash_prefilter(mut self, margin: f64) -> Self {
+        self.minhash_prefilter_margin = margin;
+        self
+    }
+
+    /// Returns this builder with the prebuilt LSH instance `probes_lsh` of the probes, e.g., loaded from a cache, instead of building it in `build`. Ignored if the probes use the exact similarity backend.
+    pub fn with_probes_lsh(mut self, probes_lsh: LSH) -> Self {
+        self.probes_lsh = Some(probes_lsh);
+        self
+    }
+
+    /// Returns this builder with the client object for communicating with the dg server. None disables the dg server, i.e., the dg check is skipped and every Info-DNA has no dg energy (`EncodedStrand::dg` is None, not 0).
+    pub fn with_dg_client(mut self, dg_client: Option<DGClient>) -> Self {
+        self.dg_client = dg_client;
+        self
+    }
+
+    /// Returns this builder with the constraint on the dg energy.
+    pub fn with_dg_rules(mut self, dg_rules: DgRules) -> Self {
+        self.dg_rules = dg_rules;
+        self
+    }
+
+    /// Returns this builder with the minimum length of a strand to query its dg energy. Shorter strands pass the dg check without a query.
+    pub fn with_dg_min_len(mut self, dg_min_len: usize) -> Self {
+        self.dg_min_len = dg_min_len;
+        self
+    }
+
+    /// Returns this builder with the temperature in °C at which the dg server predicts the secondary structures.
+    pub fn with_sec_struct_temp(mut self, sec_struct_temp: f32) -> Self {
+        self.sec_struct_temp = sec_struct_temp;
+        self
+    }
+
+    /// Returns this builder with the time limit in ms for encoding a single data object (0 = no limit). A data object that is not encoded in time is abandoned and reported as a `LineError`.
+    /// The limit is checked before every loop of RQ, i.e., a data object may exceed it by the time of a single loop.
+    pub fn with_per_line_timeout_ms(mut self, per_line_timeout_ms: u64) -> Self {
+        self.per_line_timeout_ms = per_line_timeout_ms;
+        self
+    }
+
+    /// Returns this builder with the callback `on_retry` that is called whenever a data object failed to encode within `MAX_ENCODE_LOOPS` loops of RQ and is retried with more packets per block (see `RetryCallback`), e.g., to print a warning.
+    /// It is called by the worker that encodes the data object.
+    pub fn with_on_retry(mut self, on_retry: impl Fn(usize, &EncodeError, usize) + Send + Sync + 'static) -> Self {
+        self.on_retry = Some(Box::new(on_retry));
+        self
+    }
+
+    /// Returns this builder with the seed for the random number generators of the LSH instances and the data objects. If set, the data objects are encoded one after another to obtain a deterministic output.
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Builds the LSH instance of the probes with the LSH parameters of this builder. Its source fingerprint is the fingerprint of the probes (see `SeqsFingerprint`).
+    pub fn build_probes_lsh(&self) -> LSH {
+        let lsh = new_lsh(self.lsh_bucketing, self.lsh_cgk_len, self.lsh_k_probes, self.lsh_r_probes, self.lsh_b_probes, self.lsh_permutation, &mut seeded_rng(self.seed, RNG_STREAM_PROBES_LSH))
+            .with_canonical_k_mers(self.canonical_k_mers)
+            .with_source_fingerprint(Some(SeqsFingerprint::of(&self.probes)))
+            .with_min_hash_cache(self.minhash_prefilter_margin.is_finite());
+        build_lsh(&self.probes, lsh, self.n_workers)
+    }
+
+    /// Creates the encoder, i.e., the similarity backends of the probes and the Info-DNAs and the thread pools.
+    pub fn build(mut self) -> Encoder {
+        let n_workers = self.n_workers;
+        let dist_pool = Arc::new(ThreadPoolBuilder::new().num_threads(n_workers).build().unwrap()); // the thread pool that is used to parallelize distance checks (shared by all workers, which spawn their checks concurrently)
+        let prefilter = self.minhash_prefilter_margin;
+        let probes_similarity: Arc<dyn SimilarityBackend> = if self.probes_backend == SIMILARITY_BACKEND_APPROX {
+            let probes_lsh = match self.probes_lsh.take() {
+                Some(lsh) => lsh,
+                None => self.build_probes_lsh()
+            };
+            let probes_approx = ApproxBackend::new(probes_lsh, self.distance_metric, dist_pool.clone());
+            Arc::new(if prefilter.is_finite() { probes_approx.with_min_hash_prefilter(prefilter) } else { probes_approx })
+        }
+        else {
+            Arc::new(ExactBackend::new(self.probes, self.lsh_k_probes, self.distance_metric, dist_pool.clone()).with_canonical_k_mers(self.canonical_k_mers))
+        };
+        let seqs_similarity: Arc<dyn SimilarityBackend> = if self.seqs_backend == SIMILARITY_BACKEND_APPROX {
+            let seqs_lsh = new_lsh(self.lsh_bucketing, self.lsh_cgk_len, self.lsh_k_seqs, self.lsh_r_seqs, self.lsh_b_seqs, self.lsh_permutation, &mut seeded_rng(self.seed, RNG_STREAM_SEQS_LSH));
+            let seqs_approx = ApproxBackend::new(seqs_lsh.with_canonical_k_mers(self.canonical_k_mers), self.distance_metric, dist_pool);
+            Arc::new(if prefilter.is_finite() { seqs_approx.with_min_hash_prefilter(prefilter) } else { seqs_approx })
+        }
+        else {
+            Arc::new(ExactBackend::new(Vec::new(), self.lsh_k_seqs, self.distance_metric, dist_pool).with_canonical_k_mers(self.canonical_k_mers))
+        };
+
+        // the cheap built-in checks first, so that the user-supplied rules are only called for strands that satisfy them
+        let mut strand_rules: Vec<Box<dyn Rule + Send + Sync>> = vec![Box::new(self.motif_filter), Box::new(self.tm_rules), Box::new(self.entropy_rules), Box::new(self.hairpin_rules)];
+        strand_rules.append(&mut self.rules);
+
+        let line_encoder = LineEncoder {
+            // the thread pool that generates the packets of large blocks in parallel (not used if seeded, since a thread waiting for this pool may start encoding another data object, which would change the order in which Info-DNAs are accepted)
+            raptor: Arc::new(match self.seed {
+                Some(_) => self.raptor,
+                None => self.raptor.with_packet_pool(Arc::new(ThreadPoolBuilder::new().num_threads(n_workers).build().unwrap()))
+            }),
+            probes_similarity,
+            seqs_similarity,
+            emitted: Arc::new(Mutex::new(HashSet::new())),
+            overhead: self.overhead,
+            target_redundancy: self.target_redundancy,
+            gc_hp_rules: self.gc_hp_rules,
+            base_hp_rules: self.base_hp_rules,
+            gc_window_rules: self.gc_window_rules,
+            entropy_rules: self.entropy_rules,
+            strand_rules: Arc::new(strand_rules),
+            min_dist_to_probes: self.min_dist_to_probes,
+            min_dist_to_seqs: self.min_dist_to_seqs,
+            min_dist_to_seqs_schedule: self.min_dist_to_seqs_schedule,
+            check_revcomp: self.check_revcomp,
+            dg_client: self.dg_client,
+            dg_rules: self.dg_rules,
+            dg_min_len: self.dg_min_len,
+            sec_struct_temp: self.sec_struct_temp,
+            per_line_timeout: Some(Duration::from_millis(self.per_line_timeout_ms)).filter(|timeout| !timeout.is_zero()),
+            on_retry: self.on_retry
+        };
+
+        Encoder {
+            line_encoder: Arc::new(line_encoder),
+            // a single thread if seeded, since the order in which Info-DNAs are accepted must not depend on timing
+            pool: ThreadPoolBuilder::new().num_threads(if self.seed.is_some() { 1_usize } else { n_workers }).build().unwrap(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            seed: self.seed
+        }
+    }
+}
+
+/// Returns a random number generator seeded with `seed` + `stream` if `seed` is set, else a random number generator seeded from the operating system's entropy.
+fn seeded_rng(seed: Option<u64>, stream: u64) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(stream)),
+        None => StdRng::from_entropy()
+    }
+}
+
+/// Creates an empty LSH instance with `r` hash functions and `b` bands that uses the bucketing `bucketing`, i.e., MinHash of the `k`-mers with permutations of the kind `permutation_kind`,
+/// or the CGK embeddings of sequences of length `cgk_len`, both drawn from `rng`.
+fn new_lsh(bucketing: usize, cgk_len: usize, k: usize, r: usize, b: usize, permutation_kind: usize, rng: &mut StdRng) -> LSH {
+    if bucketing == lsh::LSH_BUCKETING_CGK {
+        LSH::new_cgk_with_rng(cgk_len, r, b, rng)
+    }
+    else {
+        LSH::new_with_permutation_kind(k, r, b, permutation_kind, rng)
+    }
+}
+
+/// Inserts all `probes` into the empty LSH instance `probes_lsh` in parallel using `n_workers` threads.
+fn build_lsh(probes: &[Arc<BaseSequence>], probes_lsh: LSH, n_workers: usize) -> LSH {
+    let insert_pool = ThreadPoolBuilder::new().num_threads(n_workers).build().unwrap();
+
+    // insert all read probes into the probes' LSH instance in parallel (halts until all probes were inserted into the LSH)
+    insert_pool.scope(|scope| {
+        for probe in probes.iter() {
+            let probes_lsh_ref = &probes_lsh;
+            scope.spawn(move |_| { probes_lsh_ref.insert(probe); });
+        }
+    });
+    probes_lsh
+}
+
+/// The function that encodes a single data object.
+///
+/// # Arguments
+///
+/// * `line` - The data object that will be encoded. line.0 is the 1-based id of that object (its index in the lines + 1), and line.1 contains the data object.
+/// * `encoder` - The parameters and the state shared by all data objects, i.e., the RQ configuration, the similarity backends of the probes and of the Info-DNAs encoded so far, the set of emitted Info-DNAs, the constraints, the minimum distances, and the dg server.
+/// * `sender` - The channel's sender that is used to send the encoding result to.
+/// * `packets_per_block` - The number of packets initially generated by RQ. Increased by `INITIAL_PACKETS_PER_BLOCK` whenever RQ failed to find an Info-DNA within `MAX_ENCODE_LOOPS` loops, which is reported to `encoder.on_retry`.
+/// * `rng` - The random number generator that determines the order in which RQ packets are combined.
+///
+/// The overhead ε is derived from `encoder.target_redundancy` if set, and `encoder.min_dist_to_seqs` is relaxed by the number of Info-DNAs emitted when the data object starts encoding (see `MinDistSchedule`).
+/// Strands that violate a constraint are rejected without querying the dg server, and the data object is abandoned and sent as a `LineError` once `encoder.per_line_timeout` elapsed (checked before every loop of RQ).
+#[inline(always)]
+fn encode_file(line: (usize, Arc<Vec<u8>>),
+               encoder: &LineEncoder,
+               sender: Sender<Result<EncodedStrand, LineError>>,
+               mut packets_per_block: usize,
+               mut rng: StdRng) {
+
+    let LineEncoder { raptor: raptor_cloned, probes_similarity, seqs_similarity, emitted, gc_hp_rules, base_hp_rules, gc_window_rules, entropy_rules, strand_rules, dg_client, dg_rules, .. } = encoder;
+    let (min_dist_to_probes, check_revcomp, dg_min_len, sec_struct_temp) = (encoder.min_dist_to_probes, encoder.check_revcomp, encoder.dg_min_len, encoder.sec_struct_temp);
+    let start_time = SystemTime::now();
+    let mut trails = 0_usize;
+    let rejections = Cell::new(Rejections::default());
+    let overhead = encoder.target_redundancy.map_or(encoder.overhead, |redundancy| raptor_cloned.overhead_for_redundancy(line.1.len(), redundancy));
+    let min_dist_to_seqs = encoder.min_dist_to_seqs_schedule.min_dist(encoder.min_dist_to_seqs, emitted.lock().len());
+
+    let gc_and_hp_check = |seq: &Arc<BaseSequence>| gc_hp_rules.satisfies(seq) && base_hp_rules.satisfies(seq) && gc_window_rules.satisfies(seq) && entropy_rules.satisfies(seq); // A closure that checks GC, HP, the HP per base, windowed GC, and the entropy
+    // A closure that checks the error via the dg server and returns the dg energy (NaN if the query was skipped or failed), or None without a dg server, i.e., no strand is checked
+    let dg_rule = dg_client.as_ref().map(|client| {
+        let rejections = &rejections;
+        move |seq: &Arc<BaseSequence>| {
+            if seq.len() < dg_min_len {
+                return (true, f32::NAN); // short strands rarely form secondary structures -> skip the dg server
+            }
+            let (satisfied, dg) = match client.dg_arc(seq, sec_struct_temp) {
+                Ok(dg) => (dg_rules.satisfies(dg), dg),
+                Err(_) => (false, f32::NAN) // the dg server did not answer (e.g., timeout) -> reject the sequence
+            };
+            (reject_unless(satisfied, RejectReason::Dg, rejections), dg)
+        }
+    });
+    let strand_gc_hp_rule = |gc: f64, longest_hp: usize|
+        reject_unless(gc_hp_rules.satisfies_gc(gc), RejectReason::Gc, &rejections)
+            && reject_unless(gc_hp_rules.satisfies_hp(longest_hp), RejectReason::Homopolymer, &rejections); // A closure that checks GC and HP of a strand given its statistics
+    let strand_rule = |seq: &Arc<BaseSequence>|
+        reject_unless(base_hp_rules.satisfies(seq), RejectReason::Homopolymer, &rejections)
+            && reject_unless(gc_window_rules.satisfies(seq), RejectReason::Gc, &rejections)
+            && reject_unless(dna_rules::satisfies_all(strand_rules, seq), RejectReason::Constraint, &rejections)
+            && reject_unless(probes_similarity.is_far_enough(seq, min_dist_to_probes, check_revcomp), RejectReason::ProbeDistance, &rejections); // A closure that checks the HP per base, windowed GC, the strand rules (forbidden motifs, the melting temperature, the entropy, local hairpins, and the user-supplied rules), and the distance to the probes (before the dg server is queried)
+
+    let rules = EncodeRules::accept_all()
+        .with_packet_check(gc_and_hp_check)
+        .with_strand_gc_hp_check(strand_gc_hp_rule)
+        .with_strand_check(strand_rule)
+        .with_dg_check(dg_rule);
+
+    let mut rq_time_total = Duration::new(0_u64, 0_u32);
+    let mut dg_time_total = Duration::new(0_u64, 0_u32);
+
+    let (result_seq, result_dg, packets_used, achieved_overhead) = loop {
+        if let Some(timeout) = encoder.per_line_timeout {
+            let elapsed = SystemTime::now().duration_since(start_time).unwrap();
+            if elapsed >= timeout {
+                let message = format!("timed out after {} ms and {} trials", elapsed.as_millis(), trails);
+                let _ = sender.send(Err(LineError { line_id: line.0, message, kind: LineErrorKind::TimedOut }));
+                return;
+            }
+        }
+        trails += 1_usize;
+        let (encoded_seq, rq_time, dg_time, dg, packets_used, achieved_overhead) = match raptor_cloned.encode_to_dna_with_rules(line.1.as_slice(), EncodeLimits::new(packets_per_block, MAX_ENCODE_LOOPS, overhead).with_deadline(encoder.per_line_timeout.map(|timeout| start_time + timeout)), &mut rng, &rules) {
+            Ok(encoded) => encoded,
+            // no Info-DNA satisfied all constraints -> never emit the best candidate, but retry with more packets
+            Err(e @ EncodeError::MaxLoopsExceeded { rq_time, dg_time, .. }) => {
+                rq_time_total += rq_time;
+                dg_time_total += dg_time;
+                packets_per_block += INITIAL_PACKETS_PER_BLOCK;
+                if let Some(on_retry) = &encoder.on_retry {
+                    on_retry(line.0, &e, packets_per_block);
+                }
+                continue;
+            }
+        };
+
+        dg_time_total += dg_time;
+        rq_time_total += rq_time;
+        let time_at_arrival = SystemTime::now();
+        // check the distance to the Info-DNAs encoded so far and insert the encoded sequence consistently (i.e., without missing a sequence because of parallelism)
+        // an exact duplicate of an emitted Info-DNA is always rejected, even if `min_dist_to_seqs` is 0, so the Info-DNA is reserved before it is inserted and released if it is too close
+        if !emitted.lock().insert(encoded_seq.clone()) {
+            reject(RejectReason::Duplicate, &rejections);
+            continue;
+        }
+        if seqs_similarity.insert_if_far_enough(&encoded_seq, min_dist_to_seqs, check_revcomp) {
+            rq_time_total += SystemTime::now().duration_since(time_at_arrival).unwrap();
+            break (encoded_seq, Some(dg).filter(|dg| !dg.is_nan()), packets_used, achieved_overhead); // a passing NaN means the dg query was skipped
+        }
+        emitted.lock().remove(&encoded_seq);
+        reject(RejectReason::SeqDistance, &rejections);
+    };
+
+    let _ = sender.send(Ok(EncodedStrand {
+        line_id: line.0,
+        sequence: result_seq,
+        trials: trails,
+        bytes: line.1.len(),
+        rq_time: rq_time_total.as_millis(),
+        dg_time: dg_client.as_ref().map(|_| dg_time_total.as_millis()),
+        total_time: SystemTime::now().duration_since(start_time).unwrap().as_millis(),
+        dg: result_dg,
+        overhead,
+        packets_used,
+        achieved_overhead,
+        min_dist_to_seqs,
+        rejections: rejections.get()
+    }));
+}
+
+/// Returns `satisfied` and records a rejection for `reason` in `rejections` if it is false.
+#[inline(always)]
+fn reject_unless(satisfied: bool, reason: RejectReason, rejections: &Cell<Rejections>) -> bool {
+    if !satisfied {
+        reject(reason, rejections);
+    }
+    satisfied
+}
+
+/// Records a rejection for `reason` in `rejections`.
+#[inline(always)]
+fn reject(reason: RejectReason, rejections: &Cell<Rejections>) {
+    let mut tally = rejections.get();
+    tally.record(reason);
+    rejections.set(tally);
+}
+
+/// The schedule that relaxes the minimum distance of an Info-DNA to the other Info-DNAs as their pool grows, since every Info-DNA makes it harder to satisfy.
+/// The minimum distance is kept until the pool holds `start` Info-DNAs, then decays linearly to `floor` until the pool holds `end` Info-DNAs, and stays at `floor` afterwards. A `floor` above the minimum distance never raises it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinDistSchedule {
+    pub start: usize,  // the number of Info-DNAs after which the decay starts
+    pub end: usize,    // the number of Info-DNAs at which `floor` is reached (0 = no decay)
+    pub floor: f64     // the smallest minimum distance
+}
+
+impl MinDistSchedule {
+    /// Returns the schedule that decays the minimum distance from `start` to `end` Info-DNAs down to `floor`, or keeps it if `end` is 0. Panics if `end` is not 0 and smaller than `start`.
+    pub fn new(start: usize, end: usize, floor: f64) -> Self {
+        if end != 0_usize && end < start {
+            panic!("the decay of the minimum distance must end ({}) after it starts ({})", end, start);
+        }
+        Self {
+            start,
+            end,
+            floor
+        }
+    }
+
+    /// Returns true if this schedule relaxes the minimum distance at all.
+    pub fn is_enabled(&self) -> bool {
+        self.end != 0_usize
+    }
+
+    /// Returns the minimum distance `min_dist` relaxed for a pool of `pool_size` Info-DNAs.
+    pub fn min_dist(&self, min_dist: f64, pool_size: usize) -> f64 {
+        if !self.is_enabled() || pool_size <= self.start || self.floor >= min_dist {
+            return min_dist;
+        }
+        if pool_size >= self.end {
+            return self.floor;
+        }
+        let progress = (pool_size - self.start) as f64 / (self.end - self.start) as f64;
+        min_dist - progress * (min_dist - self.floor)
+    }
+}
+
+/// The error passed to the collector of `Encoder::encode_with` when encoding a data object panicked, timed out, or was cancelled.
+#[derive(Debug)]
+pub struct LineError {
+    pub line_id: usize,      // the 1-based id of the data object as sent by `encode_file`
+    pub message: String,     // the panic message, or the reason of the timeout or cancellation
+    pub kind: LineErrorKind  // why the data object was not encoded
+}
+
+/// The reason why a data object was not encoded (see `LineError`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineErrorKind {
+    Panicked,  // encoding the data object panicked
+    TimedOut,  // the data object exceeded the per-line timeout
+    Cancelled  // the encoder was cancelled before the data object started encoding (see `Encoder::cancel`)
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to encode line {}: {}", self.line_id, self.message)
+    }
+}
+
+impl std::error::Error for LineError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SEED: u64 = 7_u64;
+
+    fn probes() -> Vec<Arc<BaseSequence>> {
+        vec![Arc::new(BaseSequence::try_from_str("ACGTTGCAACGGTTCCAAGGTTAACCGGTT").unwrap())]
+    }
+
+    /// Returns a seeded encoder that accepts Info-DNAs at any distance to each other, so only the emitted set rejects duplicates.
+    fn encoder_without_min_dist() -> Encoder {
+        Encoder::builder(probes()).with_workers(1_usize).with_min_dist_to_seqs(0_f64).with_seed(Some(SEED)).build()
+    }
+
+    #[test]
+    fn duplicate_is_rejected_and_retried() {
+        let line = b"hello DNA storage".to_vec();
+        let first = encoder_without_min_dist().encode_strands(&[line.clone()]).remove(0);
+        assert_eq!(first.rejections.get(RejectReason::Duplicate), 0_usize);
+
+        // the same seed yields the same first candidate, which is now a duplicate
+        let encoder = encoder_without_min_dist();
+        encoder.insert(&first.sequence);
+        let second = encoder.encode_strands(&[line.clone()]).remove(0);
+        assert!(second.rejections.get(RejectReason::Duplicate) >= 1_usize);
+        assert!(second.trials > first.trials);
+        assert_ne!(second.sequence, first.sequence);
+        assert_eq!(encoder.raptor().decode_from_dna(&second.sequence), Some(line));
+    }
+
+    #[test]
+    fn line_ids_are_1_based() {
+        let lines = ["first", "second", "third"].iter().map(|line| Arc::new(line.as_bytes().to_vec())).collect::<Vec<_>>();
+        let encoder = encoder_without_min_dist();
+        let mut encoded = vec![];
+        encoder.encode_with(&lines, &[2_usize, 0_usize], |result| encoded.push(result.unwrap()));
+        encoded.sort_by_key(|strand| strand.line_id);
+        assert_eq!(encoded.iter().map(|strand| strand.line_id).collect::<Vec<_>>(), vec![1_usize, 3_usize]);
+        for strand in encoded.iter() {
+            assert_eq!(encoder.raptor().decode_from_dna(&strand.sequence).as_ref(), Some(lines[strand.line_id - 1_usize].as_ref()));
+        }
+    }
+
+    #[test]
+    fn line_that_never_converges_times_out() {
+        let encoder = Encoder::builder(probes()).with_workers(1_usize).with_seed(Some(SEED))
+            .with_rule(|_: &Arc<BaseSequence>| false)
+            .with_per_line_timeout_ms(100_u64)
+            .build();
+        let start = SystemTime::now();
+        let mut results = vec![];
+        encoder.encode_with(&[Arc::new(b"never encoded".to_vec())], &[0_usize], |result| results.push(result));
+        let elapsed = SystemTime::now().duration_since(start).unwrap();
+        match results.as_slice() {
+            [Err(e)] => {
+                assert!(matches!(e.kind, LineErrorKind::TimedOut));
+                assert_eq!(e.line_id, 1_usize);
+            }
+            _ => panic!("the line was not abandoned")
+        }
+        assert!(elapsed >= Duration::from_millis(100_u64));
+        assert!(elapsed < Duration::from_secs(30_u64), "{:?}", elapsed);
+    }
+}
@@ -0,0 +1,18 @@
+//! RQPAP encodes data objects into DNA fragments (Info-DNAs) with RaptorQ codes, such that every Info-DNA satisfies the DNA constraints and keeps a minimum distance to a set of probes and to the other Info-DNAs.
+//! Build an `Encoder` with `Encoder::builder` and call `Encoder::encode` or `Encoder::encode_strands` to encode data objects programmatically. The `RQPAP` binary is a command line interface to this library.
+pub mod lsh;
+pub mod pseudo_permutation;
+pub mod arg_parser;
+pub mod base_sequence;
+pub mod packed_sequence;
+pub mod dna_rules;
+pub mod raptor;
+pub mod dg_client;
+#[cfg(feature = "async")]
+pub mod dg_client_async;
+pub mod similarity;
+pub mod cgk_embedding;
+pub mod shingled_sequence;
+pub mod encoder;
+
+pub use encoder::{Encoder, EncoderBuilder, EncodedStrand, MinDistSchedule, RejectReason, Rejections};
@@ -1,71 +1,260 @@
 use std::collections::{HashMap, HashSet};
 use crate::base_sequence::{BaseSequence, Base};
-use crate::pseudo_permutation::PseudoPermutation;
+use crate::pseudo_permutation::{PseudoPermutation, ExactPermutation, Permutation, PERMUTATION_EXACT, PERMUTATION_PSEUDO};
 use std::collections::hash_map::RandomState;
 use std::sync::Arc;
 use parking_lot::{RwLock, Mutex, RawRwLock};
 use std::hash::Hash;
 use std::ops::{DerefMut, Deref};
-use crate::safe_cell::SafeCell;
+use crate::cgk_embedding::CgkEmbedding;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::fmt;
+use std::cmp::Reverse;
+
+static LSH_FILE_MAGIC: &[u8; 8] = b"RQPAPLSH"; // the first bytes of a persisted LSH instance (without the canonical k-mers flag)
+static LSH_FILE_MAGIC_V2: &[u8; 8] = b"RQPAPLS2"; // the first bytes of a persisted LSH instance with the canonical k-mers flag
+static LSH_FILE_MAGIC_V3: &[u8; 8] = b"RQPAPLS3"; // the first bytes of a persisted LSH instance with the canonical k-mers flag and the CGK embeddings
+static LSH_FILE_MAGIC_V4: &[u8; 8] = b"RQPAPLS4"; // the first bytes of a persisted LSH instance with the canonical k-mers flag, the reverse complement flag, and the CGK embeddings
+static LSH_FILE_MAGIC_V5: &[u8; 8] = b"RQPAPLS5"; // the first bytes of a persisted LSH instance with the canonical k-mers flag, the reverse complement flag, the fingerprint of its source sequences, and the CGK embeddings
+
+pub static MAX_K: usize                       = 31_usize;            // the largest k whose row-ids of the k-mers (0..4^k) fit into a usize
+static NO_K_MERS_MIN_HASH: usize              = usize::MAX;          // every min-hash of a sequence shorter than k, which has no k-mers (a permutation never yields it)
+
+pub static LSH_BUCKETING_MINHASH: usize       = 0_usize;             // bucketing "minhash" (MinHash of the k-mers, approximating the Jaccard similarity) is represented as 0
+pub static LSH_BUCKETING_CGK: usize           = 1_usize;             // bucketing "cgk" (bit sampling of the CGK embedding, approximating the edit distance) is represented as 1
 
 pub struct LSH {
     k: usize,
     band_size: usize,
     bands: Vec<RwLock<HashMap<String, HashSet<Arc<BaseSequence>>>>>,
-    permutations: Vec<PseudoPermutation>
+    permutations: Vec<Permutation>,
+    canonical: bool,
+    index_revcomp: bool,           // whether a sequence is also inserted into the buckets of its reverse complement
+    embeddings: Vec<CgkEmbedding>, // the CGK embedding of each band (empty = MinHash bucketing)
+    source_fingerprint: Option<SeqsFingerprint>, // the fingerprint of the sequences the instance was built from (None = unknown)
+    min_hash_cache: Option<RwLock<HashMap<Arc<BaseSequence>, Vec<usize>>>> // the min-hashes of the inserted sequences (None = not cached)
 }
 
 impl LSH {
-    /// Creates an LSH instance that is completely thread-safe.
+    /// Creates an LSH instance that is completely thread-safe. Two instances created with the same `seed` produce identical signatures.
     /// # Arguments
     /// * `k` - The length of the k-mers.
     /// * `r` - The number of hash functions.
     /// * `b` - The number of bands.
-    pub fn new(k: usize, r: usize, b: usize) -> Self {
-        if r % b != 0_usize {
+    /// * `seed` - The seed of the random number generator that draws the permutations (None = drawn from the thread's random number generator, i.e., not reproducible).
+    pub fn new(k: usize, r: usize, b: usize, seed: Option<u64>) -> Self {
+        match seed {
+            Some(seed) => Self::new_with_rng(k, r, b, &mut StdRng::seed_from_u64(seed)),
+            None => Self::new_with_rng(k, r, b, &mut rand::thread_rng())
+        }
+    }
+
+    /// Creates an LSH instance whose permutations are drawn from `rng`. Use a seeded `rng` to obtain reproducible signatures.
+    /// # Arguments
+    /// * `k` - The length of the k-mers.
+    /// * `r` - The number of hash functions.
+    /// * `b` - The number of bands.
+    /// * `rng` - The random number generator used to draw the permutations.
+    pub fn new_with_rng(k: usize, r: usize, b: usize, rng: &mut impl Rng) -> Self {
+        Self::new_with_permutation_kind(k, r, b, PERMUTATION_PSEUDO, rng)
+    }
+
+    /// Creates an LSH instance with the given kind of permutations that are drawn from `rng`.
+    /// `PERMUTATION_PSEUDO` uses a PseudoPermutation, which only approximates a permutation and may map two k-mers to the same min-hash, while `PERMUTATION_EXACT` uses an ExactPermutation.
+    /// Exact permutations store a table of 4^`k` entries each (4 bytes per entry), i.e., they require r * 4^`k` * 4 bytes and are only supported up to k = 12.
+    /// # Arguments
+    /// * `k` - The length of the k-mers.
+    /// * `r` - The number of hash functions.
+    /// * `b` - The number of bands.
+    /// * `permutation_kind` - The kind of the permutations (`PERMUTATION_PSEUDO` or `PERMUTATION_EXACT`).
+    /// * `rng` - The random number generator used to draw the permutations.
+    pub fn new_with_permutation_kind(k: usize, r: usize, b: usize, permutation_kind: usize, rng: &mut impl Rng) -> Self {
+        if !r.is_multiple_of(b) {
             panic!("r must be a multiple of b");
         }
-        if k > 33_usize {
-            panic!("this LSH only supports k-mers up to k = 33");
+        if k > MAX_K {
+            panic!("this LSH only supports k-mers up to k = {}, but k = {} was requested", MAX_K, k);
         }
 
         let k_mers = 4_usize.pow(k as u32);
-        let mut p = k_mers;
         let mut ps = Vec::with_capacity(r);
-        for _ in 0..r {
-            let permutation = PseudoPermutation::new_from_p(k_mers, p);
-            p = permutation.get_p();
-            ps.push(permutation);
+        if permutation_kind == PERMUTATION_EXACT {
+            for _ in 0..r {
+                ps.push(Permutation::Exact(ExactPermutation::new_with_rng(k_mers, rng)));
+            }
+        }
+        else {
+            let mut p = k_mers;
+            for _ in 0..r {
+                let permutation = PseudoPermutation::new_from_p_seeded(k_mers, p, rng);
+                p = permutation.get_p();
+                ps.push(Permutation::Pseudo(permutation));
+            }
         }
 
         LSH {
             k,
             band_size: r / b,
             bands: (0..b).map(|_| RwLock::new(HashMap::new())).collect::<Vec<_>>(),
-            permutations: ps
+            permutations: ps,
+            canonical: false,
+            index_revcomp: false,
+            embeddings: Vec::new(),
+            source_fingerprint: None,
+            min_hash_cache: None
         }
     }
 
-    /// Inserts `seq` into the LSH.
-    pub fn insert(&mut self, seq: &Arc<BaseSequence>) {
-        let sigs = self.signatures(seq);
-        for band in 0_usize..self.bands.len() {
-            let sig = sigs[band].as_str();
-            let mut map = self.bands.get_mut(band).unwrap().write();
+    /// Creates an LSH instance that buckets sequences by their CGK embeddings (see `CgkEmbedding`) instead of the MinHash of their k-mers, i.e., that approximates the edit distance instead of the Jaccard distance.
+    /// Each band samples `r` / `b` positions of its own embedding, and the "min-hashes" of a sequence are the symbols of its embeddings at the sampled positions.
+    /// Two sequences collide in a band if they agree on all of its positions, which is likely if their edit distance is small relative to `len`, even if it consists of indels.
+    /// # Arguments
+    /// * `len` - The length of the sequences to insert. Positions are sampled from the embedding of the first `len` bases.
+    /// * `r` - The number of hash functions, i.e., sampled positions.
+    /// * `b` - The number of bands.
+    /// * `rng` - The random number generator used to draw the embeddings.
+    pub fn new_cgk_with_rng(len: usize, r: usize, b: usize, rng: &mut impl Rng) -> Self {
+        if !r.is_multiple_of(b) {
+            panic!("r must be a multiple of b");
+        }
+
+        LSH {
+            k: 0_usize,
+            band_size: r / b,
+            bands: (0..b).map(|_| RwLock::new(HashMap::new())).collect::<Vec<_>>(),
+            permutations: Vec::new(),
+            canonical: false,
+            index_revcomp: false,
+            embeddings: (0..b).map(|_| CgkEmbedding::new_with_rng(len, r / b, rng)).collect::<Vec<_>>(),
+            source_fingerprint: None,
+            min_hash_cache: None
+        }
+    }
+
+    /// Sets whether the min-hashes are computed from the canonical k-mers (see `BaseSequence::canonical_k_mers_set`) instead of the k-mers, i.e., whether a sequence and its reverse complement have the same signatures.
+    /// Must be set before the first sequence is inserted.
+    pub fn with_canonical_k_mers(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// Sets the fingerprint of the sequences this instance is built from, which is persisted by `save`, so that a loaded instance can be checked against the current sequences (see `SeqsFingerprint`).
+    pub fn with_source_fingerprint(mut self, source_fingerprint: Option<SeqsFingerprint>) -> Self {
+        self.source_fingerprint = source_fingerprint;
+        self
+    }
+
+    /// Sets whether `insert` also adds a sequence to the buckets of its reverse complement, so that a query that is similar to the reverse complement of an inserted sequence returns that sequence as a candidate, too.
+    /// The buckets store the inserted sequence itself in both cases, i.e., `similar_seqs` returns the original sequence (once) regardless of the orientation it matched in, and callers that compare the candidates to the query should check both orientations.
+    /// This costs up to twice the buckets and an additional pass over the reverse complement per insertion. Must be set before the first sequence is inserted.
+    pub fn with_revcomp_indexing(mut self, enabled: bool) -> Self {
+        self.index_revcomp = enabled;
+        self
+    }
+
+    /// Sets whether the min-hashes of the inserted sequences are cached, which makes `estimated_jaccard` cheap for them at the cost of r * 8 bytes per sequence (e.g., 1.6 KB for r = 200).
+    /// The cache is not persisted by `save`, i.e., the min-hashes of the sequences of a loaded instance are cached as soon as they are needed.
+    pub fn with_min_hash_cache(mut self, enabled: bool) -> Self {
+        self.min_hash_cache = if enabled { self.min_hash_cache.take().or_else(|| Some(RwLock::new(HashMap::new()))) } else { None };
+        self
+    }
+
+    /// Inserts `seq` into the LSH and returns the token of this insertion, which can be passed to `rollback` to undo it, e.g., if `seq` turns out to be invalid after it was inserted speculatively. The token may be dropped if the insertion is final.
+    /// Every band is locked separately, so `insert` can be called from several threads at once, e.g., to build an instance in parallel. A concurrent query, however, may observe a partial insertion, i.e., `seq` is contained in some of its buckets only.
+    /// Hence, an instance that is queried while sequences are inserted must be guarded by a lock, e.g., a `RwLock` as in `ApproxBackend` that is written for inserts (see `LshInsertion`).
+    pub fn insert(&self, seq: &Arc<BaseSequence>) -> LshInsertion {
+        let min_hashes = self.min_hashes(seq);
+        let sigs = self.indexed_signatures_of(seq, &min_hashes);
+        let cached = match self.min_hash_cache.as_ref() {
+            Some(cache) => cache.write().insert(seq.clone(), min_hashes).is_none(),
+            None => false
+        };
+        let mut inserted_bands = Vec::with_capacity(sigs.len());
+        for (i, sig) in sigs.iter().enumerate() {
+            let band = i % self.bands.len();
+            let mut map = self.bands[band].write();
             match map.get_mut(sig) {
                 None => {
                     let mut set = HashSet::new();
                     set.insert(seq.clone());
                     map.insert(sig.to_owned(), set);
+                    inserted_bands.push(true);
                 }
                 Some(set) => {
-                    set.insert(seq.clone());
+                    inserted_bands.push(set.insert(seq.clone()));
                 }
             }
         }
+        LshInsertion {
+            seq: seq.clone(),
+            sigs,
+            inserted_bands,
+            cached
+        }
     }
 
-    /// Queries the LSh with `seq` and returns similar sequence it matches.
+    /// Undoes the insertion `insertion` returned by `insert`, i.e., removes its sequence from the buckets it was added to and from the min-hash cache if it was cached by this insertion. Buckets that become empty are removed as well.
+    /// The buckets in which the sequence was already contained before the insertion are left unchanged, so rolling back the insertion of a sequence that was inserted before keeps it in the LSH.
+    /// Returns true if the sequence was removed from at least one band. Rolling back insertions of the same sequence in another order than the reverse order of their insertion may remove a sequence that a remaining insertion expects to be contained.
+    pub fn rollback(&mut self, insertion: LshInsertion) -> bool {
+        if insertion.cached {
+            if let Some(cache) = self.min_hash_cache.as_ref() {
+                cache.write().remove(&insertion.seq);
+            }
+        }
+        let mut removed = false;
+        for (i, (sig, inserted)) in insertion.sigs.iter().zip(insertion.inserted_bands.iter()).enumerate() {
+            if *inserted {
+                let band = i % self.bands.len();
+                removed |= Self::remove_from_band(self.bands[band].get_mut(), sig, &insertion.seq);
+            }
+        }
+        removed
+    }
+
+    /// Removes `seq` from the LSH, i.e., also from the buckets of its reverse complement if `with_revcomp_indexing` is enabled. Buckets that become empty are removed as well. Returns true if `seq` was removed from at least one band.
+    pub fn remove(&mut self, seq: &Arc<BaseSequence>) -> bool {
+        let sigs = self.indexed_signatures_of(seq, &self.min_hashes(seq));
+        if let Some(cache) = self.min_hash_cache.as_ref() {
+            cache.write().remove(seq);
+        }
+        let mut removed = false;
+        for (i, sig) in sigs.iter().enumerate() {
+            let band = i % self.bands.len();
+            removed |= Self::remove_from_band(self.bands[band].get_mut(), sig, seq);
+        }
+        removed
+    }
+
+    /// Returns the signatures under which `seq` with the min-hashes `min_hashes` is stored, i.e., one per band, followed by one per band of its reverse complement if `with_revcomp_indexing` is enabled.
+    fn indexed_signatures_of(&self, seq: &Arc<BaseSequence>, min_hashes: &[usize]) -> Vec<String> {
+        let mut sigs = self.signatures_of(min_hashes);
+        if self.index_revcomp {
+            sigs.extend(self.signatures(&Arc::new(seq.reverse_complement())));
+        }
+        sigs
+    }
+
+    /// Removes `seq` from the bucket `sig` of the band `map` and removes the bucket if it becomes empty. Returns true if `seq` was contained in the bucket.
+    fn remove_from_band(map: &mut HashMap<String, HashSet<Arc<BaseSequence>>>, sig: &str, seq: &Arc<BaseSequence>) -> bool {
+        match map.get_mut(sig) {
+            Some(set) => {
+                if !set.remove(seq) {
+                    return false;
+                }
+                if set.is_empty() {
+                    map.remove(sig);
+                }
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Queries the LSh with `seq` and returns similar sequence it matches. Every candidate is returned once, even if it matches in several bands or, if `with_revcomp_indexing` is enabled, in both orientations.
     pub fn similar_seqs(&self, seq: &Arc<BaseSequence>) -> HashSet<Arc<BaseSequence>> {
         let sigs = self.signatures(seq);
         let mut result = HashSet::new();
@@ -83,14 +272,96 @@ impl LSH {
         result
     }
 
+    /// Returns the sequences that share at least one band with `seq` (see `similar_seqs`) together with the number of buckets they share with `seq`, sorted by this number in descending order.
+    /// A sequence that shares more buckets is more likely to be close to `seq`, so checking the candidates in this order finds a sequence that is too close early. Sequences sharing the same number of buckets are in an unspecified order.
+    pub fn similar_seqs_ranked(&self, seq: &Arc<BaseSequence>) -> Vec<(Arc<BaseSequence>, usize)> {
+        let sigs = self.signatures(seq);
+        let mut shared = HashMap::new();
+        for (band, sig) in self.bands.iter().zip(sigs.iter()) {
+            if let Some(set) = band.read().get(sig.as_str()) {
+                set.iter().for_each(|s| *shared.entry(s.clone()).or_insert(0_usize) += 1_usize);
+            }
+        }
+        let mut ranked = shared.into_iter().collect::<Vec<_>>();
+        ranked.sort_unstable_by_key(|(_, count)| Reverse(*count));
+        ranked
+    }
+
+    /// Returns the number of distinct sequences stored, i.e., a sequence that is contained in several buckets (one per band, or two per band if `with_revcomp_indexing` is enabled) is counted once.
+    pub fn len(&self) -> usize {
+        self.distinct_seqs().len()
+    }
+
+    /// Returns true if no sequence is stored.
+    pub fn is_empty(&self) -> bool {
+        self.bands.iter().all(|band| band.read().is_empty())
+    }
+
+    /// Removes all sequences (and their cached min-hashes), but keeps the parameters and permutations, i.e., the signatures of a sequence do not change.
+    pub fn clear(&mut self) {
+        self.bands.iter_mut().for_each(|band| band.get_mut().clear());
+        if let Some(cache) = self.min_hash_cache.as_mut() {
+            cache.get_mut().clear();
+        }
+    }
+
+    /// Returns an iterator over the distinct sequences stored, i.e., every sequence is yielded once regardless of the number of buckets it is contained in. The order is unspecified.
+    /// The iterator is a snapshot, i.e., sequences inserted or removed afterwards are not reflected.
+    pub fn iter(&self) -> impl Iterator<Item = Arc<BaseSequence>> {
+        self.distinct_seqs().into_iter()
+    }
+
+    /// Returns the distinct sequences of all buckets of all bands.
+    fn distinct_seqs(&self) -> HashSet<Arc<BaseSequence>> {
+        let mut seqs = HashSet::new();
+        for band in self.bands.iter() {
+            band.read().values().for_each(|set| seqs.extend(set.iter().cloned()));
+        }
+        seqs
+    }
+
+    /// Returns the statistics of the buckets of each band and the number of distinct sequences stored, e.g., to detect bands that collapse most sequences into a few buckets.
+    pub fn stats(&self) -> LshStats {
+        let mut sequences = HashSet::new();
+        let bands = self.bands.iter().map(|band| {
+            let map = band.read();
+            let sizes = map.values().map(|set| set.len()).collect::<Vec<_>>();
+            map.values().for_each(|set| sequences.extend(set.iter().cloned()));
+            BandStats {
+                buckets: sizes.len(),
+                max_bucket_size: sizes.iter().copied().max().unwrap_or(0_usize),
+                mean_bucket_size: if sizes.is_empty() { 0_f64 } else { sizes.iter().sum::<usize>() as f64 / sizes.len() as f64 }
+            }
+        }).collect::<Vec<_>>();
+
+        LshStats {
+            sequences: sequences.len(),
+            bands
+        }
+    }
+
+    /// Returns the number of candidates `similar_seqs` returns for each of the `queries`, e.g., for the distribution of the candidate set sizes of a sample of queries.
+    pub fn candidate_set_sizes(&self, queries: &[Arc<BaseSequence>]) -> Vec<usize> {
+        queries.iter().map(|query| self.similar_seqs(query).len()).collect()
+    }
+
+    /// Returns the r min-hashes of `seq`, or the symbols of the CGK embedding of `seq` at the sampled positions for the CGK bucketing.
+    /// A sequence shorter than k has no k-mers, i.e., all of its min-hashes are `usize::MAX`. Hence, such sequences share their buckets only with each other, but their estimated Jaccard similarity to every sequence is 0 (see `estimated_jaccard`).
     pub fn min_hashes(&self, seq: &Arc<BaseSequence>) -> Vec<usize> {
+        if !self.embeddings.is_empty() {
+            return self.embeddings.iter().flat_map(|embedding| embedding.apply(seq)).collect();
+        }
+        if seq.len() < self.k {
+            return vec![NO_K_MERS_MIN_HASH; self.permutations.len()];
+        }
         let mut min_hashes = Vec::with_capacity(self.permutations.len());
         let mut min_hash:usize;
         let mut perm_hash:usize;
-        for i in 0_usize..self.permutations.len() {
-            let p = &self.permutations[i];
+        // the row-ids of the k-mers are the same for every permutation
+        let shingles = Self::row_ids(seq.as_slice(), self.k, self.canonical);
+        for p in self.permutations.iter() {
             min_hash = usize::MAX;
-            for shingle in seq.k_mers(self.k).into_iter().map(|k_mer| Self::initial_row_id(k_mer)).collect::<Vec<_>>() {
+            for &shingle in shingles.iter() {
                 perm_hash = p.apply(shingle);
                 if perm_hash == 0_usize {
                     min_hash = 0_usize;
@@ -106,8 +377,45 @@ impl LSH {
         min_hashes
     }
 
+    /// Estimates the Jaccard similarity of `a` and `b` as the fraction of their min-hashes that match. The standard error of the estimate is sqrt(J * (1 - J) / r) for a Jaccard similarity J,
+    /// e.g., about 0.035 for J = 0.5 and r = 200, i.e., a larger `r` gives a more accurate estimate, but each min-hash costs a pass over the k-mers of a sequence that is not cached (see `with_min_hash_cache`).
+    /// A sequence shorter than k shares no k-mer with any sequence, so the estimate is 0 if `a` or `b` is shorter than k, even though the min-hashes of two such sequences match.
+    pub fn estimated_jaccard(&self, a: &Arc<BaseSequence>, b: &Arc<BaseSequence>) -> f64 {
+        self.estimated_jaccard_to(&self.min_hashes(a), b)
+    }
+
+    /// Estimates the Jaccard similarity of the sequence with the min-hashes `min_hashes` to `seq` (see `estimated_jaccard`). The min-hashes of `seq` are taken from the cache if enabled.
+    pub fn estimated_jaccard_to(&self, min_hashes: &[usize], seq: &Arc<BaseSequence>) -> f64 {
+        let matching = |other: &[usize]| match Self::has_k_mers(min_hashes) && Self::has_k_mers(other) {
+            true  => min_hashes.iter().zip(other.iter()).filter(|(a, b)| a == b).count() as f64 / min_hashes.len() as f64,
+            false => 0_f64
+        };
+        match self.min_hash_cache.as_ref() {
+            Some(cache) => {
+                if let Some(other) = cache.read().get(seq) {
+                    return matching(other);
+                }
+                let other = self.min_hashes(seq);
+                let estimate = matching(&other);
+                cache.write().insert(seq.clone(), other);
+                estimate
+            }
+            None => matching(&self.min_hashes(seq))
+        }
+    }
+
+    /// Returns false if `min_hashes` are those of a sequence shorter than k (see `min_hashes`).
+    #[inline]
+    fn has_k_mers(min_hashes: &[usize]) -> bool {
+        min_hashes.iter().any(|&min_hash| min_hash != NO_K_MERS_MIN_HASH)
+    }
+
     pub fn signatures(&self, seq: &Arc<BaseSequence>) -> Vec<String> {
-        let min_hashes = self.min_hashes(seq);
+        self.signatures_of(&self.min_hashes(seq))
+    }
+
+    /// Returns the signatures of the bands given the `min_hashes` of a sequence.
+    fn signatures_of(&self, min_hashes: &[usize]) -> Vec<String> {
         let mut sigs = Vec::with_capacity(self.bands.len());
         let mut offset = 0_usize;
 
@@ -118,16 +426,17 @@ impl LSH {
             }
 
             sigs.push(sb);
-            offset = offset + self.band_size;
+            offset += self.band_size;
         }
 
         sigs
     }
 
+    /// Returns the row-id of the k-mer `seq`, i.e., its bases as a number in base 4 with the first base as the least significant digit. Fits into a usize for k-mers up to `MAX_K`.
     pub fn initial_row_id(seq: &[Base]) -> usize {
         let mut id = 0_usize;
-        for i in 0_usize..seq.len() {
-            let order = match seq[i] {
+        for (i, base) in seq.iter().enumerate() {
+            let order = match base {
                 Base::A => 0,
                 Base::C => 1,
                 Base::G => 2,
@@ -137,20 +446,694 @@ impl LSH {
                 continue;
             }
 
-            id = id + order * 4_usize.pow(i as u32);
+            id += order * 4_usize.pow(i as u32);
         }
 
         id
     }
 
+    /// Returns the row-ids (see `initial_row_id`) of the k-mers of `seq` in order, or of their canonical k-mers if `canonical` is true (see `BaseSequence::canonical_k_mers_set`, but with duplicates).
+    /// Adjacent k-mers share k - 1 bases, so each row-id is rolled from the previous one in O(1) instead of O(k). Panics if `k` exceeds the length of `seq`.
+    pub fn row_ids(seq: &[Base], k: usize, canonical: bool) -> Vec<usize> {
+        if k > seq.len() {
+            panic!("cannot create kmers of k={} for seq of len {}", k, seq.len());
+        }
+        if k == 0_usize {
+            return vec![0_usize; seq.len() + 1];
+        }
+
+        let mask = usize::MAX >> (usize::BITS as usize - 2 * k); // 4^k - 1
+        let high = 2 * (k - 1);
+        // the row-id has the first base as the least significant digit, whereas the lexicographic id has it as the most significant one.
+        // Hence, the row-id of the reverse complement is mask - lexicographic id, and its lexicographic id is mask - row-id.
+        let mut row_id = Self::initial_row_id(&seq[..k]);
+        let mut lex_id = seq[..k].iter().fold(0_usize, |id, &base| id << 2 | base as usize);
+        let canonical_of = |row_id: usize, lex_id: usize| if mask - row_id < lex_id { mask - lex_id } else { row_id };
+        let mut ids = Vec::with_capacity(1 + seq.len() - k);
+        ids.push(if canonical { canonical_of(row_id, lex_id) } else { row_id });
+        for &base in seq[k..].iter() {
+            row_id = row_id >> 2 | (base as usize) << high;
+            lex_id = (lex_id << 2 | base as usize) & mask;
+            ids.push(if canonical { canonical_of(row_id, lex_id) } else { row_id });
+        }
+        ids
+    }
+
+    /// Persists the current LSH instance to `path` using a deterministic binary format (all integers are u64 big endian):
+    /// the magic bytes "RQPAPLS5", `k`, `band_size`, 1 if the canonical k-mers are used (else 0), 1 if the reverse complements are indexed (else 0), 1 if the source fingerprint is known (else 0) followed by its count and hash, the number of CGK embeddings followed by each embedding (number of random bits, the bits, number of positions, and the positions), the number of permutations followed by (m, p, a, b) of each permutation (an exact permutation is stored as (m, 0, 0, 0) followed by its m table entries),
+    /// the number of distinct sequences followed by each sequence (length and DNA bases as ASCII, sorted), and the number of bands
+    /// followed by each band's number of buckets and each bucket (signature length, signature, number of entries, and the entries' indices of the sequences, all sorted).
+    /// Returns an error if the file cannot be created or written.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let bands = self.bands.iter().map(|band| band.read()).collect::<Vec<_>>();
+        let mut seqs = bands.iter().flat_map(|band| band.values().flat_map(|set| set.iter())).map(|seq| seq.to_string()).collect::<HashSet<_>>().into_iter().collect::<Vec<_>>();
+        seqs.sort();
+        let seq_ids = seqs.iter().enumerate().map(|(id, seq)| (seq.as_str(), id)).collect::<HashMap<_, _>>();
+
+        let mut w = BufWriter::new(File::create(path)?);
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(LSH_FILE_MAGIC_V5);
+        Self::push_u64(&mut bytes, self.k);
+        Self::push_u64(&mut bytes, self.band_size);
+        Self::push_u64(&mut bytes, self.canonical as usize);
+        Self::push_u64(&mut bytes, self.index_revcomp as usize);
+        let (has_fingerprint, fingerprint) = self.source_fingerprint.map_or((false, SeqsFingerprint { count: 0_usize, hash: 0_u64 }), |fingerprint| (true, fingerprint));
+        Self::push_u64(&mut bytes, has_fingerprint as usize);
+        Self::push_u64(&mut bytes, fingerprint.count);
+        bytes.extend_from_slice(&fingerprint.hash.to_be_bytes());
+        Self::push_u64(&mut bytes, self.embeddings.len());
+        for embedding in self.embeddings.iter() {
+            Self::push_u64(&mut bytes, embedding.bits().len());
+            embedding.bits().iter().for_each(|&bits| Self::push_u64(&mut bytes, bits as usize));
+            Self::push_u64(&mut bytes, embedding.positions().len());
+            embedding.positions().iter().for_each(|&position| Self::push_u64(&mut bytes, position));
+        }
+        Self::push_u64(&mut bytes, self.permutations.len());
+        for p in self.permutations.iter() {
+            match p {
+                Permutation::Pseudo(p) => {
+                    Self::push_u64(&mut bytes, p.get_m());
+                    Self::push_u64(&mut bytes, p.get_p());
+                    Self::push_u64(&mut bytes, p.get_a());
+                    Self::push_u64(&mut bytes, p.get_b());
+                }
+                Permutation::Exact(p) => {
+                    Self::push_u64(&mut bytes, p.get_m());
+                    Self::push_u64(&mut bytes, 0_usize);
+                    Self::push_u64(&mut bytes, 0_usize);
+                    Self::push_u64(&mut bytes, 0_usize);
+                    p.table().iter().for_each(|&x| Self::push_u64(&mut bytes, x as usize));
+                }
+            }
+        }
+        Self::push_u64(&mut bytes, seqs.len());
+        for seq in seqs.iter() {
+            Self::push_u64(&mut bytes, seq.len());
+            bytes.extend_from_slice(seq.as_bytes());
+        }
+        Self::push_u64(&mut bytes, bands.len());
+        for band in bands.iter() {
+            let mut sigs = band.keys().collect::<Vec<_>>();
+            sigs.sort();
+            Self::push_u64(&mut bytes, sigs.len());
+            for sig in sigs {
+                let mut ids = band[sig].iter().map(|seq| seq_ids[seq.to_string().as_str()]).collect::<Vec<_>>();
+                ids.sort_unstable();
+                Self::push_u64(&mut bytes, sig.len());
+                bytes.extend_from_slice(sig.as_bytes());
+                Self::push_u64(&mut bytes, ids.len());
+                ids.into_iter().for_each(|id| Self::push_u64(&mut bytes, id));
+            }
+        }
+        w.write_all(&bytes)?;
+        w.flush()
+    }
+
+    /// Loads an LSH instance persisted by `save` from `path`. The permutations are restored exactly, so the signatures are the same as those of the persisted instance.
+    /// Files with the magic bytes "RQPAPLSH", "RQPAPLS2", "RQPAPLS3", or "RQPAPLS4" of older releases lack the canonical k-mers flag, the CGK embeddings, the reverse complement flag, or the source fingerprint, respectively, and are loaded with plain k-mers, MinHash bucketing, without indexing the reverse complements, or without a source fingerprint.
+    /// Returns an error if the file cannot be read, is not an LSH file, has a header without bands (0 bands or a band size of 0), is truncated, or is corrupt.
+    pub fn load(path: &str) -> Result<Self, LshLoadError> {
+        let mut bytes = Vec::new();
+        BufReader::new(File::open(path).map_err(LshLoadError::Io)?).read_to_end(&mut bytes).map_err(LshLoadError::Io)?;
+        let has_fingerprint = bytes.starts_with(LSH_FILE_MAGIC_V5);
+        let has_revcomp_flag = has_fingerprint || bytes.starts_with(LSH_FILE_MAGIC_V4);
+        let has_embeddings = has_revcomp_flag || bytes.starts_with(LSH_FILE_MAGIC_V3);
+        let has_canonical_flag = has_embeddings || bytes.starts_with(LSH_FILE_MAGIC_V2);
+        if !has_canonical_flag && !bytes.starts_with(LSH_FILE_MAGIC) {
+            return Err(LshLoadError::NotAnLshFile);
+        }
+
+        let mut pos = LSH_FILE_MAGIC.len();
+        let k = Self::read_u64(&bytes, &mut pos)?;
+        let band_size = Self::read_u64(&bytes, &mut pos)?;
+        if band_size == 0_usize {
+            return Err(LshLoadError::InvalidHeader("the band size is 0".to_string()));
+        }
+        let canonical = has_canonical_flag && Self::read_u64(&bytes, &mut pos)? != 0_usize;
+        let index_revcomp = has_revcomp_flag && Self::read_u64(&bytes, &mut pos)? != 0_usize;
+        let source_fingerprint = if has_fingerprint {
+            let known = Self::read_u64(&bytes, &mut pos)? != 0_usize;
+            let count = Self::read_u64(&bytes, &mut pos)?;
+            let mut hash = [0_u8; 8];
+            hash.copy_from_slice(Self::read_slice(&bytes, &mut pos, 8)?);
+            Some(SeqsFingerprint { count, hash: u64::from_be_bytes(hash) }).filter(|_| known)
+        }
+        else {
+            None
+        };
+        let mut embeddings = Vec::new();
+        if has_embeddings {
+            for _ in 0..Self::read_u64(&bytes, &mut pos)? {
+                let bits = (0..Self::read_u64(&bytes, &mut pos)?).map(|_| Self::read_u64(&bytes, &mut pos).map(|bits| bits as u8)).collect::<Result<Vec<_>, _>>()?;
+                let positions = (0..Self::read_u64(&bytes, &mut pos)?).map(|_| Self::read_u64(&bytes, &mut pos)).collect::<Result<Vec<_>, _>>()?;
+                if positions.iter().any(|&position| position >= bits.len()) {
+                    return Err(LshLoadError::Corrupt(format!("a sampled position of a CGK embedding exceeds its {} steps", bits.len())));
+                }
+                embeddings.push(CgkEmbedding::from_parts(bits, positions));
+            }
+        }
+        let mut permutations = Vec::new();
+        for _ in 0..Self::read_u64(&bytes, &mut pos)? {
+            let m = Self::read_u64(&bytes, &mut pos)?;
+            let p = Self::read_u64(&bytes, &mut pos)?;
+            let a = Self::read_u64(&bytes, &mut pos)?;
+            let b = Self::read_u64(&bytes, &mut pos)?;
+            if p == 0_usize {
+                let table = (0..m).map(|_| Self::read_u64(&bytes, &mut pos).map(|x| x as u32)).collect::<Result<Vec<_>, _>>()?;
+                let mut sorted = table.clone();
+                sorted.sort_unstable();
+                if sorted.iter().enumerate().any(|(i, &x)| x as usize != i) {
+                    return Err(LshLoadError::Corrupt("the table of an exact permutation is not a permutation".to_string()));
+                }
+                permutations.push(Permutation::Exact(ExactPermutation::from_table(table)));
+            }
+            else {
+                permutations.push(Permutation::Pseudo(PseudoPermutation::from_params(m, p, a, b)));
+            }
+        }
+        let mut seqs = Vec::new();
+        for _ in 0..Self::read_u64(&bytes, &mut pos)? {
+            let len = Self::read_u64(&bytes, &mut pos)?;
+            let seq = std::str::from_utf8(Self::read_slice(&bytes, &mut pos, len)?).map_err(|e| LshLoadError::Corrupt(e.to_string()))?;
+            seqs.push(Arc::new(BaseSequence::try_from_str(seq).map_err(|e| LshLoadError::Corrupt(e.to_string()))?));
+        }
+        let band_count = Self::read_u64(&bytes, &mut pos)?;
+        if band_count == 0_usize {
+            return Err(LshLoadError::InvalidHeader("the number of bands is 0".to_string()));
+        }
+        let mut bands = Vec::new();
+        for _ in 0..band_count {
+            let mut map = HashMap::new();
+            for _ in 0..Self::read_u64(&bytes, &mut pos)? {
+                let sig_len = Self::read_u64(&bytes, &mut pos)?;
+                let sig = String::from_utf8(Self::read_slice(&bytes, &mut pos, sig_len)?.to_vec()).map_err(|e| LshLoadError::Corrupt(e.to_string()))?;
+                let mut set = HashSet::new();
+                for _ in 0..Self::read_u64(&bytes, &mut pos)? {
+                    let id = Self::read_u64(&bytes, &mut pos)?;
+                    set.insert(seqs.get(id).ok_or_else(|| LshLoadError::Corrupt(format!("a bucket refers to sequence {} of {}", id, seqs.len())))?.clone());
+                }
+                map.insert(sig, set);
+            }
+            bands.push(RwLock::new(map));
+        }
+
+        Ok(LSH {
+            k,
+            band_size,
+            bands,
+            permutations,
+            canonical,
+            index_revcomp,
+            embeddings,
+            source_fingerprint,
+            min_hash_cache: None
+        })
+    }
+
+    #[inline]
+    fn push_u64(bytes: &mut Vec<u8>, value: usize) {
+        bytes.extend_from_slice(&(value as u64).to_be_bytes());
+    }
+
+    #[inline]
+    fn read_u64(bytes: &[u8], pos: &mut usize) -> Result<usize, LshLoadError> {
+        let mut buffer = [0_u8; 8];
+        buffer.copy_from_slice(Self::read_slice(bytes, pos, 8)?);
+        Ok(u64::from_be_bytes(buffer) as usize)
+    }
+
+    #[inline]
+    fn read_slice<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], LshLoadError> {
+        if len > bytes.len() - *pos {
+            return Err(LshLoadError::Truncated);
+        }
+        *pos += len;
+        Ok(&bytes[*pos - len..*pos])
+    }
 
     #[inline]
     pub fn k(&self) -> usize {
         self.k
     }
 
+    /// Returns the number of hash functions.
+    #[inline]
+    pub fn r(&self) -> usize {
+        self.band_size * self.bands.len()
+    }
+
+    /// Returns the bucketing of this instance, i.e., `LSH_BUCKETING_MINHASH` or `LSH_BUCKETING_CGK`.
+    #[inline]
+    pub fn bucketing(&self) -> usize {
+        if self.embeddings.is_empty() { LSH_BUCKETING_MINHASH } else { LSH_BUCKETING_CGK }
+    }
+
+    /// Returns the length of the sequences the CGK embeddings were created for, or 0 for MinHash bucketing.
+    #[inline]
+    pub fn cgk_len(&self) -> usize {
+        self.embeddings.first().map_or(0_usize, |embedding| embedding.len())
+    }
+
+    /// Returns true if the sequences are also inserted into the buckets of their reverse complements (see `with_revcomp_indexing`).
+    #[inline]
+    pub fn indexes_revcomp(&self) -> bool {
+        self.index_revcomp
+    }
+
+    /// Returns the kind of the permutations, i.e., `PERMUTATION_PSEUDO` or `PERMUTATION_EXACT`.
+    #[inline]
+    pub fn permutation_kind(&self) -> usize {
+        self.permutations.first().map_or(PERMUTATION_PSEUDO, |p| p.kind())
+    }
+
+    /// Returns the fingerprint of the sequences this instance was built from, or None if it is unknown, e.g., for an instance loaded from a file of an older release (see `with_source_fingerprint`).
+    #[inline]
+    pub fn source_fingerprint(&self) -> Option<SeqsFingerprint> {
+        self.source_fingerprint
+    }
+
+    /// Returns true if the min-hashes are computed from the canonical k-mers.
+    #[inline]
+    pub fn is_canonical(&self) -> bool {
+        self.canonical
+    }
+
+    /// Returns the number of bands.
+    #[inline]
+    pub fn b(&self) -> usize {
+        self.bands.len()
+    }
+
     #[inline]
     pub fn band_size(&self) -> usize {
         self.band_size
     }
-}
\ No newline at end of file
+}
+
+/// The token of a single insertion into an LSH instance (see `LSH::insert`), which allows `LSH::rollback` to undo exactly this insertion without recomputing the signatures of the sequence.
+/// A token belongs to the LSH instance that returned it and must not be rolled back on another instance.
+#[derive(Clone, Debug)]
+pub struct LshInsertion {
+    seq: Arc<BaseSequence>,    // the inserted sequence
+    sigs: Vec<String>,         // the signature of the sequence in each band (followed by those of its reverse complement if they are indexed)
+    inserted_bands: Vec<bool>, // whether the sequence was added to the bucket of each signature, i.e., was not contained in it before
+    cached: bool               // whether the min-hashes of the sequence were added to the min-hash cache by this insertion
+}
+
+impl LshInsertion {
+    /// Returns the inserted sequence.
+    pub fn seq(&self) -> &Arc<BaseSequence> {
+        &self.seq
+    }
+
+    /// Returns true if the sequence was added to at least one bucket, i.e., false if it was already contained in the LSH.
+    pub fn is_new(&self) -> bool {
+        self.inserted_bands.iter().any(|&inserted| inserted)
+    }
+}
+
+/// The statistics of the buckets of an LSH instance (see `LSH::stats`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LshStats {
+    pub sequences: usize,     // the number of distinct sequences stored
+    pub bands: Vec<BandStats> // the statistics of each band
+}
+
+/// The statistics of the buckets of a single band of an LSH instance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BandStats {
+    pub buckets: usize,        // the number of buckets
+    pub max_bucket_size: usize, // the number of sequences in the largest bucket
+    pub mean_bucket_size: f64  // the mean number of sequences per bucket
+}
+
+impl fmt::Display for LshStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "sequences = {}, bands = {}", self.sequences, self.bands.len())?;
+        write!(f, "{:>6} {:>10} {:>10} {:>10}", "band", "buckets", "max size", "mean size")?;
+        for (band, stats) in self.bands.iter().enumerate() {
+            write!(f, "\n{:>6} {:>10} {:>10} {:>10.3}", band, stats.buckets, stats.max_bucket_size, stats.mean_bucket_size)?;
+        }
+        Ok(())
+    }
+}
+
+/// The fingerprint of the sequences an LSH instance was built from, i.e., their number and a hash of their lengths and bases in the given order.
+/// It is persisted with the instance (see `LSH::with_source_fingerprint`) and tells whether a loaded instance was built from other sequences, e.g., from an edited file of probes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SeqsFingerprint {
+    count: usize,
+    hash: u64
+}
+
+impl SeqsFingerprint {
+    /// Computes the fingerprint of `seqs`. The hash is the 64-bit FNV-1a hash of each sequence's length (u64 big endian) followed by its bases, which, unlike `DefaultHasher`, is the same in every release.
+    pub fn of(seqs: &[Arc<BaseSequence>]) -> Self {
+        let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+        let mut feed = |byte: u8| hash = (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3_u64);
+        for seq in seqs.iter() {
+            (seq.len() as u64).to_be_bytes().iter().for_each(|&byte| feed(byte));
+            seq.as_slice().iter().for_each(|&base| feed(base as u8));
+        }
+        Self { count: seqs.len(), hash }
+    }
+
+    /// Returns the number of sequences.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the hash of the sequences.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// The error returned when a persisted LSH instance cannot be loaded (see `LSH::load`).
+#[derive(Debug)]
+pub enum LshLoadError {
+    /// The file cannot be opened or read.
+    Io(std::io::Error),
+    /// The file does not start with the magic bytes of a persisted LSH instance.
+    NotAnLshFile,
+    /// The file describes an LSH instance without bands, i.e., with 0 bands or a band size of 0.
+    InvalidHeader(String),
+    /// The file ends within the persisted LSH instance.
+    Truncated,
+    /// The file contains a value that no persisted LSH instance has.
+    Corrupt(String)
+}
+
+impl fmt::Display for LshLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LshLoadError::Io(e) => write!(f, "{}", e),
+            LshLoadError::NotAnLshFile => write!(f, "not an LSH file"),
+            LshLoadError::InvalidHeader(reason) => write!(f, "LSH file has an invalid header: {}", reason),
+            LshLoadError::Truncated => write!(f, "LSH file is truncated"),
+            LshLoadError::Corrupt(reason) => write!(f, "LSH file is corrupt: {}", reason)
+        }
+    }
+}
+
+impl std::error::Error for LshLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LshLoadError::Io(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+/// Converts the LSH bucketing's string into a number, i.e., (0="minhash", 1="cgk").
+#[inline(always)]
+pub fn extract_lsh_bucketing(arg: &str) -> usize {
+    if arg.eq_ignore_ascii_case("minhash") {
+        LSH_BUCKETING_MINHASH
+    }
+    else if arg.eq_ignore_ascii_case("cgk") {
+        LSH_BUCKETING_CGK
+    }
+    else {
+        panic!("cannot determine LSH bucketing: {}", arg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SEED: u64 = 7_u64;
+
+    fn seq(s: &str) -> Arc<BaseSequence> {
+        Arc::new(BaseSequence::try_from_str(s).unwrap())
+    }
+
+    #[test]
+    fn seeded_instances_produce_identical_signatures() {
+        let s = seq("ACGTTGCAACGGTTCCAAGGTTAACCGGTTACGATCGA");
+        let lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED));
+        assert_eq!(lsh.signatures(&s), LSH::new(5_usize, 20_usize, 4_usize, Some(SEED)).signatures(&s));
+        assert_eq!(lsh.min_hashes(&s), LSH::new(5_usize, 20_usize, 4_usize, Some(SEED)).min_hashes(&s));
+        assert_ne!(lsh.min_hashes(&s), LSH::new(5_usize, 20_usize, 4_usize, Some(SEED + 1)).min_hashes(&s));
+    }
+
+    #[test]
+    fn estimated_jaccard_approximates_the_jaccard_similarity() {
+        let lsh = LSH::new(4_usize, 400_usize, 4_usize, Some(SEED));
+        let a = seq("ACGTTGCAACGGTTCCAAGGTTAACCGGTTACGATCGA");
+        let b = seq("ACGTTGCAACGGTTCCAAGGTTAACCGGTTAGCTAGCTAGCA");
+        assert_eq!(lsh.estimated_jaccard(&a, &a), 1_f64);
+        assert!(lsh.estimated_jaccard(&seq("AAAAAAAAAA"), &seq("CCCCCCCCCC")) < 0.05_f64); // no shared k-mer, but the pseudo permutations may collide
+        let jaccard = 1_f64 - a.jaccard_distance_arc(&b, 4_usize);
+        assert!((lsh.estimated_jaccard(&a, &b) - jaccard).abs() < 0.1_f64, "estimated {} for {}", lsh.estimated_jaccard(&a, &b), jaccard);
+        assert_eq!(lsh.estimated_jaccard_to(&lsh.min_hashes(&a), &b), lsh.estimated_jaccard(&a, &b));
+    }
+
+    #[test]
+    fn estimated_jaccard_of_sequences_shorter_than_k_is_0() {
+        let lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED));
+        let (short, other_short, long) = (seq("ACGT"), seq("TTGA"), seq("ACGTTGCAACGG"));
+        assert_eq!(lsh.min_hashes(&short), lsh.min_hashes(&other_short));
+        assert_eq!(lsh.estimated_jaccard(&short, &other_short), 0_f64);
+        assert_eq!(lsh.estimated_jaccard(&short, &short), 0_f64);
+        assert_eq!(lsh.estimated_jaccard(&short, &long), 0_f64);
+        assert_eq!(lsh.estimated_jaccard(&long, &short), 0_f64);
+    }
+
+    #[test]
+    fn min_hash_cache_holds_the_inserted_sequences() {
+        let mut lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED)).with_min_hash_cache(true);
+        let uncached = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED));
+        let (a, b, c) = (seq("ACGTTGCAACGGTTCCAAGGTTAACCGG"), seq("ACGTTGCAACGGTTCCAAGGTTTTTTTT"), seq("GGGCCCATATATCGCGTTAAGG"));
+        let cached = |lsh: &LSH, s: &Arc<BaseSequence>| lsh.min_hash_cache.as_ref().unwrap().read().get(s).cloned();
+
+        lsh.insert(&a);
+        assert_eq!(cached(&lsh, &a), Some(lsh.min_hashes(&a)));
+        assert_eq!(cached(&lsh, &b), None);
+        // a query caches the min-hashes of the queried sequence, and the cached estimate equals the uncached one
+        assert_eq!(lsh.estimated_jaccard(&a, &b), uncached.estimated_jaccard(&a, &b));
+        assert_eq!(cached(&lsh, &b), Some(lsh.min_hashes(&b)));
+        assert_eq!(lsh.estimated_jaccard(&a, &b), uncached.estimated_jaccard(&a, &b));
+
+        let insertion = lsh.insert(&c);
+        assert!(cached(&lsh, &c).is_some());
+        lsh.rollback(insertion);
+        assert_eq!(cached(&lsh, &c), None);
+        lsh.remove(&a);
+        assert_eq!(cached(&lsh, &a), None);
+        lsh.clear();
+        assert_eq!(cached(&lsh, &b), None);
+        assert!(lsh.with_min_hash_cache(false).min_hash_cache.is_none());
+    }
+
+    #[test]
+    fn supports_k_up_to_max_k() {
+        let k_mer = vec![Base::T; MAX_K];
+        assert_eq!(LSH::initial_row_id(&k_mer), 4_usize.pow(MAX_K as u32) - 1_usize);
+        let lsh = LSH::new(MAX_K, 20_usize, 4_usize, Some(SEED));
+        let a = seq("ACGTTGCAACGGTTCCAAGGTTAACCGGTTA");
+        let b = seq("ACGTTGCAACGGTTCCAAGGTTAACCGGTTC"); // differs only in the last base
+        assert_ne!(lsh.min_hashes(&a), lsh.min_hashes(&b));
+    }
+
+    #[test]
+    #[should_panic(expected = "up to k = 31")]
+    fn rejects_k_beyond_max_k() {
+        LSH::new(MAX_K + 1_usize, 20_usize, 4_usize, Some(SEED));
+    }
+
+    /// Returns a path in the temporary directory that is unique to this process and `name`.
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("rqpap_lsh_{}_{}", std::process::id(), name)).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn save_load_round_trip_keeps_the_signatures() {
+        let seqs = ["ACGTTGCAACGGTTCCAAGGTTAACCGGTTACGATCGA", "TTGACCAGTAGGCATGACCATGGTACGTAGCTAGCTAA", "GGGCCCATATATCGCGTTAAGGCCTTAACCGGTTAACG"].iter().map(|s| seq(s)).collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let instances = vec![
+            ("pseudo", LSH::new(5_usize, 20_usize, 4_usize, Some(SEED))),
+            ("exact", LSH::new_with_permutation_kind(4_usize, 12_usize, 3_usize, PERMUTATION_EXACT, &mut rng).with_canonical_k_mers(true)),
+            ("cgk", LSH::new_cgk_with_rng(40_usize, 12_usize, 3_usize, &mut rng))
+        ];
+        for (name, lsh) in instances {
+            seqs.iter().for_each(|s| { lsh.insert(s); });
+            let path = temp_path(name);
+            lsh.save(&path).unwrap();
+            let loaded = LSH::load(&path);
+            std::fs::remove_file(&path).unwrap();
+            let loaded = loaded.unwrap_or_else(|e| panic!("cannot load the {} LSH: {}", name, e));
+            assert_eq!((loaded.k(), loaded.r(), loaded.b(), loaded.bucketing(), loaded.is_canonical()), (lsh.k(), lsh.r(), lsh.b(), lsh.bucketing(), lsh.is_canonical()));
+            assert_eq!(loaded.len(), lsh.len());
+            for s in seqs.iter() {
+                assert_eq!(loaded.signatures(s), lsh.signatures(s), "{}", name);
+                assert_eq!(loaded.similar_seqs(s), lsh.similar_seqs(s), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn load_rejects_invalid_files() {
+        let lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED));
+        lsh.insert(&seq("ACGTTGCAACGGTTCCAAGGTTAACCGGTTACGATCGA"));
+        let path = temp_path("invalid");
+        lsh.save(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(LSH::load(&path), Err(LshLoadError::Truncated)));
+        std::fs::write(&path, b"not an LSH file").unwrap();
+        assert!(matches!(LSH::load(&path), Err(LshLoadError::NotAnLshFile)));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(LSH::load(&path), Err(LshLoadError::Io(_))));
+    }
+
+    #[test]
+    fn remove_erases_the_sequence_and_its_empty_buckets() {
+        for &revcomp in [false, true].iter() {
+            let mut lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED)).with_revcomp_indexing(revcomp);
+            let (a, b) = (seq("ACGTTGCAACGGTTCCAAGGTTAACCGG"), seq("GGGCCCATATATCGCGTTAAGGCATG"));
+            lsh.insert(&a);
+            lsh.insert(&b);
+            assert_eq!(lsh.len(), 2_usize);
+
+            assert!(lsh.remove(&a));
+            assert!(!lsh.remove(&a));
+            assert_eq!(lsh.iter().collect::<Vec<_>>(), vec![b.clone()]);
+            assert!(!lsh.similar_seqs(&a).contains(&a));
+            assert!(lsh.similar_seqs(&b).contains(&b));
+
+            assert!(lsh.remove(&b));
+            assert!(lsh.is_empty());
+            assert!(lsh.stats().bands.iter().all(|band| band.buckets == 0_usize));
+        }
+    }
+
+    #[test]
+    fn rollback_keeps_sequences_inserted_before() {
+        let mut lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED));
+        let a = seq("ACGTTGCAACGGTTCCAAGGTTAACCGG");
+        lsh.insert(&a);
+        let insertion = lsh.insert(&a);
+        assert!(!lsh.rollback(insertion));
+        assert!(lsh.similar_seqs(&a).contains(&a));
+        lsh.remove(&a);
+        let insertion = lsh.insert(&a);
+        assert!(lsh.rollback(insertion));
+        assert!(lsh.is_empty());
+    }
+
+    #[test]
+    fn exact_permutations_estimate_identical_sequences_as_identical() {
+        let lsh = LSH::new_with_permutation_kind(5_usize, 20_usize, 4_usize, PERMUTATION_EXACT, &mut StdRng::seed_from_u64(SEED));
+        assert_eq!(lsh.permutation_kind(), PERMUTATION_EXACT);
+        let (a, b) = (seq("ACGTTGCAACGGTTCCAAGGTTAACCGG"), seq("GGGCCCATATATCGCGTTAAGGCATG"));
+        lsh.insert(&a);
+        assert!(lsh.similar_seqs(&a).contains(&a));
+        assert_eq!(lsh.estimated_jaccard(&a, &a), 1_f64);
+        assert!(lsh.estimated_jaccard(&a, &b) < 0.5_f64);
+    }
+
+    #[test]
+    fn sequences_shorter_than_k_are_stored_without_panicking() {
+        let lsh = LSH::new(8_usize, 20_usize, 4_usize, Some(SEED));
+        let (short, other_short) = (seq("ACGTAC"), seq("TTGCA"));
+        assert_eq!(lsh.min_hashes(&short), vec![NO_K_MERS_MIN_HASH; 20_usize]);
+        lsh.insert(&short);
+        lsh.insert(&seq("ACGTTGCAACGGTTCCAAGG"));
+        assert_eq!(lsh.len(), 2_usize);
+        // sequences without k-mers share their signatures, i.e., they are candidates of each other
+        assert!(lsh.similar_seqs(&other_short).contains(&short));
+        assert!(!lsh.similar_seqs(&seq("ACGTTGCAACGGTTCCAAGG")).contains(&short));
+    }
+
+    #[test]
+    fn min_hashes_match_the_min_hashes_of_every_k_mer() {
+        let lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED));
+        let s = seq("ACGTTGCAACGGTTCCAAGGTTAACCGGAAAAACCCCC");
+        let naive = lsh.permutations.iter()
+            .map(|p| s.k_mers(5_usize).into_iter().map(|k_mer| p.apply(LSH::initial_row_id(k_mer))).min().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lsh.min_hashes(&s), naive);
+    }
+
+    #[test]
+    fn rolled_row_ids_match_the_row_ids_of_every_k_mer() {
+        let s = seq("ACGTTGCAACGGTTCCAAGGTTAACCGGAAAAACCCCCGTA");
+        for &k in [1_usize, 2, 5, 13, MAX_K].iter() {
+            let k_mers = s.k_mers(k);
+            let expected = k_mers.iter().map(|k_mer| LSH::initial_row_id(k_mer)).collect::<Vec<_>>();
+            assert_eq!(LSH::row_ids(s.as_slice(), k, false), expected, "k = {}", k);
+            let expected_canonical = k_mers.iter().map(|&k_mer| {
+                let rc = k_mer.iter().rev().map(|base| base.complement()).collect::<Vec<_>>();
+                LSH::initial_row_id(if rc.as_slice() < k_mer { rc.as_slice() } else { k_mer })
+            }).collect::<Vec<_>>();
+            assert_eq!(LSH::row_ids(s.as_slice(), k, true), expected_canonical, "k = {}", k);
+        }
+        let t_mer = vec![Base::T; MAX_K + 1_usize];
+        assert_eq!(LSH::row_ids(&t_mer, MAX_K, false), vec![4_usize.pow(MAX_K as u32) - 1_usize; 2_usize]);
+    }
+
+    #[test]
+    fn load_rejects_headers_without_bands() {
+        let lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED));
+        lsh.insert(&seq("ACGTTGCAACGGTTCCAAGGTTAACCGGTTACGATCGA"));
+        let path = temp_path("no_bands");
+        lsh.save(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let band_size_pos = LSH_FILE_MAGIC_V5.len() + 8_usize;
+
+        let mut zero_band_size = bytes.clone();
+        zero_band_size[band_size_pos..band_size_pos + 8_usize].copy_from_slice(&0_u64.to_be_bytes());
+        std::fs::write(&path, &zero_band_size).unwrap();
+        assert!(matches!(LSH::load(&path), Err(LshLoadError::InvalidHeader(_))));
+
+        let empty = LSH { bands: Vec::new(), ..LSH::new(5_usize, 20_usize, 4_usize, Some(SEED)) };
+        empty.save(&path).unwrap();
+        let loaded = LSH::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(LshLoadError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn save_reports_unwritable_paths() {
+        let lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED));
+        let dir = temp_path("unwritable_dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let result = lsh.save(&dir);
+        std::fs::remove_dir(&dir).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn source_fingerprint_is_persisted_and_tells_edited_sequences_apart() {
+        let seqs = ["ACGTTGCAACGGTTCCAAGGTTAACCGGTTACGATCGA", "TTGACCAGTAGGCATGACCATGGTACGTAGCTAGCTAA"].iter().map(|s| seq(s)).collect::<Vec<_>>();
+        let fingerprint = SeqsFingerprint::of(&seqs);
+        assert_eq!(fingerprint.count(), 2_usize);
+        assert_eq!(fingerprint, SeqsFingerprint::of(&seqs.clone()));
+        assert_ne!(fingerprint, SeqsFingerprint::of(&[seqs[0].clone(), seq("TTGACCAGTAGGCATGACCATGGTACGTAGCTAGCTAT")]));
+        assert_ne!(fingerprint, SeqsFingerprint::of(&[seqs[1].clone(), seqs[0].clone()]));
+        assert_ne!(fingerprint, SeqsFingerprint::of(&seqs[..1]));
+
+        let lsh = LSH::new(5_usize, 20_usize, 4_usize, Some(SEED)).with_source_fingerprint(Some(fingerprint));
+        seqs.iter().for_each(|s| { lsh.insert(s); });
+        let path = temp_path("fingerprint");
+        lsh.save(&path).unwrap();
+        assert_eq!(LSH::load(&path).unwrap().source_fingerprint(), Some(fingerprint));
+
+        // a file of the previous release has no fingerprint, i.e., it lacks the 3 values after the reverse complement flag
+        let bytes = std::fs::read(&path).unwrap();
+        let fingerprint_pos = LSH_FILE_MAGIC_V5.len() + 4_usize * 8_usize;
+        let v4 = [LSH_FILE_MAGIC_V4.as_ref(), &bytes[LSH_FILE_MAGIC_V5.len()..fingerprint_pos], &bytes[fingerprint_pos + 3_usize * 8_usize..]].concat();
+        std::fs::write(&path, &v4).unwrap();
+        let loaded = LSH::load(&path);
+        LSH::new(5_usize, 20_usize, 4_usize, Some(SEED)).save(&path).unwrap();
+        let unknown = LSH::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.source_fingerprint(), None);
+        assert_eq!(loaded.similar_seqs(&seqs[0]), lsh.similar_seqs(&seqs[0]));
+        assert_eq!(unknown.unwrap().source_fingerprint(), None);
+    }
+}
@@ -1,31 +1,22 @@
 use std::{env, fs};
 use std::time::{SystemTime, Duration};
 use std::sync::Arc;
-use crate::lsh::LSH;
-use crate::raptor::RaptorQ;
-use crate::safe_cell::SafeCell;
+use std::sync::atomic::Ordering;
+use rqpap::lsh::{LSH, SeqsFingerprint};
+use rqpap::raptor::{RaptorQ, BaseMapping};
 use std::fs::{OpenOptions, File, read};
 use std::io::{BufReader, Read, BufRead, Write, stdout, stdin};
-use crate::base_sequence::BaseSequence;
-use crate::dg_client::DGClient;
-use rayon::ThreadPool;
-use crossbeam_channel::{Sender, Receiver, bounded};
+use rqpap::base_sequence::{Base, BaseSequence};
+use rqpap::dg_client::{DGClient, DGClientConfig, DGTimeouts};
+use rqpap::dna_rules::{BaseHpRules, DgRules, EntropyRules, GcHpRules, GcWindowRules, HairpinRules, MotifFilter, TmRules};
+use rqpap::similarity::{SIMILARITY_BACKEND_EXACT, SIMILARITY_BACKEND_APPROX};
+use rqpap::encoder::{Encoder, EncodedStrand, LineErrorKind, MinDistSchedule, RejectReason, Rejections};
+use rqpap::{arg_parser, dg_client, dna_rules, encoder, lsh, pseudo_permutation, raptor, similarity};
 use std::ops::{Deref, Add};
 use std::path::Path;
-use parking_lot::RwLockReadGuard;
-use std::collections::HashSet;
 use std::rc::Rc;
-use parking_lot::RwLock;
-mod lsh;
-mod pseudo_permutation;
-mod safe_cell;
-mod arg_parser;
-mod base_sequence;
-mod dna_rules;
-mod raptor;
-mod dg_client;
-
-static DISTANCE_CHECK_POOLING_TRIGGER: usize  = 2000_usize;          // the number of distance checks before parallelizing the computations (should be manually adjusted for the target machine)
+use std::collections::{HashSet, BTreeMap};
+
 static DEFAULT_CSV_DELIMITER: &str            = ",";                 // csv delimiter
 static DEFAULT_CSV_NEW_LINE: &str             = "\n";                // csv new line
 
@@ -33,58 +24,203 @@ static ENCODING_MODE_LSH: usize               = 0_usize;             // encoding
 static ENCODING_MODE_MIXED: usize             = 1_usize;             // encoding mode "MIXED" is represented as 1
 static ENCODING_MODE_NAIVE: usize             = 2_usize;             // encoding mode "NAIVE" is represented as 2
 
-static INITIAL_PACKETS_PER_BLOCK: usize       = 5_usize;             // default starting number of packets that are generated by RQ
-static MAX_ENCODE_LOOPS: usize                = 200_usize;           // number of loops in RQ attempting to find packets that fulfill the given constraints
-
-
-static DEFAULT_MAX_HP_LEN: usize              = 5_usize;             // default maximum homopolymer length
-static DEFAULT_OVERHEAD: usize                = 0_usize;             // default RQ overhead
-static DEFAULT_SECONDARY_STRUCT_TEMP: f32     = 25_f32;              // default temperature for the dg energy
-static DEFAULT_MAX_DG_ERROR: f32              = 0.5_f32;             // default maximum error calculated from the dg energy
+static REPORT_FORMAT_CSV: usize               = 0_usize;             // report format "CSV" is represented as 0
+static REPORT_FORMAT_JSON: usize              = 1_usize;             // report format "JSON" is represented as 1
+static REPORT_FORMAT_BOTH: usize              = 2_usize;             // report format "BOTH" (CSV and JSON) is represented as 2
+
+static OUTPUT_FORMAT_FASTA: usize             = 0_usize;             // output format "FASTA" is represented as 0
+static OUTPUT_FORMAT_BINARY: usize            = 1_usize;             // output format "BINARY" (length-prefixed and 2-bit packed) is represented as 1
+
+
+static DEFAULT_MAX_HP_LEN: usize              = encoder::DEFAULT_MAX_HP_LEN; // default maximum homopolymer length
+static DEFAULT_MAX_HP_PER_BASE: usize         = 0_usize;             // default maximum homopolymer length of a single base (0 = max_hp_len)
+static DEFAULT_MIN_GC: f64                    = dna_rules::MIN_GC_CONTENT; // default minimum GC content
+static DEFAULT_MAX_GC: f64                    = dna_rules::MAX_GC_CONTENT; // default maximum GC content
+static DEFAULT_GC_WINDOW: usize               = encoder::DEFAULT_GC_WINDOW; // default window size for the local GC content (0 = disabled)
+static DEFAULT_MIN_WINDOW_GC: f64             = encoder::DEFAULT_MIN_WINDOW_GC; // default minimum GC content of every window
+static DEFAULT_MAX_WINDOW_GC: f64             = encoder::DEFAULT_MAX_WINDOW_GC; // default maximum GC content of every window
+static DEFAULT_FORBIDDEN_MOTIFS: &str         = "";                  // default forbidden motifs separated by ";" (empty = none)
+static DEFAULT_MIN_TM: f64                    = encoder::DEFAULT_MIN_TM; // default minimum melting temperature in °C
+static DEFAULT_MAX_TM: f64                    = encoder::DEFAULT_MAX_TM; // default maximum melting temperature in °C
+static DEFAULT_MIN_ENTROPY: f64               = encoder::DEFAULT_MIN_ENTROPY; // default minimum dinucleotide entropy in bits (0 = disabled)
+static DEFAULT_MAX_HAIRPIN_STEM: usize        = encoder::DEFAULT_MAX_HAIRPIN_STEM; // default maximum length of a local hairpin stem (0 = disabled)
+static DEFAULT_HAIRPIN_MIN_LOOP: usize        = encoder::DEFAULT_HAIRPIN_MIN_LOOP; // default minimum loop length of a local hairpin
+static DEFAULT_OVERHEAD: usize                = encoder::DEFAULT_OVERHEAD; // default RQ overhead
+static DEFAULT_RQ_SYMBOL_SIZE: usize          = raptor::DEFAULT_SYMBOL_SIZE; // default RQ symbol size in bytes
+static DEFAULT_RQ_SOURCE_BLOCKS: usize        = raptor::DEFAULT_SOURCE_BLOCKS; // default number of RQ source blocks
+static DEFAULT_RQ_SUB_BLOCKS: usize           = raptor::DEFAULT_SUB_BLOCKS; // default number of RQ sub-blocks
+static DEFAULT_RQ_ALIGNMENT: usize            = raptor::DEFAULT_ALIGNMENT; // default RQ symbol alignment in bytes
+static DEFAULT_SECONDARY_STRUCT_TEMP: f32     = encoder::DEFAULT_SECONDARY_STRUCT_TEMP; // default temperature for the dg energy
+static DEFAULT_MAX_DG_ERROR: f32              = dna_rules::MAX_DG_ERROR; // default maximum error calculated from the dg energy
+static DEFAULT_DG_ERROR_OFFSET: f32           = dna_rules::DG_ERROR_OFFSET; // default offset of the dg energy in the sigmoid of the dg error
+static DEFAULT_DG_ERROR_STEEPNESS: f32        = dna_rules::DG_ERROR_STEEPNESS; // default steepness of the sigmoid of the dg error
+static DEFAULT_DG_HOST: &str                  = "127.0.0.1";         // default hostname or IP address of the dg server
 static DEFAULT_DG_START_PORT: u16             = 6000_u16;            // default starting port for the dg server
+static DEFAULT_DG_CONNECT_TIMEOUT_MS: u64     = dg_client::DEFAULT_CONNECT_TIMEOUT_MS; // default timeout in milliseconds for connecting to the dg server
+static DEFAULT_DG_READ_TIMEOUT_MS: u64        = dg_client::DEFAULT_READ_TIMEOUT_MS; // default timeout in milliseconds for receiving a dg energy (0 = no timeout)
+static DEFAULT_DG_MAX_RETRIES: usize         = dg_client::DEFAULT_MAX_RETRIES; // default number of reconnect attempts after the connection to the dg server failed
+static DEFAULT_DG_CHANNELS: usize             = 0_usize;             // default number of dg server ports to connect to, starting at the starting port (0 = threads)
+static DEFAULT_DG_MAX_INFLIGHT: usize         = dg_client::DEFAULT_MAX_INFLIGHT; // default maximum number of concurrent dg queries (0 = limited by the number of dg channels only)
+static DEFAULT_DG_MIN_LEN: usize              = encoder::DEFAULT_DG_MIN_LEN; // default minimum length of a strand to query its dg energy (shorter strands pass without a query)
+static DEFAULT_DG_STATS: bool                 = true;                // default value for whether or not to print the distribution of the dg energies of the Info-DNAs at the end
+static DEFAULT_STRAND_STATS: bool             = true;                // default value for whether or not to print the distributions of the GC content, the length, and the longest homopolymer of the Info-DNAs at the end
+static DEFAULT_RESUME: bool                   = false;               // default value for whether or not to resume an interrupted run from the existing Info-DNA file
+static DEFAULT_CHECKPOINT_EVERY: usize        = 0_usize;             // default number of encoded lines after which they are appended to the checkpoint (0 = no checkpoints)
+static CHECKPOINT_EXTENSION: &str             = ".checkpoint";       // the extension appended to info_dna_path to obtain the path of the checkpoint
+static TEMP_EXTENSION: &str                   = ".tmp";              // the extension of the temporary file that is renamed to the checkpoint once it is rewritten completely
+static DEFAULT_QUIET: bool                    = false;               // default value for whether or not to suppress the progress line while encoding
+static DEFAULT_DRY_RUN: bool                  = false;               // default value for whether or not to only estimate the Info-DNAs without encoding
+static DEFAULT_PER_LINE_TIMEOUT_MS: u64       = encoder::DEFAULT_PER_LINE_TIMEOUT_MS; // default time limit in ms for encoding a single line (0 = no limit)
+static EXIT_CODE_INTERRUPTED: i32             = 130_i32;             // exit code of a run that was interrupted by Ctrl-C (128 + SIGINT)
+static EXIT_CODE_INVALID_ARGUMENT: i32        = 2_i32;               // exit code of a run that was started with an argument that cannot be parsed
+static DISTRIBUTION_HISTOGRAM_BINS: usize     = 10_usize;            // number of bins of the histograms printed at the end
+static GC_HISTOGRAM_BIN_WIDTH: f64            = 0.02_f64;            // width of the bins of the GC content histogram printed at the end
+static HISTOGRAM_BIN_EPSILON: f64             = 1e-9_f64;            // tolerance (in bins) that keeps a value on a bin boundary from falling into the previous bin due to rounding
 static DEFAULT_USE_DG: bool                   = true;                // default value for whether or not to check a sequence's error with the dg server
 static DEFAULT_READ_AS_LINES: bool            = true;                // default value for reading a csv file in lines-mode
 static DEFAULT_APPROVE: bool                  = true;                // default value for whether to check the given parameters before running or not
+static DEFAULT_STRICT_ARGS: bool              = true;                // default value for whether or not unknown arguments abort the program (otherwise, they are ignored with a warning)
 static DEFAULT_APPEND_TO_REPORT: bool         = true;                // default value that determines if we append the results to an existing file or create a new one
 static DEFAULT_REPORT: bool                   = true;                // default value to turn on/off results reporting to a csv file
 static DEFAULT_REPORT_PATH: &str              = "RQPAP_report.csv";  // default csv file's path with results
+static DEFAULT_REPORT_JSON_PATH: &str         = "RQPAP_report.jsonl"; // default newline-delimited json file's path with results
+static DEFAULT_REPORT_FORMAT_STR: &str        = "csv";               // default report format
 static DEFAULT_ENCODING_MODE_STR: &str        = "lsh";               // default encoding mode
+static DEFAULT_SIMILARITY_BACKEND_STR: &str   = "auto";              // default similarity backend ("auto" derives the backends from the encoding mode)
+static DEFAULT_DISTANCE_METRIC_STR: &str      = "jaccard";           // default distance metric
+static DEFAULT_BASE_MAPPING_STR: &str         = "ACGT";              // default DNA bases of the 2-bit values 00, 01, 10, and 11
+static DEFAULT_ROTATE_MAPPING: bool           = false;               // default value for whether or not the 2-bit values are rotated by the position of the base before mapping
 static DEFAULT_PROBES_PATH: &str              = "probes.fa";         // default fasta file of probes that will be used
+static PROBES_PATH_SEPARATOR: char            = ',';                 // the separator of several fasta files (or patterns) of probes in probes_path
 static DEFAULT_LINES_PATH: &str               = "lines.txt";         // default file's path of data objects
+static LINES_PATH_STDIN: &str                 = "-";                 // the value of lines_path that reads the data objects from stdin
 static DEFAULT_INFO_DNA_PATH: &str            = "info-dna.fa";       // default fasta file for Info-DNA sequences
+static DEFAULT_OUTPUT_FORMAT_STR: &str        = "fasta";             // default format of the Info-DNA file
+static DEFAULT_PREFIX_ADAPTER: &str           = "";                  // default constant DNA sequence written before every Info-DNA (empty = none)
+static DEFAULT_SUFFIX_ADAPTER: &str           = "";                  // default constant DNA sequence written after every Info-DNA (empty = none)
+static DEFAULT_PROBES_LSH_CACHE: &str         = "";                  // default file's path of the cached LSH instance of the probes (empty = no caching)
+static DEFAULT_MAX_IUPAC_EXPANSIONS: usize    = 0_usize;             // default maximum number of concrete sequences a degenerate probe may expand to (0 = IUPAC codes are not allowed)
 
-static DEFAULT_LSH_K_PROBES: usize            = 4_usize;             // default k for the probes' LSH instance
-static DEFAULT_LSH_R_PROBES: usize            = 200_usize;           // default r for the probes' LSH instance
-static DEFAULT_LSH_B_PROBES: usize            = 20_usize;            // default b for the probes' LSH instance
+static DEFAULT_LSH_K_PROBES: usize            = encoder::DEFAULT_LSH_K_PROBES; // default k for the probes' LSH instance
+static DEFAULT_LSH_R_PROBES: usize            = encoder::DEFAULT_LSH_R_PROBES; // default r for the probes' LSH instance
+static DEFAULT_LSH_B_PROBES: usize            = encoder::DEFAULT_LSH_B_PROBES; // default b for the probes' LSH instance
 
-static DEFAULT_LSH_K_SEQS: usize              = 5_usize;             // default k for the Info-DNAs' LSH instance
-static DEFAULT_LSH_R_SEQS: usize              = 200_usize;           // default r for the Info-DNAs' LSH instance
-static DEFAULT_LSH_B_SEQS: usize              = 20_usize;            // default b for the Info-DNAs' LSH instance
+static DEFAULT_LSH_K_SEQS: usize              = encoder::DEFAULT_LSH_K_SEQS; // default k for the Info-DNAs' LSH instance
+static DEFAULT_LSH_R_SEQS: usize              = encoder::DEFAULT_LSH_R_SEQS; // default r for the Info-DNAs' LSH instance
+static DEFAULT_LSH_B_SEQS: usize              = encoder::DEFAULT_LSH_B_SEQS; // default b for the Info-DNAs' LSH instance
 
-static DEFAULT_MIN_DIST_TO_PROBES: f64        = 0.4_f64;             // default minimum distance to probes
-static DEFAULT_MIN_DIST_TO_SEQS: f64          = 0.4_f64;             // default minimum distance to Info-DNAs
+static DEFAULT_LSH_PERMUTATION_STR: &str      = "pseudo";            // default kind of the permutations of the LSH instances
+static DEFAULT_LSH_BUCKETING_STR: &str        = "minhash";           // default bucketing of the LSH instances
+static DEFAULT_LSH_CGK_LEN: usize             = encoder::DEFAULT_LSH_CGK_LEN; // default length of the sequences embedded by the CGK bucketing
+static DEFAULT_MINHASH_PREFILTER_MARGIN: f64  = encoder::DEFAULT_MINHASH_PREFILTER_MARGIN; // default margin of the MinHash pre-filter of the Jaccard distance (inf = disabled)
+static DEFAULT_CANONICAL_K_MERS: bool         = encoder::DEFAULT_CANONICAL_K_MERS; // default value for whether or not the LSH instances and the Jaccard distance use canonical k-mers
+static DEFAULT_LSH_STATS: bool                = false;               // default value for whether or not to print the bucket statistics of the probes' LSH instance
+static LSH_STATS_SAMPLE_SIZE: usize           = 1000_usize;          // maximum number of probes queried for the distribution of the candidate set sizes
+
+static DEFAULT_MIN_DIST_TO_PROBES: f64        = encoder::DEFAULT_MIN_DIST_TO_PROBES; // default minimum distance to probes
+static DEFAULT_MIN_DIST_TO_SEQS: f64          = encoder::DEFAULT_MIN_DIST_TO_SEQS; // default minimum distance to Info-DNAs
+static DEFAULT_MIN_DIST_TO_SEQS_DECAY_START: usize = encoder::DEFAULT_MIN_DIST_TO_SEQS_DECAY_START; // default number of Info-DNAs after which the minimum distance to Info-DNAs starts to decay
+static DEFAULT_MIN_DIST_TO_SEQS_DECAY_END: usize = encoder::DEFAULT_MIN_DIST_TO_SEQS_DECAY_END; // default number of Info-DNAs at which the minimum distance to Info-DNAs reaches its floor (0 = no decay)
+static DEFAULT_MIN_DIST_TO_SEQS_FLOOR: f64    = encoder::DEFAULT_MIN_DIST_TO_SEQS_FLOOR; // default smallest minimum distance to Info-DNAs the decay reaches
+static DEFAULT_CHECK_REVCOMP: bool            = encoder::DEFAULT_CHECK_REVCOMP; // default value for whether or not the reverse complement of an Info-DNA must be far enough from the probes and Info-DNAs, too
 
 
 
 fn main() {
-    let n_workers = num_cpus::get(); // total number of available logical CPUs
-    let args_parser = arg_parser::ArgsParser::from(env::args().skip(1).collect()); // reading and parsing arguments from console
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "help" || arg == "--help" || arg == "-h") {
+        print_help();
+        return;
+    }
+    if args.iter().any(|arg| arg == "version" || arg == "--version" || arg == "-V") {
+        println!("RQPAP {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+    let args_parser = arg_parser::ArgsParser::from(args); // reading and parsing arguments from console
     let lines_path = args_parser.get_or_else("lines_path", DEFAULT_LINES_PATH);
     let probes_path = args_parser.get_or_else("probes_path", DEFAULT_PROBES_PATH);
+    let probes_lsh_cache = args_parser.get_or_else("probes_lsh_cache", DEFAULT_PROBES_LSH_CACHE);
+    let max_iupac_expansions = args_parser.get_as("max_iupac_expansions", DEFAULT_MAX_IUPAC_EXPANSIONS);
     let info_dna_path = args_parser.get_or_else("info_dna_path", DEFAULT_INFO_DNA_PATH);
+    let output_format_str = args_parser.get_or_else("output_format", DEFAULT_OUTPUT_FORMAT_STR);
+    let prefix_adapter = args_parser.get_or_else("prefix_adapter", DEFAULT_PREFIX_ADAPTER);
+    let suffix_adapter = args_parser.get_or_else("suffix_adapter", DEFAULT_SUFFIX_ADAPTER);
     let encoding_mode_str = args_parser.get_or_else("encoding_mode", DEFAULT_ENCODING_MODE_STR);
+    let similarity_backend_str = args_parser.get_or_else("similarity_backend", DEFAULT_SIMILARITY_BACKEND_STR);
+    let distance_metric_str = args_parser.get_or_else("distance_metric", DEFAULT_DISTANCE_METRIC_STR);
+    let base_mapping_str = args_parser.get_or_else("base_mapping", DEFAULT_BASE_MAPPING_STR);
+    let rotate_mapping = args_parser.get_as_bool("rotate_mapping", DEFAULT_ROTATE_MAPPING);
     let overhead = args_parser.get_as("overhead", DEFAULT_OVERHEAD);
+    let target_redundancy = match args_parser.get("target_redundancy").as_str() {
+        "" => None,
+        s => Some(s.parse::<f64>().unwrap_or_else(|_| panic!("cannot parse target_redundancy: {}", s)))
+    };
+    let rq_symbol_size = args_parser.get_as("rq_symbol_size", DEFAULT_RQ_SYMBOL_SIZE);
+    let rq_source_blocks = args_parser.get_as("rq_source_blocks", DEFAULT_RQ_SOURCE_BLOCKS);
+    let rq_sub_blocks = args_parser.get_as("rq_sub_blocks", DEFAULT_RQ_SUB_BLOCKS);
+    let rq_alignment = args_parser.get_as("rq_alignment", DEFAULT_RQ_ALIGNMENT);
     let max_hp_len = args_parser.get_as("max_hp_len", DEFAULT_MAX_HP_LEN);
+    let max_hp_a = args_parser.get_as("max_hp_a", DEFAULT_MAX_HP_PER_BASE);
+    let max_hp_c = args_parser.get_as("max_hp_c", DEFAULT_MAX_HP_PER_BASE);
+    let max_hp_g = args_parser.get_as("max_hp_g", DEFAULT_MAX_HP_PER_BASE);
+    let max_hp_t = args_parser.get_as("max_hp_t", DEFAULT_MAX_HP_PER_BASE);
+    let min_gc = args_parser.get_as("min_gc", DEFAULT_MIN_GC);
+    let max_gc = args_parser.get_as("max_gc", DEFAULT_MAX_GC);
+    let gc_window = args_parser.get_as("gc_window", DEFAULT_GC_WINDOW);
+    let min_window_gc = args_parser.get_as("min_window_gc", DEFAULT_MIN_WINDOW_GC);
+    let max_window_gc = args_parser.get_as("max_window_gc", DEFAULT_MAX_WINDOW_GC);
+    let forbidden_motifs = args_parser.get_or_else("forbidden_motifs", DEFAULT_FORBIDDEN_MOTIFS);
+    let min_tm = args_parser.get_as("min_tm", DEFAULT_MIN_TM);
+    let max_tm = args_parser.get_as("max_tm", DEFAULT_MAX_TM);
+    let min_entropy = args_parser.get_as("min_entropy", DEFAULT_MIN_ENTROPY);
+    let max_hairpin_stem = args_parser.get_as("max_hairpin_stem", DEFAULT_MAX_HAIRPIN_STEM);
+    let hairpin_min_loop = args_parser.get_as("hairpin_min_loop", DEFAULT_HAIRPIN_MIN_LOOP);
     let use_dg_server = args_parser.get_as_bool("use_dg_server", DEFAULT_USE_DG);
+    let dg_host = args_parser.get_or_else("dg_host", DEFAULT_DG_HOST);
+    let dg_max_inflight = args_parser.get_as("dg_max_inflight", DEFAULT_DG_MAX_INFLIGHT);
+    let dg_connect_timeout_ms = args_parser.get_as("dg_connect_timeout_ms", DEFAULT_DG_CONNECT_TIMEOUT_MS);
+    let dg_read_timeout_ms = args_parser.get_as("dg_read_timeout_ms", DEFAULT_DG_READ_TIMEOUT_MS);
+    let dg_max_retries = args_parser.get_as("dg_max_retries", DEFAULT_DG_MAX_RETRIES);
+    let dg_min_len = args_parser.get_as("dg_min_len", DEFAULT_DG_MIN_LEN);
+    let sec_struct_temp = args_parser.get_as("sec_struct_temp", DEFAULT_SECONDARY_STRUCT_TEMP);
+    let dg_error_offset = args_parser.get_as("dg_error_offset", DEFAULT_DG_ERROR_OFFSET);
+    let dg_error_steepness = args_parser.get_as("dg_error_steepness", DEFAULT_DG_ERROR_STEEPNESS);
+    let max_dg_error = args_parser.get_as("max_dg_error", DEFAULT_MAX_DG_ERROR);
+    let dg_stats = args_parser.get_as_bool("dg_stats", DEFAULT_DG_STATS);
+    let strand_stats = args_parser.get_as_bool("strand_stats", DEFAULT_STRAND_STATS);
+    let quiet = args_parser.get_as_bool("quiet", DEFAULT_QUIET);
+    let resume = args_parser.get_as_bool("resume", DEFAULT_RESUME);
+    let checkpoint_every = args_parser.get_as("checkpoint_every", DEFAULT_CHECKPOINT_EVERY);
+    let dry_run = args_parser.get_as_bool("dry_run", DEFAULT_DRY_RUN);
     let read_as_lines = args_parser.get_as("read_as_lines", DEFAULT_READ_AS_LINES);
     let approve = args_parser.get_as_bool("approve", DEFAULT_APPROVE);
+    let strict_args = args_parser.get_as_bool("strict_args", DEFAULT_STRICT_ARGS);
+    let n_workers = args_parser.get_as("threads", num_cpus::get()); // the number of threads of every pool (default: total number of available logical CPUs)
+    if n_workers == 0 {
+        panic!("threads must be at least 1");
+    }
+    let per_line_timeout_ms = args_parser.get_as("per_line_timeout_ms", DEFAULT_PER_LINE_TIMEOUT_MS);
+    let dg_channels = match args_parser.get_as("dg_channels", DEFAULT_DG_CHANNELS) {
+        0 => n_workers,
+        n => n
+    };
+    let seed = match args_parser.get("seed").as_str() {
+        "" => None,
+        s => Some(s.parse::<u64>().unwrap_or_else(|_| panic!("cannot parse seed: {}", s)))
+    };
 
     let append_to_report = args_parser.get_as_bool("append_to_report", DEFAULT_APPEND_TO_REPORT);
     let report = args_parser.get_as_bool("report", DEFAULT_REPORT);
     let report_path = args_parser.get_or_else("report_path", DEFAULT_REPORT_PATH);
+    let report_json_path = args_parser.get_or_else("report_json_path", DEFAULT_REPORT_JSON_PATH);
+    let report_format_str = args_parser.get_or_else("report_format", DEFAULT_REPORT_FORMAT_STR);
 
     let min_dist_to_probes = args_parser.get_as("min_dist_to_probes", DEFAULT_MIN_DIST_TO_PROBES);
     let min_dist_to_seqs = args_parser.get_as("min_dist_to_seqs", DEFAULT_MIN_DIST_TO_SEQS);
+    let min_dist_to_seqs_decay_start = args_parser.get_as("min_dist_to_seqs_decay_start", DEFAULT_MIN_DIST_TO_SEQS_DECAY_START);
+    let min_dist_to_seqs_decay_end = args_parser.get_as("min_dist_to_seqs_decay_end", DEFAULT_MIN_DIST_TO_SEQS_DECAY_END);
+    let min_dist_to_seqs_floor = args_parser.get_as("min_dist_to_seqs_floor", DEFAULT_MIN_DIST_TO_SEQS_FLOOR);
+    let check_revcomp = args_parser.get_as_bool("check_revcomp", DEFAULT_CHECK_REVCOMP);
 
     let lsh_k_probes = args_parser.get_as("lsh_k_probes", DEFAULT_LSH_K_PROBES);
     let lsh_r_probes = args_parser.get_as("lsh_r_probes", DEFAULT_LSH_R_PROBES);
@@ -93,107 +229,305 @@ fn main() {
     let lsh_k_seqs = args_parser.get_as("lsh_k_seqs", DEFAULT_LSH_K_SEQS);
     let lsh_r_seqs = args_parser.get_as("lsh_r_seqs", DEFAULT_LSH_R_SEQS);
     let lsh_b_seqs = args_parser.get_as("lsh_b_seqs", DEFAULT_LSH_B_SEQS);
+    let lsh_permutation_str = args_parser.get_or_else("lsh_permutation", DEFAULT_LSH_PERMUTATION_STR);
+    let lsh_bucketing_str = args_parser.get_or_else("lsh_bucketing", DEFAULT_LSH_BUCKETING_STR);
+    let lsh_cgk_len = args_parser.get_as("lsh_cgk_len", DEFAULT_LSH_CGK_LEN);
+    let canonical_k_mers = args_parser.get_as_bool("canonical_k_mers", DEFAULT_CANONICAL_K_MERS);
+    let minhash_prefilter_margin = args_parser.get_as("minhash_prefilter_margin", DEFAULT_MINHASH_PREFILTER_MARGIN);
+    let lsh_stats = args_parser.get_as_bool("lsh_stats", DEFAULT_LSH_STATS);
+    let unknown_args = args_parser.validate();
+    if strict_args && !unknown_args.is_empty() {
+        panic!("unknown argument(s): {}. Run with --help to list all arguments or set strict_args=false to ignore unknown arguments", unknown_args.join(", "));
+    }
+    for unknown_arg in unknown_args {
+        println!("WARNING: ignoring unknown argument {}", unknown_arg);
+    }
 
 
     let mut encoding_mode = extract_encoding_mode(encoding_mode_str.as_str());
+    let (probes_backend, seqs_backend) = extract_backends(encoding_mode, similarity_backend_str.as_str());
+    let distance_metric = similarity::extract_distance_metric(distance_metric_str.as_str());
+    let lsh_permutation = pseudo_permutation::extract_permutation_kind(lsh_permutation_str.as_str());
+    let lsh_bucketing = lsh::extract_lsh_bucketing(lsh_bucketing_str.as_str());
+    validate_lsh_params("probes", lsh_k_probes, lsh_r_probes, lsh_b_probes, lsh_bucketing);
+    validate_lsh_params("seqs", lsh_k_seqs, lsh_r_seqs, lsh_b_seqs, lsh_bucketing);
+    let report_format = extract_report_format(report_format_str.as_str());
+    let output_format = extract_output_format(output_format_str.as_str());
+    let base_mapping = base_mapping_str.parse::<BaseMapping>().unwrap_or_else(|e| {
+        println!("ERROR: cannot parse base_mapping {}: {}", base_mapping_str, e);
+        std::process::exit(EXIT_CODE_INVALID_ARGUMENT);
+    }).with_rotation(rotate_mapping);
+    let raptor = RaptorQ::new(rq_source_blocks, rq_sub_blocks, rq_alignment, rq_symbol_size, base_mapping);
+    let adapters = Adapters::from_str(prefix_adapter.as_str(), suffix_adapter.as_str());
+    let min_dist_to_seqs_schedule = MinDistSchedule::new(min_dist_to_seqs_decay_start, min_dist_to_seqs_decay_end, min_dist_to_seqs_floor);
 
     print_parameters(
         lines_path.as_str(),
         probes_path.as_str(),
+        probes_lsh_cache.as_str(),
+        max_iupac_expansions,
         info_dna_path.as_str(),
+        output_format_str.as_str(),
+        prefix_adapter.as_str(),
+        suffix_adapter.as_str(),
+        &raptor,
         overhead,
+        target_redundancy,
         max_hp_len,
+        [max_hp_a, max_hp_c, max_hp_g, max_hp_t],
+        min_gc,
+        max_gc,
+        gc_window,
+        min_window_gc,
+        max_window_gc,
+        forbidden_motifs.as_str(),
+        min_tm,
+        max_tm,
+        min_entropy,
+        max_hairpin_stem,
+        hairpin_min_loop,
         read_as_lines,
         use_dg_server,
+        dg_host.as_str(),
+        dg_channels,
+        dg_max_inflight,
+        dg_connect_timeout_ms,
+        dg_read_timeout_ms,
+        dg_max_retries,
+        dg_min_len,
+        sec_struct_temp,
+        dg_error_offset,
+        dg_error_steepness,
+        max_dg_error,
+        dg_stats,
+        strand_stats,
+        resume,
+        checkpoint_every,
+        quiet,
+        dry_run,
         encoding_mode_str.as_str(),
+        similarity_backend_str.as_str(),
+        distance_metric_str.as_str(),
         min_dist_to_probes,
         min_dist_to_seqs,
+        &min_dist_to_seqs_schedule,
+        check_revcomp,
         approve,
+        strict_args,
+        n_workers,
+        per_line_timeout_ms,
+        seed,
         report,
+        report_format,
+        report_format_str.as_str(),
         report_path.as_str(),
+        report_json_path.as_str(),
         append_to_report,
-        encoding_mode,
+        probes_backend,
+        seqs_backend,
         lsh_k_probes,
         lsh_r_probes,
         lsh_b_probes,
         lsh_k_seqs,
         lsh_r_seqs,
-        lsh_b_seqs);
-
-    if approve && !approve_parameters() {
+        lsh_b_seqs,
+        lsh_permutation_str.as_str(),
+        lsh_bucketing_str.as_str(),
+        lsh_cgk_len,
+        canonical_k_mers,
+        minhash_prefilter_margin,
+        lsh_stats);
+
+    if approve && lines_path != LINES_PATH_STDIN && !approve_parameters() {
         println!("------------------------------------------------------");
         println!("-> Parameters were not approved -> program terminated.");
         return;
     }
     println!("------------------------------------------------------");
 
-    let dg_client = Arc::new(match use_dg_server {
-        true => match DGClient::new(127, 0, 0, 1, DEFAULT_DG_START_PORT, n_workers as u16) {
-            Some(client) => Some(client),
-            _ => panic!("failed to connect to dg server!")
+    if dry_run {
+        print_dry_run(&read_lines_arc(lines_path.as_str(), read_as_lines), &raptor, overhead, target_redundancy, &adapters);
+        return;
+    }
+
+    // a per-base limit of 0 falls back to max_hp_len, and the scalar limit is the largest per-base limit, so that it does not reject the longer homopolymers allowed for a base
+    let max_hp_lens = [max_hp_a, max_hp_c, max_hp_g, max_hp_t].map(|max| if max == 0 { max_hp_len } else { max });
+    let gc_hp_rules = GcHpRules::new(min_gc, max_gc, max_hp_lens.iter().copied().max().unwrap());
+    let base_hp_rules = BaseHpRules::new(max_hp_lens.map(|max| if max == gc_hp_rules.max_hp_len { 0 } else { max })); // the bases at the scalar limit are already checked by gc_hp_rules
+    let gc_window_rules = GcWindowRules::new(gc_window, min_window_gc, max_window_gc);
+    let motif_filter = forbidden_motifs.parse::<MotifFilter>().unwrap_or_else(|e| panic!("cannot parse forbidden_motifs: {}", e));
+    let tm_rules = TmRules::new(min_tm, max_tm);
+    let dg_rules = DgRules::new(dg_error_offset, dg_error_steepness, max_dg_error);
+    let entropy_rules = EntropyRules::new(min_entropy);
+    let hairpin_rules = HairpinRules::new(max_hairpin_stem, hairpin_min_loop);
+    let dg_client = match use_dg_server {
+        true => {
+            let config = DGClientConfig::new(DEFAULT_DG_START_PORT, dg_channels as u16)
+                .with_max_inflight(dg_max_inflight)
+                .with_timeouts(DGTimeouts::from_millis(dg_connect_timeout_ms, dg_read_timeout_ms))
+                .with_max_retries(dg_max_retries);
+            match DGClient::from_host(dg_host.as_str(), &config) {
+                Ok(client) => {
+                    if client.channel_count() < dg_channels {
+                        println!("WARNING: only {} of {} dg channels connected to {} (ports {} to {}) -> using {} channels", client.channel_count(), dg_channels, dg_host, DEFAULT_DG_START_PORT, DEFAULT_DG_START_PORT as usize + dg_channels - 1_usize, client.channel_count());
+                    }
+                    Some(client)
+                },
+                Err(e) => panic!("{}", e)
+            }
         },
         false => None
-    });
+    };
 
-    let mut lines = read_lines_arc(lines_path.as_str(), read_as_lines);
+    let lines = read_lines_arc(lines_path.as_str(), read_as_lines);
     println!("lines imported         = {}", lines.len());
 
-    let probes = Arc::new(SafeCell::new(BaseSequence::read_fasta_arc(probes_path.as_str())));
+    let probes = read_probes(&probes_paths(probes_path.as_str()), max_iupac_expansions);
     println!("probes imported        = {}", probes.len());
     println!("------------------------------------------------------");
+    if lines.len() != probes.len() {
+        println!("WARNING: jobs ({}) != probes ({})", lines.len(), probes.len());
+    }
+    let short_probes = probes.iter().filter(|probe| probe.len() < lsh_k_probes).count();
+    if probes_backend == SIMILARITY_BACKEND_APPROX && lsh_bucketing == lsh::LSH_BUCKETING_MINHASH && short_probes > 0 {
+        println!("WARNING: {} probes are shorter than lsh_k_probes ({}) -> they are never returned as LSH candidates", short_probes, lsh_k_probes);
+    }
 
-    let mut probes_lsh = Arc::new(SafeCell::new(LSH::new(lsh_k_probes, 1, 1))); // the probes' LSH instance (is ignored if encoding mode is NAIVE)
-    let mut seqs_lsh = Arc::new(RwLock::new(SafeCell::new(LSH::new(lsh_k_seqs, 1, 1)))); // the Info-DNAs' LSH instance (is ignored if encoding mode is MIXED or NAIVE)
     let mut start_time = SystemTime::now();
-    if encoding_mode == ENCODING_MODE_LSH || encoding_mode == ENCODING_MODE_MIXED {
-        println!("building LSH for probes...");
-        probes_lsh = Arc::new(SafeCell::new(LSH::new(lsh_k_probes, lsh_r_probes, lsh_b_probes)));
-        let start_building_time = SystemTime::now();
-        let insert_pool = rayon::ThreadPoolBuilder::new().num_threads(n_workers).build().unwrap();
-        let probes_count = probes.len();
-        let (sender, receiver) = bounded(probes_count);
-
-        // insert all read probes into the probes' LSH instance in parallel
-        for p in probes.iter() {
-            let sender_cloned = sender.clone();
-            let probes_lsh_cloned = probes_lsh.clone();
-            let probe = p.clone();
-            insert_pool.spawn(move|| {
-                probes_lsh_cloned.get_mut().insert(&probe);
-                sender_cloned.send(true);
-            });
+    let mut encoder_builder = Encoder::builder(probes)
+        .with_workers(n_workers)
+        .with_raptor(raptor)
+        .with_overhead(overhead)
+        .with_target_redundancy(target_redundancy)
+        .with_gc_hp_rules(gc_hp_rules)
+        .with_base_hp_rules(base_hp_rules)
+        .with_gc_window_rules(gc_window_rules)
+        .with_motif_filter(motif_filter)
+        .with_tm_rules(tm_rules)
+        .with_entropy_rules(entropy_rules)
+        .with_hairpin_rules(hairpin_rules)
+        .with_min_dist_to_probes(min_dist_to_probes)
+        .with_min_dist_to_seqs(min_dist_to_seqs)
+        .with_min_dist_to_seqs_schedule(min_dist_to_seqs_schedule)
+        .with_check_revcomp(check_revcomp)
+        .with_similarity_backends(probes_backend, seqs_backend)
+        .with_distance_metric(distance_metric)
+        .with_probes_lsh_params(lsh_k_probes, lsh_r_probes, lsh_b_probes)
+        .with_seqs_lsh_params(lsh_k_seqs, lsh_r_seqs, lsh_b_seqs)
+        .with_lsh_permutation(lsh_permutation)
+        .with_lsh_bucketing(lsh_bucketing, lsh_cgk_len)
+        .with_canonical_k_mers(canonical_k_mers)
+        .with_min_hash_prefilter(minhash_prefilter_margin)
+        .with_dg_client(dg_client)
+        .with_dg_rules(dg_rules)
+        .with_dg_min_len(dg_min_len)
+        .with_sec_struct_temp(sec_struct_temp)
+        .with_per_line_timeout_ms(per_line_timeout_ms)
+        .with_on_retry(|line_id, e, packets_per_block| println!("WARNING: failed to encode line {} within {} loops ({}) -> retrying with {} packets per block", line_id, encoder::MAX_ENCODE_LOOPS, e, packets_per_block))
+        .with_seed(seed);
+    if probes_backend == SIMILARITY_BACKEND_APPROX {
+        let cached_probes_lsh = if !probes_lsh_cache.is_empty() && Path::new(probes_lsh_cache.as_str()).exists() {
+            match LSH::load(probes_lsh_cache.as_str()) {
+                Ok(lsh) => {
+                    let same_bucketing = if lsh_bucketing == lsh::LSH_BUCKETING_CGK {
+                        lsh.bucketing() == lsh_bucketing && lsh.cgk_len() == lsh_cgk_len
+                    }
+                    else {
+                        lsh.bucketing() == lsh_bucketing && lsh.k() == lsh_k_probes && lsh.permutation_kind() == lsh_permutation && lsh.is_canonical() == canonical_k_mers
+                    };
+                    let probes_fingerprint = SeqsFingerprint::of(encoder_builder.probes());
+                    if !(same_bucketing && lsh.r() == lsh_r_probes && lsh.b() == lsh_b_probes && !lsh.indexes_revcomp()) {
+                        println!("WARNING: cached LSH for probes has different parameters (k={}, r={}, b={}, exact permutations={}, canonical k-mers={}, cgk_len={}, revcomp indexed={}) -> rebuilding", lsh.k(), lsh.r(), lsh.b(), lsh.permutation_kind() == pseudo_permutation::PERMUTATION_EXACT, lsh.is_canonical(), lsh.cgk_len(), lsh.indexes_revcomp());
+                        None
+                    }
+                    else if lsh.source_fingerprint() != Some(probes_fingerprint) {
+                        let cached_probes = lsh.source_fingerprint().map_or_else(|| "unknown".to_string(), |fingerprint| fingerprint.count().to_string());
+                        println!("WARNING: cached LSH for probes was built from different probes ({} cached, {} imported) -> rebuilding", cached_probes, probes_fingerprint.count());
+                        None
+                    }
+                    else {
+                        println!("loaded LSH for probes from {}", probes_lsh_cache);
+                        Some(lsh)
+                    }
+                }
+                Err(e) => {
+                    println!("WARNING: cannot load cached LSH for probes from {} ({}) -> rebuilding", probes_lsh_cache, e);
+                    None
+                }
+            }
         }
-        receiver.iter().take(probes_count).for_each(|_| {}); // halts until all probes were inserted into the LSH
-        println!("finished building LSH for probes in {} seconds", SystemTime::now().duration_since(start_building_time).unwrap().as_millis() as f64 / 1000_f64);
-    }
-    if encoding_mode == ENCODING_MODE_LSH {
-        seqs_lsh = Arc::new(RwLock::new(SafeCell::new(LSH::new(lsh_k_seqs, lsh_r_seqs, lsh_b_seqs))));
+        else {
+            None
+        };
+        let probes_lsh = cached_probes_lsh.unwrap_or_else(|| {
+            println!("building LSH for probes...");
+            let start_building_time = SystemTime::now();
+            let lsh = encoder_builder.build_probes_lsh();
+            println!("finished building LSH for probes in {} seconds", SystemTime::now().duration_since(start_building_time).unwrap().as_millis() as f64 / 1000_f64);
+            if !probes_lsh_cache.is_empty() {
+                match lsh.save(probes_lsh_cache.as_str()) {
+                    Ok(()) => println!("saved LSH for probes to {}", probes_lsh_cache),
+                    Err(e) => println!("WARNING: cannot save LSH for probes to {} ({}) -> continuing without caching it", probes_lsh_cache, e)
+                }
+            }
+            lsh
+        });
+        if lsh_stats {
+            print_lsh_stats("probes", &probes_lsh, encoder_builder.probes());
+        }
+        encoder_builder = encoder_builder.with_probes_lsh(probes_lsh);
     }
+    let encoder = encoder_builder.build();
 
     println!("initiating...");
 
+    let checkpoint_path = format!("{}{}", info_dna_path, CHECKPOINT_EXTENSION);
+    let mut resumed = if resume && Path::new(info_dna_path.as_str()).exists() {
+        read_resumable_info_dnas(info_dna_path.as_str(), output_format, &lines, encoder.raptor(), &adapters)
+    }
+    else {
+        Vec::new()
+    };
+    // the checkpoint of a crashed run may hold Info-DNAs that did not make it into info_dna_path, since they are written there in the order of the lines
+    if resume && Path::new(checkpoint_path.as_str()).exists() {
+        let line_ids = resumed.iter().map(|(line_id, _)| *line_id).collect::<HashSet<_>>();
+        let checkpointed = read_resumable_info_dnas(checkpoint_path.as_str(), OUTPUT_FORMAT_BINARY, &lines, encoder.raptor(), &adapters);
+        resumed.extend(checkpointed.into_iter().filter(|(line_id, _)| !line_ids.contains(line_id)));
+    }
     match fs::remove_file(info_dna_path.as_str()) {
         Ok(_) => println!("Overriding file: {}", info_dna_path.as_str()),
         Err(_) => {}
     }
-    let mut info_dna_file = OpenOptions::new().append(true).create(true).open(info_dna_path.as_str()).unwrap();
+    let mut info_dna_file = BaseSequence::create_maybe_gzipped(info_dna_path.as_str()).unwrap_or_else(|e| panic!("failed to create {}: {}", info_dna_path, e));
+    // the first Ctrl-C stops dispatching new lines, so that the lines in flight finish and the output files are flushed, and the second one aborts immediately
+    let cancel_flag = encoder.cancel_flag();
+    ctrlc::set_handler(move || {
+        if cancel_flag.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_CODE_INTERRUPTED);
+        }
+        println!("\ninterrupted: finishing the lines in flight (press Ctrl-C again to abort)...");
+    }).unwrap_or_else(|e| println!("WARNING: failed to install the Ctrl-C handler: {}", e));
     encode_pipeline(
-        n_workers,
         report,
+        report_format,
         append_to_report,
         report_path.as_str(),
+        report_json_path.as_str(),
         use_dg_server,
-        probes_lsh,
-        seqs_lsh,
-        probes,
+        dg_stats,
+        strand_stats,
+        quiet,
+        &encoder,
         info_dna_file,
         lines,
+        resumed,
+        checkpoint_every,
+        checkpoint_path.as_str(),
+        output_format,
+        adapters,
         encoding_mode,
-        overhead,
-        max_hp_len,
-        min_dist_to_probes,
-        min_dist_to_seqs,
-        dg_client
+        gc_hp_rules,
+        min_dist_to_probes
     );
 
     let time_millis = SystemTime::now().duration_since(start_time).unwrap().as_millis();
@@ -201,6 +535,9 @@ fn main() {
     println!("finished encoding all lines in {} seconds", (time_millis as f64 / 1000 as f64));
     println!("finished encoding all lines in {} minutes", (time_millis as f64 / 1000 as f64 / 60 as f64));
     println!("finished encoding all lines in {} hours", (time_millis as f64 / 1000 as f64 / 60 as f64 / 60 as f64 ));
+    if encoder.is_cancelled() {
+        std::process::exit(EXIT_CODE_INTERRUPTED);
+    }
 
 }
 
@@ -208,100 +545,88 @@ fn main() {
 ///
 /// # Arguments
 ///
-/// * `n_workers` - The number of available logical CPUs.
-/// * `report` - "true" to report encoding results into a csv file, and "false" to disable reporting.
-/// * `append_to_report` - "true" to append encoding results to an existing csv file, and "false" to write the results to a new csv file.
+/// * `report` - "true" to report encoding results into a csv and/or json file, and "false" to disable reporting.
+/// * `report_format` - The report format represented as a number (0=CSV, 1=JSON, 2=BOTH).
+/// * `append_to_report` - "true" to append encoding results to existing report files, and "false" to write the results to new files.
 /// * `report_path` - The csv file's path to report encoding results to.
+/// * `report_json_path` - The newline-delimited json file's path to report encoding results to.
 /// * `use_dg_server` - "true" to use the dg server, and "no" to disable using the dg server.
-/// * `probes_lsh` - The probes' LSH instance.
-/// * `seqs_lsh` - The Indo-DNAs' LSH instance.
-/// * `probes` - The vector containing the probes.
-/// * `info_dna_file` - The Info-DNA's file path.
+/// * `dg_stats` - "true" to print the distribution of the dg energies of the Info-DNAs at the end (ignored if the dg server is not used).
+/// * `strand_stats` - "true" to print the distributions of the GC content, the length, and the longest homopolymer of all Info-DNAs in `info_dna_file` (including the resumed ones) at the end.
+/// * `quiet` - "true" to suppress the progress line that is updated whenever a data object was encoded.
+/// * `encoder` - The encoder of the data objects.
+/// * `info_dna_file` - The Info-DNA's file path. The Info-DNAs are written in the order of `lines` regardless of the order in which they are encoded.
 /// * `lines` - A vector with the data objects to encode.
+/// * `resumed` - The Info-DNAs of an interrupted run as (line id, Info-DNA). They are written to `info_dna_file` at their lines, and only the other data objects are encoded.
+/// * `checkpoint_every` - The number of encoded data objects after which their Info-DNAs are appended to `checkpoint_path`, which starts with the resumed ones (0 = no checkpoints).
+/// * `checkpoint_path` - The path of the checkpoint (see `write_checkpoint` and `append_to_checkpoint`). It is removed once `info_dna_file` is complete.
+/// * `output_format` - The format of `info_dna_file` represented as a number (0=FASTA, 1=BINARY).
+/// * `adapters` - The adapters that flank every Info-DNA written to `info_dna_file`.
 /// * `encoding_mode` - The encoding mode represented as a number (0=LSH, 1=MIXED, 2=NAIVE).
-/// * `overhead` - The overhead ε for RQ.
-/// * `max_hp_len` - The maximum allowed length of a homopolymer.
-/// * `min_dist_to_probes` - The minimum required distance of an Info-DNA to a probe.
-/// * `min_dist_to_seqs` - The minimum required distance of an Info-DNA to another Info-DNA.
-/// * `dg_client` - The client object for communicating with the dg server.
-fn encode_pipeline(n_workers: usize,
-                   report: bool,
+/// * `gc_hp_rules` - The constraints on the GC content and the maximum homopolymer length of an Info-DNA (reported only).
+/// * `min_dist_to_probes` - The minimum required distance of an Info-DNA to a probe (reported only).
+fn encode_pipeline(report: bool,
+                   report_format: usize,
                    append_to_report: bool,
                    report_path: &str,
+                   report_json_path: &str,
                    use_dg_server: bool,
-                   probes_lsh: Arc<SafeCell<LSH>>,
-                   seqs_lsh: Arc<RwLock<SafeCell<LSH>>>,
-                   probes: Arc<SafeCell<Vec<Arc<BaseSequence>>>>,
-                   mut info_dna_file: File,
+                   dg_stats: bool,
+                   strand_stats: bool,
+                   quiet: bool,
+                   encoder: &Encoder,
+                   mut info_dna_file: Box<dyn Write>,
                    lines: Vec<Arc<Vec<u8>>>,
+                   resumed: Vec<(usize, Arc<BaseSequence>)>,
+                   checkpoint_every: usize,
+                   checkpoint_path: &str,
+                   output_format: usize,
+                   adapters: Adapters,
                    encoding_mode: usize,
-                   overhead: usize,
-                   max_hp_len: usize,
-                   min_dist_to_probes: f64,
-                   min_dist_to_seqs: f64,
-                   dg_client: Arc<Option<DGClient>>) {
-
-    if lines.len() != probes.get().len() {
-        println!("WARNING: jobs ({}) != probes ({})", lines.len(), probes.get().len());
-    }
+                   gc_hp_rules: GcHpRules,
+                   min_dist_to_probes: f64) {
 
     let mut csv = None;
+    let mut json = None;
 
-    if report {
+    if report && report_format != REPORT_FORMAT_JSON {
         if !append_to_report {
             fs::remove_file(report_path);
             csv = Some(OpenOptions::new().append(true).create(true).open(report_path).unwrap());
-            csv.as_ref().unwrap().write_all(["Progress(%)", "Line Id", "Done Id", "Trials", "Time(ms)", "Time For", "File Size", "Total Bytes", "Overhead", "Length", "Max HP Length", "Min. Dist To Probes", "Min. Dist To Seqs", "Encoding Mode", "Use DG Server", "Total Time"].join(DEFAULT_CSV_DELIMITER).as_bytes());
+            csv.as_ref().unwrap().write_all(ReportRow::header().as_bytes());
         }
         else {
             csv = Some(OpenOptions::new().append(true).create(true).open(report_path).unwrap());
             if Path::new(report_path).metadata().unwrap().len() == 0_u64 {
-                csv.as_ref().unwrap().write_all(["Progress(%)", "Line Id", "Done Id", "Trials", "Time(ms)", "Time For", "File Size", "Total Bytes", "Overhead", "Length", "Max HP Length", "Min. Dist To Probes", "Min. Dist To Seqs", "Encoding Mode", "Use DG Server", "Total Time"].join(DEFAULT_CSV_DELIMITER).as_bytes());
+                csv.as_ref().unwrap().write_all(ReportRow::header().as_bytes());
             }
         }
     }
+    if report && report_format != REPORT_FORMAT_CSV {
+        json = Some(OpenOptions::new().append(true).create(true).truncate(false).open(report_json_path).unwrap());
+        if !append_to_report {
+            json.as_ref().unwrap().set_len(0_u64).unwrap();
+        }
+    }
 
-    let pool = rayon::ThreadPoolBuilder::new().num_threads(n_workers).build().unwrap(); // the thread pool that encodes the data objects each in a thread
-    let dist_pool = Arc::new(RwLock::new(rayon::ThreadPoolBuilder::new().num_threads(n_workers).build().unwrap())); // the thread pool that is used to parallelize distance checks
-
-    let (sender, receiver) = bounded(lines.len());
-    let raptor = Arc::new(RaptorQ::default());
-    let mut seqs = Arc::new(RwLock::new(Vec::with_capacity(lines.len())));
+    let mut buffered = BTreeMap::new(); // line id -> Info-DNA (None if the line failed to encode) of the lines that are done but not written yet, since a previous line is still encoding
+    let mut next_line_id = 0_usize; // the id of the next line to write to `info_dna_file`
+    let mut written = 0_usize; // the number of Info-DNAs written to `info_dna_file`
+    for (line_id, seq) in resumed.iter() {
+        encoder.insert(seq);
+        buffered.insert(*line_id, Some(seq.clone()));
+    }
+    let pending = (0..lines.len()).filter(|line_id| !buffered.contains_key(line_id)).collect::<Vec<_>>(); // the ids of the lines that are not encoded yet
+    let mut done = resumed; // the Info-DNAs written to `info_dna_file` or buffered as (line id, Info-DNA)
+    write_info_dnas_in_order(&mut info_dna_file, &mut buffered, &mut next_line_id, &mut written, &adapters, output_format);
+    if checkpoint_every > 0_usize {
+        write_checkpoint(checkpoint_path, &done, &adapters);
+    }
+    let mut checkpointed = done.len(); // the number of Info-DNAs of `done` in the checkpoint
 
     println!("---> [started] <---");
     let start_time = Rc::new(SystemTime::now());
 
-    for line_id in 0..lines.len() {
-        let sender_cloned = sender.clone();
-        let line = lines.get(line_id).unwrap().clone();
-        let raptor_cloned = raptor.clone();
-        let encoded_seqs_lsh_cloned = seqs_lsh.clone();
-        let probes_lsh_cloned = probes_lsh.clone();
-        let dg_client_cloned = dg_client.clone();
-        let seqs_cloned = seqs.clone();
-        let probes_cloned = probes.clone();
-        let dist_pool_cloned = dist_pool.clone();
-        pool.spawn(move|| {
-            encode_file(
-                encoding_mode,
-                dist_pool_cloned,
-                (line_id + 1_usize, line),
-                raptor_cloned,
-                encoded_seqs_lsh_cloned,
-                probes_lsh_cloned,
-                seqs_cloned,
-                probes_cloned,
-                min_dist_to_probes,
-                min_dist_to_seqs,
-                sender_cloned,
-                INITIAL_PACKETS_PER_BLOCK,
-                overhead,
-                max_hp_len,
-                dg_client_cloned
-            )
-        });
-    }
-
     let encoding_mode_string = if encoding_mode == ENCODING_MODE_LSH {
         String::from("LSH")
     }
@@ -312,340 +637,533 @@ fn encode_pipeline(n_workers: usize,
         String::from("Naive")
     };
 
-    let use_dg_server_string = use_dg_server.to_string();
-    let min_dist_to_probes_string = min_dist_to_probes.to_string();
-    let min_dist_to_seqs_string = min_dist_to_seqs.to_string();
-    let overhead_string = overhead.to_string();
-    let max_hp_length_string = max_hp_len.to_string();
-    let mut caption = String::new();
     let mut total_bytes = 0_usize;
-    for done_id in 1..=lines.len() {
-        let (line_id, seq, trails, size, rq_time, dg_time, total_time) = receiver.recv().unwrap();
-        caption.push_str(">");
-        caption.push_str((line_id + 1_usize).to_string().as_str());
-        BaseSequence::append_to_fasta_file_with_caption_arc(&mut info_dna_file, &seq, caption.as_str(), done_id == 1);
-        caption.clear();
+    let mut dgs = Vec::with_capacity(pending.len());
+    let mut failed = Vec::new(); // the ids of the lines that failed to encode
+    let mut timed_out = Vec::new(); // the ids of the lines that exceeded `per_line_timeout_ms`
+    let mut cancelled = 0_usize; // the number of lines that were not started because the run was interrupted
+    let mut rejections_total = Rejections::default(); // the rejected candidates of all encoded lines
+    let mut accepted = 0_usize; // the number of encoded lines, i.e., accepted candidates
+    let mut done_id = 0_usize;
+    encoder.encode_with(&lines, &pending, |encoded| {
+        done_id += 1_usize;
+        let EncodedStrand { line_id, sequence: seq, trials: trails, bytes: size, rq_time, dg_time, total_time, dg, overhead, packets_used, achieved_overhead, min_dist_to_seqs, rejections } = match encoded {
+            Ok(strand) => strand,
+            Err(e) => {
+                match e.kind {
+                    LineErrorKind::Cancelled => cancelled += 1_usize,
+                    LineErrorKind::TimedOut  => { println!("\nERROR: {} -> skipped", e); timed_out.push(e.line_id) },
+                    LineErrorKind::Panicked  => { println!("\nERROR: {} -> skipped", e); failed.push(e.line_id) }
+                }
+                buffered.insert(e.line_id - 1_usize, None);
+                write_info_dnas_in_order(&mut info_dna_file, &mut buffered, &mut next_line_id, &mut written, &adapters, output_format);
+                return;
+            }
+        };
+        if let Some(dg) = dg.filter(|dg| dg.is_finite()) {
+            dgs.push(dg as f64);
+        }
+        rejections_total = rejections_total.add(&rejections);
+        accepted += 1_usize;
+        buffered.insert(line_id - 1_usize, Some(seq.clone()));
+        done.push((line_id - 1_usize, seq.clone()));
+        write_info_dnas_in_order(&mut info_dna_file, &mut buffered, &mut next_line_id, &mut written, &adapters, output_format);
+        if checkpoint_every > 0_usize && accepted.is_multiple_of(checkpoint_every) {
+            append_to_checkpoint(checkpoint_path, &done, checkpointed, &adapters);
+            checkpointed = done.len();
+        }
+
+        if !quiet {
+            print_progress(done_id, pending.len(), SystemTime::now().duration_since(*start_time).unwrap());
+        }
 
         if report {
             total_bytes += size;
-            let progress_string = (100_f64 * done_id as f64 / lines.len() as f64).to_string();
-            let line_id_string = line_id.to_string();
-            let done_id_str = done_id.to_string();
-            let trails_string = trails.to_string();
-            let rq_time_str = rq_time.to_string();
-            let dg_time_str = dg_time.to_string();
-            let total_time_string = total_time.to_string();
-            let file_size_string = size.to_string();
-            let total_bytes_string = total_bytes.to_string();
-            let seq_len_string = seq.len().to_string();
-            let time_till_now = SystemTime::now().duration_since(*start_time.clone()).unwrap().as_millis().to_string();
-            report_to_csv(&mut csv,
-                          encoding_mode_string.as_str(),
-                          use_dg_server_string.as_str(),
-                          min_dist_to_probes_string.as_str(),
-                          min_dist_to_seqs_string.as_str(),
-                          overhead_string.as_str(),
-                          progress_string.as_str(),
-                          line_id_string.as_str(),
-                          done_id_str.as_str(),
-                          trails_string.as_str(),
-                          rq_time_str.as_str(),
-                          dg_time_str.as_str(),
-                          total_time_string.as_str(),
-                          file_size_string.as_str(),
-                          total_bytes_string.as_str(),
-                          seq_len_string.as_str(),
-                          max_hp_length_string.as_str(),
-                          time_till_now.as_str());
+            let time_till_now_millis = SystemTime::now().duration_since(*start_time.clone()).unwrap().as_millis();
+            if json.is_some() {
+                report_to_json(&mut json,
+                               encoding_mode_string.as_str(),
+                               use_dg_server,
+                               min_dist_to_probes,
+                               min_dist_to_seqs,
+                               overhead,
+                               gc_hp_rules.max_hp_len,
+                               100_f64 * done_id as f64 / pending.len() as f64,
+                               line_id,
+                               done_id,
+                               trails,
+                               rq_time,
+                               dg_time,
+                               total_time,
+                               size,
+                               total_bytes,
+                               seq.len(),
+                               dg,
+                               packets_used,
+                               achieved_overhead,
+                               &rejections,
+                               time_till_now_millis);
+            }
+            if csv.is_some() {
+                let rq_row = ReportRow {
+                    progress: 100_f64 * done_id as f64 / pending.len() as f64,
+                    line_id,
+                    done_id,
+                    trials: trails,
+                    time: Some(rq_time),
+                    time_for: "RQ",
+                    file_size: size,
+                    total_bytes,
+                    overhead,
+                    length: seq.len(),
+                    max_hp_len: gc_hp_rules.max_hp_len,
+                    min_dist_to_probes,
+                    min_dist_to_seqs,
+                    encoding_mode: encoding_mode_string.as_str(),
+                    use_dg_server,
+                    time_till_now: time_till_now_millis,
+                    packets_used,
+                    achieved_overhead
+                };
+                report_to_csv(&mut csv, &[
+                    rq_row,
+                    ReportRow { time: dg_time, time_for: if dg.is_some() || dg_time.is_none() { "Sec. Struct." } else { "Sec. Struct. (skipped)" }, ..rq_row },
+                    ReportRow { time: Some(total_time), time_for: "Total", ..rq_row }
+                ]);
+            }
         }
-    }
+    });
 
-    if report {
-        csv.as_ref().unwrap().flush();
+    if let Some(csv) = csv.as_mut() {
+        csv.flush();
+    }
+    if let Some(json) = json.as_mut() {
+        json.flush().unwrap();
     }
+    info_dna_file.flush().unwrap();
+    let _ = fs::remove_file(checkpoint_path); // every Info-DNA of the checkpoint is in `info_dna_file` now
 
+    if !quiet {
+        println!();
+    }
+    if !failed.is_empty() {
+        failed.sort_unstable();
+        println!("WARNING: {} of {} lines failed to encode and were skipped: {:?}", failed.len(), pending.len(), failed);
+    }
+    if !timed_out.is_empty() {
+        timed_out.sort_unstable();
+        println!("WARNING: {} of {} lines timed out and were skipped: {:?}", timed_out.len(), pending.len(), timed_out);
+    }
+    if cancelled > 0_usize {
+        println!("interrupted: {} of {} lines completed, {} were not started (rerun with resume=true to encode them)", pending.len() - cancelled, pending.len(), cancelled);
+    }
     println!("---> [finished] <---");
+
+    print_rejections(&rejections_total, accepted);
+    if use_dg_server && dg_stats {
+        print_distribution("dg energy", &mut dgs, DISTRIBUTION_HISTOGRAM_BINS);
+    }
+    if strand_stats {
+        print_strand_stats(&done.into_iter().map(|(_, seq)| seq).collect::<Vec<_>>());
+    }
 }
 
-/// The funtion that reports the current encoding state to the csv file.
-#[inline(always)]
-fn report_to_csv(csv: &mut Option<File>, encoding_mode_string: &str, use_dg_server_string: &str, min_dist_to_probes_string: &str, min_dist_to_seqs_string: &str, overhead_string: &str, progress_string: &str, line_id_string: &str, done_id_str: &str, trails_string: &str, rq_time_str: &str, dg_time_str: &str, total_time_string: &str, file_size_string: &str, total_bytes_string: &str, seq_len_string: &str, max_hp_length_string: &str, time_till_now: &str) {
-    let mut row = String::new();
-    row.push_str(DEFAULT_CSV_NEW_LINE);
-    row.push_str(progress_string);               // progress in %
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(line_id_string);                // line id
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(done_id_str);             // done_id
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(trails_string);                 // trys
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(rq_time_str);             // rq time
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str("RQ");                    // time type
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(file_size_string);              // file size
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(total_bytes_string);            // total bytes
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(overhead_string);               // overhead
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(seq_len_string);                // length
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(max_hp_length_string);          // max hp length
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(min_dist_to_probes_string);     // min dist to probes
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(min_dist_to_seqs_string);       // min dist to seqs
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(encoding_mode_string);          // encoding mode
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(use_dg_server_string);          // use_dg_server
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(time_till_now);           // total_time_till_now
-
-
-    row.push_str(DEFAULT_CSV_NEW_LINE);
-    row.push_str(progress_string);               // progress in %
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(line_id_string);                // line id
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(done_id_str);             // done_id
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(trails_string);                 // trys
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(dg_time_str);             // dg time
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str("Sec. Struct.");          // time type
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(file_size_string);              // file size
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(total_bytes_string);            // total bytes
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(overhead_string);               // overhead
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(seq_len_string);                // length
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(max_hp_length_string);          // max hp length
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(min_dist_to_probes_string);     // min dist to probes
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(min_dist_to_seqs_string);       // min dist to seqs
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(encoding_mode_string);          // encoding mode
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(use_dg_server_string);          // use_dg_server
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(time_till_now);           // total_time_till_now
-
-
-    row.push_str(DEFAULT_CSV_NEW_LINE);
-    row.push_str(progress_string);               // progress in %
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(line_id_string);                // line id
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(done_id_str);             // done_id
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(trails_string);                 // trys
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(total_time_string);             // total time
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str("Total");                 // time type
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(file_size_string);              // file size
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(total_bytes_string);            // total bytes
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(overhead_string);               // overhead
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(seq_len_string);                // length
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(max_hp_length_string);          // max hp length
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(min_dist_to_probes_string);     // min dist to probes
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(min_dist_to_seqs_string);       // min dist to seqs
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(encoding_mode_string);          // encoding mode
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(use_dg_server_string);          // use_dg_server
-    row.push_str(DEFAULT_CSV_DELIMITER);
-    row.push_str(time_till_now);           // total_time_till_now
+/// Prints the number of candidate Info-DNAs rejected for every reason (see `RejectReason`) next to the `accepted` ones to the console, together with the reason most candidates were rejected for and the collision rate,
+/// i.e., the share of the candidates that passed all other checks but were too close to (or duplicates of) an Info-DNA encoded before. A high collision rate suggests that `min_dist_to_seqs` is too strict.
+fn print_rejections(rejections: &Rejections, accepted: usize) {
+    let share = |count: usize, of: usize| if of > 0 { 100_f64 * count as f64 / of as f64 } else { 0_f64 };
+    let candidates = rejections.total() + accepted;
+    let collisions = rejections.get(RejectReason::Duplicate) + rejections.get(RejectReason::SeqDistance);
+    println!("------------------------------------------------------");
+    println!("candidates: {} accepted, {} rejected ({:.2}%)", accepted, rejections.total(), share(rejections.total(), candidates));
+    for (reason, count) in rejections.iter() {
+        println!("rejected for {:<14} = {} ({:.2}%)", reason.name(), count, share(count, rejections.total()));
+    }
+    if let Some(reason) = rejections.most_frequent() {
+        println!("most rejections: {} ({:.2}% of the retries)", reason, share(rejections.get(reason), rejections.total()));
+    }
+    println!("collision rate with Info-DNAs = {:.2}%", share(collisions, collisions + accepted));
+}
 
-    csv.as_ref().unwrap().write_all(row.as_bytes());
+/// Prints the distributions of the GC content (in bins of `GC_HISTOGRAM_BIN_WIDTH`), the length, and the longest homopolymer (in bins of 1) of the Info-DNAs `seqs` to the console, followed by the base composition of all Info-DNAs together.
+/// The Info-DNAs include their headers, but not the adapters.
+fn print_strand_stats(seqs: &[Arc<BaseSequence>]) {
+    print_distribution_with_bin_width("GC content", &mut seqs.iter().map(|seq| seq.gc()).collect::<Vec<_>>(), GC_HISTOGRAM_BIN_WIDTH);
+    print_distribution("length", &mut seqs.iter().map(|seq| seq.len() as f64).collect::<Vec<_>>(), DISTRIBUTION_HISTOGRAM_BINS);
+    print_distribution_with_bin_width("longest homopolymer", &mut seqs.iter().map(|seq| seq.longest_hp() as f64).collect::<Vec<_>>(), 1_f64);
+
+    let counts = seqs.iter().fold([0_usize; 4], |mut total, seq| {
+        total.iter_mut().zip(seq.count_bases().iter()).for_each(|(total, count)| *total += count);
+        total
+    });
+    let bases = counts.iter().sum::<usize>();
+    println!("------------------------------------------------------");
+    println!("base composition ({} bases)", bases);
+    println!("{}", Base::ALL.iter().map(|&base| format!("{} = {:.2}%", base.to_string(), if bases > 0 { 100_f64 * counts[base as usize] as f64 / bases as f64 } else { 0_f64 })).collect::<Vec<_>>().join(", "));
 }
 
+/// Writes the Info-DNAs in `buffered` (line id -> Info-DNA, or None if the line failed to encode) to `info_dna_file` as long as they continue the lines written so far, i.e., starting at the line id `next_line_id`, and removes them from `buffered`.
+/// Hence, the Info-DNAs are written in the order of their lines, even though they are encoded in parallel. `next_line_id` and the number of written Info-DNAs `written` are updated accordingly.
+fn write_info_dnas_in_order(info_dna_file: &mut impl Write, buffered: &mut BTreeMap<usize, Option<Arc<BaseSequence>>>, next_line_id: &mut usize, written: &mut usize, adapters: &Adapters, output_format: usize) {
+    while let Some(seq) = buffered.remove(next_line_id) {
+        if let Some(seq) = seq {
+            write_info_dna(info_dna_file, &adapters.wrap(&seq), *next_line_id + 1_usize, output_format, *written == 0_usize);
+            *written += 1_usize;
+        }
+        *next_line_id += 1_usize;
+    }
+}
 
-/// The function that converts the dg energy obtained from the dg server into an error score betwee 0 (lowest) and 1 (highest).
+/// Appends the Info-DNA `seq` of the data object with the number `line_number` (as sent by `encode_file`, i.e., its line id + 1) to `info_dna_file` in the format `output_format`, i.e., as a fasta record captioned by `info_dna_caption` or as a binary record with the id `info_dna_id`.
+/// `is_first_entry` denotes whether or not `info_dna_file` is empty.
 #[inline(always)]
-fn dg_error(dg: f32) -> f32 {
-    let err = 1_f32 / (1_f32 + f32::exp(dg + 4_f32));
-    return if err.is_normal() {
-        err
+fn write_info_dna(info_dna_file: &mut impl Write, seq: &Arc<BaseSequence>, line_number: usize, output_format: usize, is_first_entry: bool) {
+    if output_format == OUTPUT_FORMAT_BINARY {
+        BaseSequence::append_to_binary_file_with_id_arc(info_dna_file, seq, info_dna_id(line_number) as u32);
     }
     else {
-        0_f32
+        BaseSequence::append_to_fasta_file_with_caption_arc(info_dna_file, seq, info_dna_caption(line_number).as_str(), is_first_entry);
+    }
+}
+
+/// Returns the id of the Info-DNA of the data object with the number `line_number` (as sent by `encode_file`, i.e., starting at 1). The id is `line_number`, i.e., the Info-DNA of the first line has the id 1.
+#[inline(always)]
+fn info_dna_id(line_number: usize) -> usize {
+    line_number
+}
+
+/// Returns the number of the data object (as sent by `encode_file`) whose Info-DNA has the id `id`, i.e., the inverse of `info_dna_id`. Returns None for the id 0, which belongs to no data object.
+#[inline(always)]
+fn line_number_from_id(id: usize) -> Option<usize> {
+    Some(id).filter(|&id| id >= 1_usize)
+}
+
+/// Returns the fasta caption of the Info-DNA of the data object with the number `line_number` (as sent by `encode_file`), i.e., its id (see `info_dna_id`).
+#[inline(always)]
+fn info_dna_caption(line_number: usize) -> String {
+    format!(">{}", info_dna_id(line_number))
+}
+
+/// Returns the number of the data object (as sent by `encode_file`) whose Info-DNA has the fasta caption `caption` without the leading ">", i.e., the inverse of `info_dna_caption`.
+#[inline(always)]
+fn line_number_from_caption(caption: &str) -> Option<usize> {
+    line_number_from_id(caption.trim().parse::<usize>().ok()?)
+}
+
+/// Writes the Info-DNAs `done` as (line id, Info-DNA) flanked by `adapters` to the checkpoint `checkpoint_path` in the binary output format, i.e., with their ids (see `info_dna_id`), such that `read_resumable_info_dnas` can resume them.
+/// The checkpoint is written to a temporary file first, which then replaces `checkpoint_path`. Hence, a crash while writing leaves the previous checkpoint intact. A checkpoint that cannot be written is reported, but does not stop the run.
+fn write_checkpoint(checkpoint_path: &str, done: &[(usize, Arc<BaseSequence>)], adapters: &Adapters) {
+    let temp_path = format!("{}{}", checkpoint_path, TEMP_EXTENSION);
+    let written = File::create(temp_path.as_str())
+        .and_then(|mut file| file.write_all(&checkpoint_records(done, adapters)).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(temp_path.as_str(), checkpoint_path));
+    if let Err(e) = written {
+        println!("\nWARNING: failed to write the checkpoint {}: {}", checkpoint_path, e);
+    }
+}
+
+/// Appends the Info-DNAs `done[from..]` that were encoded since the last checkpoint to the checkpoint `checkpoint_path` written by `write_checkpoint`, i.e., only the new Info-DNAs are written.
+/// A crash while appending leaves a truncated record at the end of the checkpoint, which `read_resumable_info_dnas` skips. If appending fails, the whole checkpoint is rewritten by `write_checkpoint`, so that no partial record remains between the Info-DNAs of later checkpoints.
+fn append_to_checkpoint(checkpoint_path: &str, done: &[(usize, Arc<BaseSequence>)], from: usize, adapters: &Adapters) {
+    let appended = OpenOptions::new().append(true).open(checkpoint_path)
+        .and_then(|mut file| file.write_all(&checkpoint_records(&done[from..], adapters)).and_then(|_| file.sync_all()));
+    if appended.is_err() {
+        write_checkpoint(checkpoint_path, done, adapters);
+    }
+}
+
+/// Returns the records of the Info-DNAs `done` as (line id, Info-DNA) flanked by `adapters` in the binary output format with their ids (see `write_checkpoint`).
+fn checkpoint_records(done: &[(usize, Arc<BaseSequence>)], adapters: &Adapters) -> Vec<u8> {
+    let mut records = Vec::new();
+    for (line_id, seq) in done.iter() {
+        BaseSequence::append_to_binary_file_with_id_arc(&mut records, &adapters.wrap(seq), info_dna_id(line_id + 1_usize) as u32);
     }
+    records
 }
 
-/// The function that encodes a single data object.
+/// Reads the Info-DNAs of an interrupted run from the file `info_dna_path` and returns those that can be resumed as (line id, Info-DNA) in the order of the file.
+/// An Info-DNA is only resumed if its caption or id belongs to a line (see `info_dna_caption` and `info_dna_id`) and it decodes to the data object of this line. Hence, a truncated Info-DNA, e.g., the last one written before the run was interrupted, is encoded again.
 ///
 /// # Arguments
 ///
-/// * `encoding_mode` - The encoding mode.
-/// * `dist_pool` - The thread pool for parallelizing distance checks.
-/// * `line` - The data object that will be encoded. line.0 is the id of that object, and line.1 contains the data object.
-/// * `raptor_cloned` - The RQ object used to encode the data object.
-/// * `encoded_seqs_lsh` - The LSH instance for Info-DNAs.
-/// * `probes_lsh` - The LSH instance for probes.
-/// * `seqs` - The vector containing the encoded Info-DNAs so far.
-/// * `probes` - The vector containing the probes.
-/// * `min_dist_to_probes` - The minimum distance required of an Info-DNA to a probe.
-/// * `min_dist_to_seqs` - The minimum distance required of an Info-DNA to another Info-DNA.
-/// * `sender` - The channel's sender that is used to send the encoding result to.
-/// * `packets_per_block` - The number of packets initially generated by RQ.
-/// * `overhead` - The overhead ε for RQ.
-/// * `max_hp_len` - The maximum allowed length of a homopolymer.
-/// * `dg_client` - The client object for communicating with the dg server.
-#[inline(always)]
-fn encode_file(encoding_mode: usize,
-               dist_pool: Arc<RwLock<ThreadPool>>,
-               line: (usize, Arc<Vec<u8>>),
-               raptor_cloned: Arc<RaptorQ>,
-               encoded_seqs_lsh: Arc<RwLock<SafeCell<LSH>>>,
-               probes_lsh: Arc<SafeCell<LSH>>,
-               seqs: Arc<RwLock<Vec<Arc<BaseSequence>>>>,
-               probes: Arc<SafeCell<Vec<Arc<BaseSequence>>>>,
-               min_dist_to_probes: f64,
-               min_dist_to_seqs: f64,
-               sender: Sender<(usize, Arc<BaseSequence>, usize, usize, u128, u128, u128)>,
-               packets_per_block: usize,
-               overhead: usize,
-               max_hp_len: usize,
-               dg_client: Arc<Option<DGClient>>) {
-
-    let start_time = SystemTime::now();
-    let mut trails = 0_usize;
-    let mut result_seq = Arc::new(BaseSequence::empty());
-    let seqs_k = encoded_seqs_lsh.read().k();
-    let probes_k = probes_lsh.k();
-    let dist_pool_cloned = dist_pool.clone();
-
-    let gc_and_hp_check = |seq: &Arc<BaseSequence>| dna_rules::satisfy_gc_hp_rules(seq, max_hp_len); // A closure that checks GC, and HP
-    let dg_rule = |seq: &Arc<BaseSequence>| dg_error(dg_arc(seq, &dg_client)) <= DEFAULT_MAX_DG_ERROR; // A closure that checks the error via the dg server
-    let strand_func_lsh_mixed_modes = |seq: &Arc<BaseSequence>|
-        dna_rules::satisfy_gc_hp_rules(seq, max_hp_len)
-            && pooled_dist_check_set(&seq, probes_lsh.similar_seqs(seq), min_dist_to_probes, seqs_k, &dist_pool_cloned); // A closure that checks GC, HP, and the distance to the probes via LSH
-
-    let strand_func_naive_mode = |seq: &Arc<BaseSequence>| dna_rules::satisfy_gc_hp_rules(&seq, max_hp_len); // A closure that checks GC, and HP
-
-    let mut rq_time_total = Duration::new(0_u64, 0_u32);
-    let mut dg_time_total = Duration::new(0_u64, 0_u32);
-
-    loop {
-        trails += 1_usize;
-        if encoding_mode == ENCODING_MODE_LSH {
-            let (encoded_seq, rq_time, dg_time) = raptor_cloned.encode_to_dna_with_rules(
-                line.1.as_slice(),
-                packets_per_block,
-                MAX_ENCODE_LOOPS,
-                overhead,
-                gc_and_hp_check,
-                strand_func_lsh_mixed_modes,
-                dg_rule);
-
-            dg_time_total += dg_time;
-            rq_time_total += rq_time;
-            let time_at_arrival = SystemTime::now();
-            // check if we missed checking a sequence because of parallelism -> lock the Info-DNA's LSH (probes' LSH did not change because probes are static)
-            let mut write_lock = encoded_seqs_lsh.write();
-            if pooled_dist_check_set(&encoded_seq, write_lock.similar_seqs(&encoded_seq), min_dist_to_seqs, seqs_k, &dist_pool) {
-                write_lock.insert(&encoded_seq);
-                result_seq = encoded_seq;
-                rq_time_total += SystemTime::now().duration_since(time_at_arrival).unwrap();
-                break;
-            }
-        }
-        else if encoding_mode == ENCODING_MODE_MIXED {
-            let (encoded_seq, rq_time, dg_time) = raptor_cloned.encode_to_dna_with_rules(
-                line.1.as_slice(),
-                packets_per_block,
-                MAX_ENCODE_LOOPS,
-                overhead,
-                gc_and_hp_check,
-                strand_func_lsh_mixed_modes,
-                dg_rule);
-
-            dg_time_total += dg_time;
-            rq_time_total += rq_time;
-            let time_at_arrival = SystemTime::now();
-            let read_lock = seqs.read();
-            let len = read_lock.len();
-            // check if we missed checking a sequence because of parallelism -> lock the Info-DNA's vector (probes' LSH did not change because probes are static)
-            if pooled_dist_check(&encoded_seq, read_lock.as_slice(), min_dist_to_seqs, seqs_k, &dist_pool) {
-                drop(read_lock);
-                if is_inserted_consistent(len, seqs_k, min_dist_to_seqs, seqs.clone(), &encoded_seq, &dist_pool) {
-                    result_seq = encoded_seq;
-                    rq_time_total += SystemTime::now().duration_since(time_at_arrival).unwrap();
-                    break;
-                }
-            }
+/// * `info_dna_path` - The Info-DNA's file path of the interrupted run.
+/// * `output_format` - The format of `info_dna_path` represented as a number (0=FASTA, 1=BINARY).
+/// * `lines` - A vector with the data objects to encode.
+/// * `raptor` - The RQ configuration used to decode the Info-DNAs, i.e., the same as the one of the interrupted run.
+/// * `adapters` - The adapters of the interrupted run. They are stripped from the Info-DNAs before decoding, and an Info-DNA without them is discarded.
+fn read_resumable_info_dnas(info_dna_path: &str, output_format: usize, lines: &[Arc<Vec<u8>>], raptor: &RaptorQ, adapters: &Adapters) -> Vec<(usize, Arc<BaseSequence>)> {
+    let records = if output_format == OUTPUT_FORMAT_BINARY {
+        BaseSequence::read_binary_with_ids_arc(info_dna_path).into_iter().map(|(id, seq)| (line_number_from_id(id as usize), seq)).collect::<Vec<_>>()
+    }
+    else {
+        BaseSequence::read_fasta_with_captions_arc(info_dna_path).into_iter().map(|(caption, seq)| (line_number_from_caption(caption.as_str()), seq)).collect::<Vec<_>>()
+    };
+    let mut line_ids = HashSet::with_capacity(records.len());
+    let resumed = records.iter().filter_map(|(line_number, seq)| {
+        let line_id = line_number.filter(|&number| number >= 1_usize && number <= lines.len())? - 1_usize;
+        let seq = adapters.strip(seq)?;
+        if line_ids.contains(&line_id) || raptor.decode_from_dna(&seq).filter(|data| data == lines[line_id].as_ref()).is_none() {
+            return None;
         }
-        else {
-            let (encoded_seq, rq_time, dg_time) = raptor_cloned.encode_to_dna_with_rules(
-                line.1.as_slice(),
-                packets_per_block,
-                MAX_ENCODE_LOOPS,
-                overhead,
-                gc_and_hp_check,
-                strand_func_naive_mode,
-                dg_rule);
-
-            dg_time_total += dg_time;
-            rq_time_total += rq_time;
-            let time_at_arrival = SystemTime::now();
-            let read_lock = seqs.read();
-            let len = read_lock.len();
-            // check if we missed checking a sequence because of parallelism -> lock the Info-DNA's and probes' vectors
-            if pooled_dist_check(&encoded_seq, read_lock.as_slice(), min_dist_to_seqs, seqs_k, &dist_pool)
-            && pooled_dist_check(&encoded_seq, probes.as_slice(), min_dist_to_probes, probes_k, &dist_pool) {
-                drop(read_lock);
-                if is_inserted_consistent(len, seqs_k, min_dist_to_seqs, seqs.clone(), &encoded_seq, &dist_pool) {
-                    result_seq = encoded_seq;
-                    rq_time_total += SystemTime::now().duration_since(time_at_arrival).unwrap();
-                    break;
-                }
-            }
+        line_ids.insert(line_id);
+        Some((line_id, Arc::new(seq)))
+    }).collect::<Vec<_>>();
+    println!("resuming {} of {} Info-DNAs from {} ({} discarded)", resumed.len(), lines.len(), info_dna_path, records.len() - resumed.len());
+    resumed
+}
+
+/// Prints the estimated number of packets and DNA bases of the Info-DNA of every data object in `lines` as a table grouped by the Info-DNA's length, followed by the totals (see `RaptorQ::estimate_encoding`).
+/// Data objects that cannot be encoded, i.e., empty ones and those exceeding `raptor::MAX_DATA_LEN` bytes, are listed instead of estimated.
+///
+/// # Arguments
+///
+/// * `lines` - A vector with the data objects to estimate.
+/// * `raptor` - The RQ configuration used to encode the data objects.
+/// * `overhead` - The overhead ε for RQ. Ignored if `target_redundancy` is set.
+/// * `target_redundancy` - The ratio of packets to source symbols from which the overhead ε of every data object is derived (None = `overhead` is used).
+/// * `adapters` - The adapters that flank every Info-DNA. Their length is included in the estimated length.
+fn print_dry_run(lines: &[Arc<Vec<u8>>], raptor: &RaptorQ, overhead: usize, target_redundancy: Option<f64>, adapters: &Adapters) {
+    let mut by_len = BTreeMap::new(); // Info-DNA length -> (number of Info-DNAs, packets per Info-DNA, min. bytes, max. bytes)
+    let mut invalid = Vec::new();
+    for (line_id, line) in lines.iter().enumerate() {
+        if line.is_empty() || line.len() > raptor::MAX_DATA_LEN {
+            invalid.push((line_id + 1_usize, line.len()));
+            continue;
         }
+        let overhead = target_redundancy.map_or(overhead, |redundancy| raptor.overhead_for_redundancy(line.len(), redundancy));
+        let (packets, len) = raptor.estimate_encoding(line.len(), overhead);
+        let entry = by_len.entry(len + adapters.len()).or_insert((0_usize, packets, usize::MAX, 0_usize));
+        entry.0 += 1_usize;
+        entry.2 = entry.2.min(line.len());
+        entry.3 = entry.3.max(line.len());
+    }
+
+    println!("dry run: estimated Info-DNAs (no constraints checked)");
+    println!("{:>10} {:>10} {:>10} {:>16}", "length", "strands", "packets", "bytes");
+    for (len, (strands, packets, min_bytes, max_bytes)) in by_len.iter() {
+        println!("{:>10} {:>10} {:>10} {:>16}", len, strands, packets, format!("{}-{}", min_bytes, max_bytes));
+    }
+    println!("------------------------------------------------------");
+    println!("strands                = {}", by_len.values().map(|entry| entry.0).sum::<usize>());
+    println!("packets                = {}", by_len.values().map(|entry| entry.0 * entry.1).sum::<usize>());
+    println!("bases                  = {}", by_len.iter().map(|(len, entry)| len * entry.0).sum::<usize>());
+    println!("bytes                  = {}", lines.iter().map(|line| line.len()).sum::<usize>());
+    if !invalid.is_empty() {
+        println!("WARNING: {} lines cannot be encoded (empty or more than {} bytes) as (line, bytes): {:?}", invalid.len(), raptor::MAX_DATA_LEN, invalid);
+    }
+}
+
+/// Overwrites the current console line with the number of encoded data objects `done` out of `total`, the progress in %, the average time per data object, the elapsed time, and the estimated remaining time.
+fn print_progress(done: usize, total: usize, elapsed: Duration) {
+    let avg_millis = elapsed.as_millis() as f64 / done as f64;
+    let eta = Duration::from_millis((avg_millis * (total - done) as f64) as u64);
+    print!("\r[{}/{}] {:.1}% | {:.1} ms/line | elapsed {} | ETA {}   ", done, total, 100_f64 * done as f64 / total as f64, avg_millis, format_hms(elapsed), format_hms(eta));
+    let _ = stdout().flush();
+}
+
+/// Formats `duration` as hours, minutes, and seconds, i.e., "hh:mm:ss".
+fn format_hms(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Prints the bucket statistics of `lsh` and the distribution of the candidate set sizes of up to `LSH_STATS_SAMPLE_SIZE` evenly spaced queries of `seqs` to the console.
+fn print_lsh_stats(name: &str, lsh: &LSH, seqs: &[Arc<BaseSequence>]) {
+    println!("------------------------------------------------------");
+    println!("LSH statistics of {}", name);
+    println!("{}", lsh.stats());
+    let sample = seqs.iter().step_by(seqs.len().div_ceil(LSH_STATS_SAMPLE_SIZE).max(1_usize)).cloned().collect::<Vec<_>>();
+    let mut sizes = lsh.candidate_set_sizes(&sample).into_iter().map(|size| size as f64).collect::<Vec<_>>();
+    print_distribution(format!("candidate set sizes of {}", name).as_str(), &mut sizes, DISTRIBUTION_HISTOGRAM_BINS);
+    println!("------------------------------------------------------");
+}
+
+/// Prints the count, min, mean, max, percentiles, and a histogram with `bins` equally sized bins of `values` to the console.
+fn print_distribution(name: &str, values: &mut [f64], bins: usize) {
+    if let Some((min, max)) = print_summary(name, values) {
+        print_histogram(values, min, (max - min) / bins as f64, bins);
+    }
+}
+
+/// Prints the count, min, mean, max, percentiles, and a histogram of `values` to the console. The bins of the histogram have the width `width` and start at multiples of it, e.g., [0.48, 0.50) for a width of 0.02.
+fn print_distribution_with_bin_width(name: &str, values: &mut [f64], width: f64) {
+    if let Some((min, max)) = print_summary(name, values) {
+        let from = (min / width).floor() * width;
+        print_histogram(values, from, width, ((max - from) / width + HISTOGRAM_BIN_EPSILON) as usize + 1_usize);
+    }
+}
+
+/// Sorts `values` and prints their count, min, mean, max, and percentiles to the console. Returns the min and the max, or None if `values` is empty.
+fn print_summary(name: &str, values: &mut [f64]) -> Option<(f64, f64)> {
+    println!("------------------------------------------------------");
+    println!("distribution of {} ({} values)", name, values.len());
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let min = values[0];
+    let max = values[values.len() - 1];
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let percentile = |p: f64| percentile_of_sorted(values, p);
+    println!("min = {}, mean = {}, max = {}", min, mean, max);
+    println!("p5 = {}, p25 = {}, p50 = {}, p75 = {}, p95 = {}", percentile(0.05), percentile(0.25), percentile(0.5), percentile(0.75), percentile(0.95));
+    Some((min, max))
+}
+
+/// Returns the `p`-th percentile (0 <= `p` <= 1) of the non-empty and sorted `values`, i.e., the value at the nearest rank.
+fn percentile_of_sorted(values: &[f64], p: f64) -> f64 {
+    values[((p * (values.len() - 1) as f64).round() as usize).min(values.len() - 1)]
+}
+
+/// Counts `values` in `bins` bins of the width `width`, starting at `from`. Values beyond the last bin are counted in it, and every value is counted in the first bin if `width` is 0.
+fn histogram_counts(values: &[f64], from: f64, width: f64, bins: usize) -> Vec<usize> {
+    let mut counts = vec![0_usize; bins];
+    for v in values.iter() {
+        let bin = if width > 0_f64 { (((v - from) / width + HISTOGRAM_BIN_EPSILON) as usize).min(bins - 1) } else { 0_usize };
+        counts[bin] += 1;
     }
+    counts
+}
 
-    sender.send((
-        line.0, // the line's id
-        result_seq, // the encoded Info-DNA for this data object (line.1)
-        trails, // number of loops that were needed to successfully encode the data object
-        line.1.len(), // the number of bytes of the data objects
-        rq_time_total.as_millis(), // the total time RQ needed to encode the data object
-        dg_time_total.as_millis(),// the total time the dg server needed to return the dg energy for the suggested sequences
-        SystemTime::now().duration_since(start_time).unwrap().as_millis())); // the total time needed to finish encoding the data object
+/// Prints a histogram of `values` with `bins` bins of the width `width`, starting at `from`, to the console. Values beyond the last bin are counted in it.
+fn print_histogram(values: &[f64], from: f64, width: f64, bins: usize) {
+    let counts = histogram_counts(values, from, width, bins);
+    let max_count = *counts.iter().max().unwrap();
+    for (bin, count) in counts.iter().enumerate() {
+        let from = from + bin as f64 * width;
+        println!("[{:>10.3}, {:>10.3}{} {:>8} {}", from, from + width, if bin == bins - 1 { "]" } else { ")" }, count, "#".repeat(50 * count / max_count));
+    }
 }
 
-/// Inserts `encoded_seq` into `seqs` if there is no Info-DNA sequence with a distance lower that `min_dist_to_seqs`.
+/// The function that reports the current encoding state of a data object to the json file as a single line (newline-delimited json). Non-finite numbers and the dg time without a dg server are written as null.
 #[inline(always)]
-fn is_inserted_consistent(len: usize, k: usize, min_dist_to_seqs: f64, seqs: Arc<RwLock<Vec<Arc<BaseSequence>>>>, encoded_seq: &Arc<BaseSequence>, dist_pool: &Arc<RwLock<ThreadPool>>) -> bool {
-    let mut write_lock = seqs.write();
-    let diff = write_lock.len() - len;
-    if diff == 0_usize {
-        write_lock.push(encoded_seq.clone());
-        return true;
+fn report_to_json(json: &mut Option<File>, encoding_mode_string: &str, use_dg_server: bool, min_dist_to_probes: f64, min_dist_to_seqs: f64, overhead: usize, max_hp_len: usize, progress: f64, line_id: usize, done_id: usize, trails: usize, rq_time: u128, dg_time: Option<u128>, total_time: u128, file_size: usize, total_bytes: usize, seq_len: usize, dg: Option<f32>, packets_used: usize, achieved_overhead: usize, rejections: &Rejections, time_till_now: u128) {
+    let number = |v: f64| if v.is_finite() { v.to_string() } else { String::from("null") };
+    let row = format!(
+        "{{\"progress\":{},\"line_id\":{},\"done_id\":{},\"trials\":{},\"file_size\":{},\"total_bytes\":{},\"length\":{},\"packets_used\":{},\"achieved_overhead\":{},\"dg\":{},\"dg_skipped\":{},\"rejections\":{{{}}},\"time_ms\":{{\"rq\":{},\"sec_struct\":{},\"total\":{},\"till_now\":{}}},\"parameters\":{{\"encoding_mode\":\"{}\",\"use_dg_server\":{},\"overhead\":{},\"max_hp_len\":{},\"min_dist_to_probes\":{},\"min_dist_to_seqs\":{}}}}}\n",
+        number(progress), line_id, done_id, trails, file_size, total_bytes, seq_len, packets_used, achieved_overhead, dg.map_or(String::from("null"), |dg| number(dg as f64)), dg.is_none() && dg_time.is_some(),
+        rejections.iter().map(|(reason, count)| format!("\"{}\":{}", reason.name(), count)).collect::<Vec<_>>().join(","),
+        rq_time, dg_time.map_or(String::from("null"), |dg_time| dg_time.to_string()), total_time, time_till_now,
+        encoding_mode_string, use_dg_server, overhead, max_hp_len, number(min_dist_to_probes), number(min_dist_to_seqs));
+
+    json.as_ref().unwrap().write_all(row.as_bytes()).unwrap();
+}
+
+/// A single row of the csv report. The order of the fields in `ReportRow::fields` must match `ReportRow::COLUMNS`, which is enforced by the length of the returned array.
+#[derive(Clone, Copy)]
+struct ReportRow<'a> {
+    progress: f64,              // progress in %
+    line_id: usize,             // 1-based line id, i.e., the caption of the Info-DNA
+    done_id: usize,             // done id
+    trials: usize,              // trys
+    time: Option<u128>,         // the time in ms spent for `time_for` (None if not applicable, i.e., the dg time without a dg server)
+    time_for: &'a str,          // time type
+    file_size: usize,           // file size
+    total_bytes: usize,         // total bytes
+    overhead: usize,            // overhead (the effective one of the data object if target_redundancy is set)
+    length: usize,              // length
+    max_hp_len: usize,          // max hp length
+    min_dist_to_probes: f64,    // min dist to probes
+    min_dist_to_seqs: f64,      // min dist to seqs
+    encoding_mode: &'a str,     // encoding mode
+    use_dg_server: bool,        // use_dg_server
+    time_till_now: u128,        // total_time_till_now
+    packets_used: usize,        // the number of RQ packets in the Info-DNA
+    achieved_overhead: usize    // the number of packets beyond the source symbols
+}
+
+impl<'a> ReportRow<'a> {
+    /// The column headers of the csv report.
+    const COLUMNS: [&'static str; 18] = ["Progress(%)", "Line Id", "Done Id", "Trials", "Time(ms)", "Time For", "File Size", "Total Bytes", "Overhead", "Length", "Max HP Length", "Min. Dist To Probes", "Min. Dist To Seqs", "Encoding Mode", "Use DG Server", "Total Time", "Packets Used", "Achieved Overhead"];
+
+    /// Returns the header line of the csv report.
+    fn header() -> String {
+        Self::COLUMNS.join(DEFAULT_CSV_DELIMITER)
     }
-    else {
-        if pooled_dist_check(encoded_seq, &write_lock[len..], min_dist_to_seqs, k, dist_pool) {
-            write_lock.push(encoded_seq.clone());
-            return true;
+
+    /// Returns the values of this row in the order of `ReportRow::COLUMNS`.
+    fn fields(&self) -> [String; ReportRow::COLUMNS.len()] {
+        [
+            self.progress.to_string(),
+            self.line_id.to_string(),
+            self.done_id.to_string(),
+            self.trials.to_string(),
+            self.time.map_or(String::from("N/A"), |time| time.to_string()),
+            self.time_for.to_string(),
+            self.file_size.to_string(),
+            self.total_bytes.to_string(),
+            self.overhead.to_string(),
+            self.length.to_string(),
+            self.max_hp_len.to_string(),
+            self.min_dist_to_probes.to_string(),
+            self.min_dist_to_seqs.to_string(),
+            self.encoding_mode.to_string(),
+            self.use_dg_server.to_string(),
+            self.time_till_now.to_string(),
+            self.packets_used.to_string(),
+            self.achieved_overhead.to_string()
+        ]
+    }
+
+    /// Returns this row as a csv line, including the leading new line.
+    fn to_csv(self) -> String {
+        let mut row = String::from(DEFAULT_CSV_NEW_LINE);
+        row.push_str(self.fields().join(DEFAULT_CSV_DELIMITER).as_str());
+        row
+    }
+}
+
+/// The funtion that reports the current encoding state to the csv file.
+#[inline(always)]
+fn report_to_csv(csv: &mut Option<File>, rows: &[ReportRow]) {
+    let row: String = rows.iter().map(|row| row.to_csv()).collect();
+    csv.as_ref().unwrap().write_all(row.as_bytes());
+}
+
+
+/// The constant DNA sequences that flank every Info-DNA in the Info-DNA file, e.g., for attaching primers.
+/// The adapters are neither checked against the DNA constraints nor part of the distance checks, since they are the same for every Info-DNA.
+struct Adapters {
+    prefix: BaseSequence, // the sequence written before every Info-DNA
+    suffix: BaseSequence  // the sequence written after every Info-DNA
+}
+
+impl Adapters {
+    /// Creates the adapters by parsing the DNA strings `prefix` and `suffix`. Panics if one of them contains a character that is not a DNA base.
+    fn from_str(prefix: &str, suffix: &str) -> Self {
+        let parse = |name: &str, adapter: &str| BaseSequence::try_from_str(adapter).unwrap_or_else(|e| panic!("cannot parse {}: {}", name, e));
+        Self {
+            prefix: parse("prefix_adapter", prefix),
+            suffix: parse("suffix_adapter", suffix)
+        }
+    }
+
+    /// Returns the total number of DNA bases of the adapters.
+    fn len(&self) -> usize {
+        self.prefix.len() + self.suffix.len()
+    }
+
+    /// Returns `seq` flanked by the adapters.
+    fn wrap(&self, seq: &Arc<BaseSequence>) -> Arc<BaseSequence> {
+        if self.len() == 0_usize {
+            return seq.clone();
         }
+        let mut wrapped = BaseSequence::new(Vec::with_capacity(seq.len() + self.len()));
+        wrapped.append_seq(&self.prefix);
+        wrapped.append_seq(seq);
+        wrapped.append_seq(&self.suffix);
+        Arc::new(wrapped)
     }
 
-    false
+    /// Returns `seq` without the adapters, i.e., the inverse of `wrap`. Returns None if `seq` is not flanked by the adapters.
+    fn strip(&self, seq: &BaseSequence) -> Option<BaseSequence> {
+        let bases = seq.as_slice();
+        if bases.len() < self.len() || !bases.starts_with(self.prefix.as_slice()) || !bases.ends_with(self.suffix.as_slice()) {
+            return None;
+        }
+        Some(BaseSequence::from_slice(&bases[self.prefix.len()..bases.len() - self.suffix.len()]))
+    }
 }
 
 // Converts the encoding mode's string into a number, i.e., (0="LSH", 1="MIXED", 2="NAIVE").
@@ -665,26 +1183,85 @@ pub fn extract_encoding_mode(arg: &str) -> usize {
     }
 }
 
+// Converts the output format's string into a number, i.e., (0="FASTA", 1="BINARY").
+#[inline(always)]
+pub fn extract_output_format(arg: &str) -> usize {
+    if arg.eq_ignore_ascii_case("fasta") {
+        OUTPUT_FORMAT_FASTA
+    }
+    else if arg.eq_ignore_ascii_case("binary") {
+        OUTPUT_FORMAT_BINARY
+    }
+    else {
+        panic!("cannot determine output format: {}", arg);
+    }
+}
 
-// The function that returns the received dg energy for a given sequence. Returns 0 if no dg server is set up.
+// Converts the report format's string into a number, i.e., (0="CSV", 1="JSON", 2="BOTH").
 #[inline(always)]
-pub fn dg_arc(seq: &Arc<BaseSequence>, dg_client: &Arc<Option<DGClient>>) -> f32 {
-    match dg_client.as_ref() {
-        None => 0_f32,
-        Some(client) => client.dg_arc(seq, DEFAULT_SECONDARY_STRUCT_TEMP)
+pub fn extract_report_format(arg: &str) -> usize {
+    if arg.eq_ignore_ascii_case("csv") {
+        REPORT_FORMAT_CSV
+    }
+    else if arg.eq_ignore_ascii_case("json") {
+        REPORT_FORMAT_JSON
+    }
+    else if arg.eq_ignore_ascii_case("both") {
+        REPORT_FORMAT_BOTH
+    }
+    else {
+        panic!("cannot determine report format: {}", arg);
     }
 }
 
-/// The function that reads the data objects into the program. Set `read_as_lines` to _true_ to interpret each line of `lines_path` as a data object. _false_ to read the file as follows: 4 bytes will be read (big endian) and converted to an integer _len_. The next _len_ bytes will be interpreted as a data object. RQPAP will loop until it finds the end of the file and report how many data objects it found. This is helpful when you consider encoding, e.g., compressed data objects that may contain the new line character "\n".
+// Determines the similarity backends for the probes and the Info-DNAs, respectively. "auto" derives them from the encoding mode (LSH=approx/approx, MIXED=approx/exact, NAIVE=exact/exact), while "exact" or "approx" overrides both.
+#[inline(always)]
+pub fn extract_backends(encoding_mode: usize, similarity_backend_str: &str) -> (usize, usize) {
+    if !similarity_backend_str.eq_ignore_ascii_case("auto") {
+        let backend = similarity::extract_similarity_backend(similarity_backend_str);
+        return (backend, backend);
+    }
+
+    if encoding_mode == ENCODING_MODE_LSH {
+        (SIMILARITY_BACKEND_APPROX, SIMILARITY_BACKEND_APPROX)
+    }
+    else if encoding_mode == ENCODING_MODE_MIXED {
+        (SIMILARITY_BACKEND_APPROX, SIMILARITY_BACKEND_EXACT)
+    }
+    else {
+        (SIMILARITY_BACKEND_EXACT, SIMILARITY_BACKEND_EXACT)
+    }
+}
+
+// Checks the LSH parameters `k`, `r`, and `b` of the options lsh_k_`name`, lsh_r_`name`, and lsh_b_`name` before any input is read, since `LSH::new` would only reject them after the probes are loaded.
+// Panics with the offending option if `b` is 0, `r` is not a multiple of `b`, or `k` exceeds `lsh::MAX_K` (only checked for the `LSH_BUCKETING_MINHASH` bucketing, which hashes k-mers).
+fn validate_lsh_params(name: &str, k: usize, r: usize, b: usize, lsh_bucketing: usize) {
+    if b == 0_usize {
+        panic!("lsh_b_{} must be at least 1", name);
+    }
+    if !r.is_multiple_of(b) {
+        panic!("lsh_r_{} ({}) must be a multiple of lsh_b_{} ({})", name, r, name, b);
+    }
+    if lsh_bucketing == lsh::LSH_BUCKETING_MINHASH && k > lsh::MAX_K {
+        panic!("lsh_k_{} ({}) must be at most {}", name, k, lsh::MAX_K);
+    }
+}
+
+/// The function that reads the data objects into the program from `lines_path`, or from stdin if `lines_path` is `LINES_PATH_STDIN`. Set `read_as_lines` to _true_ to interpret each line of `lines_path` as a data object. _false_ to read the file as follows: 4 bytes will be read (big endian) and converted to an integer _len_. The next _len_ bytes will be interpreted as a data object. RQPAP will loop until it finds the end of the file and report how many data objects it found. This is helpful when you consider encoding, e.g., compressed data objects that may contain the new line character "\n".
 #[inline(always)]
 fn read_lines_arc(lines_path: &str, read_as_lines: bool) -> Vec<Arc<Vec<u8>>> {
+    let source: Box<dyn Read> = if lines_path == LINES_PATH_STDIN {
+        Box::new(stdin())
+    }
+    else {
+        Box::new(OpenOptions::new().read(true).open(lines_path).unwrap())
+    };
     if read_as_lines {
-        let file = OpenOptions::new().read(true).open(lines_path).unwrap();
-        let reader = BufReader::new(file);
+        let reader = BufReader::new(source);
         reader.lines().map(|c| Arc::new(c.unwrap().into_bytes())).collect()
     }
     else {
-        let mut br = BufReader::new(OpenOptions::new().read(true).open(lines_path).unwrap());
+        let mut br = BufReader::new(source);
         let mut buff_size = [0_u8; 4];
         let mut lines = vec![];
         loop  {
@@ -705,6 +1282,51 @@ fn read_lines_arc(lines_path: &str, read_as_lines: bool) -> Vec<Arc<Vec<u8>>> {
     }
 }
 
+/// Returns the fasta files of probes listed in `probes_path`, i.e., the paths separated by `PROBES_PATH_SEPARATOR`, where a path with a wildcard (`*`, `?`, or `[`) is expanded into the files it matches in alphabetical order.
+/// A file listed more than once is returned only the first time. Panics if a pattern is invalid or matches no file, or if `probes_path` lists no file at all.
+fn probes_paths(probes_path: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    for path in probes_path.split(PROBES_PATH_SEPARATOR).map(|path| path.trim()).filter(|path| !path.is_empty()) {
+        if !path.contains(['*', '?', '[']) {
+            paths.push(path.to_string());
+            continue;
+        }
+        let matches = glob::glob(path).unwrap_or_else(|e| panic!("invalid pattern {} in probes_path: {}", path, e))
+            .filter_map(|entry| entry.ok())
+            .filter(|file| file.is_file())
+            .map(|file| file.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            panic!("the pattern {} in probes_path matches no file", path);
+        }
+        paths.extend(matches);
+    }
+    if paths.is_empty() {
+        panic!("probes_path lists no fasta file of probes");
+    }
+    let mut listed = HashSet::new();
+    paths.retain(|path| listed.insert(path.clone()));
+    paths
+}
+
+/// Reads the probes of the fasta files `paths` (see `probes_paths`) in their order. A probe that was already read from an earlier file is dropped, while the duplicates within a single file are kept.
+/// Prints the number of probes read from every file if there are several.
+fn read_probes(paths: &[String], max_iupac_expansions: usize) -> Vec<Arc<BaseSequence>> {
+    if paths.len() == 1 {
+        return BaseSequence::read_fasta_iupac_streaming(paths[0].as_str(), max_iupac_expansions).collect();
+    }
+    let mut probes = Vec::new();
+    let mut earlier_probes = HashSet::new();
+    for path in paths.iter() {
+        let file_probes = BaseSequence::read_fasta_iupac_streaming(path.as_str(), max_iupac_expansions).collect::<Vec<_>>();
+        let probes_before = probes.len();
+        probes.extend(file_probes.iter().filter(|probe| !earlier_probes.contains(*probe)).cloned());
+        println!("probes read from {} = {} ({} duplicates of earlier files dropped)", path, file_probes.len(), file_probes.len() - (probes.len() - probes_before));
+        earlier_probes.extend(file_probes);
+    }
+    probes
+}
+
 // The function that requires the user to approve with `y` followed by `enter` to start the encoding pipeline.
 fn approve_parameters() -> bool {
     let mut s= String::new();
@@ -720,151 +1342,491 @@ fn approve_parameters() -> bool {
 
     s.eq_ignore_ascii_case("y") || s.eq_ignore_ascii_case("1") || s.eq_ignore_ascii_case("yes") || s.eq_ignore_ascii_case("true")
 }
-/// A function that computes distances between `seq` and `candidates` (slice). Decides to parallelize the checks given candidates.len().
-#[inline(always)]
-fn pooled_dist_check(seq: &Arc<BaseSequence>, candidates: &[Arc<BaseSequence>], min: f64, k: usize, pool: &Arc<RwLock<ThreadPool>>) -> bool {
-    if candidates.len() < DISTANCE_CHECK_POOLING_TRIGGER {
-        for candidate in candidates.iter() {
-            if seq.jaccard_distance_arc(candidate, k) < min  {
-                return false;
-            }
-        }
-        return true
-    }
-    let is_dist_ok = Arc::new(parking_lot::RwLock::new(true));
-    let (tx, rx) = bounded(candidates.len());
-    let seq_arc = Arc::new(seq.clone());
-    let pool_lock = pool.write();
-    for candidate in candidates.iter() {
-        let is_dist_ok_cloned = is_dist_ok.clone();
-        let sender = tx.clone();
-        let s = seq_arc.clone();
-        let can = candidate.clone();
-        pool_lock.spawn(move|| {
-            if *is_dist_ok_cloned.read() {
-                sender.send(s.jaccard_distance_arc(&can, k));
-            }
-        });
-    }
-    for _ in 0..candidates.len() {
-        if rx.recv().unwrap() < min {
-            *is_dist_ok.write() = false;
-            return false
-        }
-    }
-
-    true
+/// Returns every recognized command line option as (name, default value, description).
+fn cli_options() -> Vec<(&'static str, String, &'static str)> {
+    vec![
+        ("lines_path", DEFAULT_LINES_PATH.to_string(), "file of data objects, each encoded to a single DNA fragment (- = stdin)"),
+        ("read_as_lines", DEFAULT_READ_AS_LINES.to_string(), "true to read each line as a data object, false to read length-prefixed (4 bytes, big endian) data objects"),
+        ("probes_path", DEFAULT_PROBES_PATH.to_string(), "fasta file(s) of probes, separated by commas (wildcards are expanded)"),
+        ("probes_lsh_cache", DEFAULT_PROBES_LSH_CACHE.to_string(), "file that caches the LSH instance of the probes (empty = no caching)"),
+        ("max_iupac_expansions", DEFAULT_MAX_IUPAC_EXPANSIONS.to_string(), "maximum number of sequences a degenerate probe may expand to (0 = IUPAC codes are not allowed)"),
+        ("encoding_mode", DEFAULT_ENCODING_MODE_STR.to_string(), "lsh, mixed, or naive"),
+        ("similarity_backend", DEFAULT_SIMILARITY_BACKEND_STR.to_string(), "auto, exact, or approx"),
+        ("distance_metric", DEFAULT_DISTANCE_METRIC_STR.to_string(), "jaccard, weighted_jaccard, edit, or hamming"),
+        ("info_dna_path", DEFAULT_INFO_DNA_PATH.to_string(), "fasta file to which the Info-DNAs are written"),
+        ("output_format", DEFAULT_OUTPUT_FORMAT_STR.to_string(), "fasta or binary (length-prefixed records of 2-bit packed bases)"),
+        ("prefix_adapter", DEFAULT_PREFIX_ADAPTER.to_string(), "constant DNA sequence written before every Info-DNA (not checked against the constraints)"),
+        ("suffix_adapter", DEFAULT_SUFFIX_ADAPTER.to_string(), "constant DNA sequence written after every Info-DNA (not checked against the constraints)"),
+        ("report", DEFAULT_REPORT.to_string(), "true to write encoding stats to the report"),
+        ("report_path", DEFAULT_REPORT_PATH.to_string(), "csv file to which encoding stats are written"),
+        ("report_format", DEFAULT_REPORT_FORMAT_STR.to_string(), "csv, json, or both"),
+        ("report_json_path", DEFAULT_REPORT_JSON_PATH.to_string(), "newline-delimited json file to which encoding stats are written"),
+        ("append_to_report", DEFAULT_APPEND_TO_REPORT.to_string(), "true to append to an existing report, false to overwrite it"),
+        ("base_mapping", DEFAULT_BASE_MAPPING_STR.to_string(), "the bases of the 2-bit values 00, 01, 10, and 11"),
+        ("rotate_mapping", DEFAULT_ROTATE_MAPPING.to_string(), "true to rotate the 2-bit values by the position of the base before mapping"),
+        ("overhead", DEFAULT_OVERHEAD.to_string(), "RQ overhead (epsilon)"),
+        ("target_redundancy", String::new(), "ratio of packets to source symbols per line, e.g., 1.2 (overrides overhead, empty = not set)"),
+        ("rq_symbol_size", DEFAULT_RQ_SYMBOL_SIZE.to_string(), "RQ symbol size in bytes (a multiple of rq_alignment)"),
+        ("rq_source_blocks", DEFAULT_RQ_SOURCE_BLOCKS.to_string(), "number of RQ source blocks (only 1 is supported)"),
+        ("rq_sub_blocks", DEFAULT_RQ_SUB_BLOCKS.to_string(), "number of RQ sub-blocks"),
+        ("rq_alignment", DEFAULT_RQ_ALIGNMENT.to_string(), "RQ symbol alignment in bytes"),
+        ("max_hp_len", DEFAULT_MAX_HP_LEN.to_string(), "maximum homopolymer length"),
+        ("max_hp_a", DEFAULT_MAX_HP_PER_BASE.to_string(), "maximum length of an A-homopolymer (0 = max_hp_len)"),
+        ("max_hp_c", DEFAULT_MAX_HP_PER_BASE.to_string(), "maximum length of a C-homopolymer (0 = max_hp_len)"),
+        ("max_hp_g", DEFAULT_MAX_HP_PER_BASE.to_string(), "maximum length of a G-homopolymer (0 = max_hp_len)"),
+        ("max_hp_t", DEFAULT_MAX_HP_PER_BASE.to_string(), "maximum length of a T-homopolymer (0 = max_hp_len)"),
+        ("min_gc", DEFAULT_MIN_GC.to_string(), "minimum GC content"),
+        ("max_gc", DEFAULT_MAX_GC.to_string(), "maximum GC content"),
+        ("gc_window", DEFAULT_GC_WINDOW.to_string(), "window size for the local GC content (0 = disabled)"),
+        ("min_window_gc", DEFAULT_MIN_WINDOW_GC.to_string(), "minimum GC content of every window"),
+        ("max_window_gc", DEFAULT_MAX_WINDOW_GC.to_string(), "maximum GC content of every window"),
+        ("forbidden_motifs", DEFAULT_FORBIDDEN_MOTIFS.to_string(), "motifs separated by \";\" that must not occur on either strand"),
+        ("min_tm", DEFAULT_MIN_TM.to_string(), "minimum melting temperature in °C"),
+        ("max_tm", DEFAULT_MAX_TM.to_string(), "maximum melting temperature in °C"),
+        ("min_entropy", DEFAULT_MIN_ENTROPY.to_string(), "minimum dinucleotide entropy in bits (0 = disabled)"),
+        ("max_hairpin_stem", DEFAULT_MAX_HAIRPIN_STEM.to_string(), "maximum length of a local hairpin stem (0 = disabled)"),
+        ("hairpin_min_loop", DEFAULT_HAIRPIN_MIN_LOOP.to_string(), "minimum loop length of a local hairpin"),
+        ("min_dist_to_probes", DEFAULT_MIN_DIST_TO_PROBES.to_string(), "minimum distance of an Info-DNA to the probes"),
+        ("min_dist_to_seqs", DEFAULT_MIN_DIST_TO_SEQS.to_string(), "minimum distance of an Info-DNA to the other Info-DNAs"),
+        ("min_dist_to_seqs_decay_start", DEFAULT_MIN_DIST_TO_SEQS_DECAY_START.to_string(), "number of Info-DNAs after which min_dist_to_seqs decays linearly"),
+        ("min_dist_to_seqs_decay_end", DEFAULT_MIN_DIST_TO_SEQS_DECAY_END.to_string(), "number of Info-DNAs at which min_dist_to_seqs reaches min_dist_to_seqs_floor (0 = no decay)"),
+        ("min_dist_to_seqs_floor", DEFAULT_MIN_DIST_TO_SEQS_FLOOR.to_string(), "smallest min_dist_to_seqs the decay reaches"),
+        ("check_revcomp", DEFAULT_CHECK_REVCOMP.to_string(), "true to check the reverse complement of an Info-DNA, too"),
+        ("lsh_k_probes", DEFAULT_LSH_K_PROBES.to_string(), "k-mer length of the probes' LSH instance"),
+        ("lsh_r_probes", DEFAULT_LSH_R_PROBES.to_string(), "number of hash functions of the probes' LSH instance"),
+        ("lsh_b_probes", DEFAULT_LSH_B_PROBES.to_string(), "number of bands of the probes' LSH instance"),
+        ("lsh_k_seqs", DEFAULT_LSH_K_SEQS.to_string(), "k-mer length of the Info-DNAs' LSH instance"),
+        ("lsh_r_seqs", DEFAULT_LSH_R_SEQS.to_string(), "number of hash functions of the Info-DNAs' LSH instance"),
+        ("lsh_b_seqs", DEFAULT_LSH_B_SEQS.to_string(), "number of bands of the Info-DNAs' LSH instance"),
+        ("lsh_permutation", DEFAULT_LSH_PERMUTATION_STR.to_string(), "pseudo or exact (a shuffled table of 4^k entries per hash function)"),
+        ("lsh_bucketing", DEFAULT_LSH_BUCKETING_STR.to_string(), "minhash (Jaccard of the k-mers) or cgk (edit distance via the CGK embedding)"),
+        ("lsh_cgk_len", DEFAULT_LSH_CGK_LEN.to_string(), "length of the sequences embedded by the cgk bucketing"),
+        ("canonical_k_mers", DEFAULT_CANONICAL_K_MERS.to_string(), "true to use canonical k-mers for LSH and the Jaccard distance"),
+        ("minhash_prefilter_margin", DEFAULT_MINHASH_PREFILTER_MARGIN.to_string(), "skip the exact Jaccard distance of LSH candidates whose estimate exceeds the minimum by this margin (inf = disabled)"),
+        ("lsh_stats", DEFAULT_LSH_STATS.to_string(), "true to print the bucket statistics of the probes' LSH instance after building it"),
+        ("use_dg_server", DEFAULT_USE_DG.to_string(), "true to check the secondary structure with the dg server"),
+        ("dg_host", DEFAULT_DG_HOST.to_string(), "hostname or IP address of the dg server"),
+        ("dg_channels", DEFAULT_DG_CHANNELS.to_string(), "number of dg server ports to connect to, starting at port 6000 (0 = threads); fewer connected ports are used with a warning"),
+        ("dg_max_inflight", DEFAULT_DG_MAX_INFLIGHT.to_string(), "maximum number of concurrent dg queries (0 = limited by the ports only)"),
+        ("dg_connect_timeout_ms", DEFAULT_DG_CONNECT_TIMEOUT_MS.to_string(), "timeout in milliseconds for connecting to the dg server"),
+        ("dg_read_timeout_ms", DEFAULT_DG_READ_TIMEOUT_MS.to_string(), "timeout in milliseconds for receiving a dg energy (0 = no timeout)"),
+        ("dg_max_retries", DEFAULT_DG_MAX_RETRIES.to_string(), "number of reconnect attempts after the connection to the dg server failed"),
+        ("dg_min_len", DEFAULT_DG_MIN_LEN.to_string(), "minimum length of a strand to query its dg energy (shorter strands pass)"),
+        ("sec_struct_temp", DEFAULT_SECONDARY_STRUCT_TEMP.to_string(), "temperature in °C at which the secondary structures are predicted"),
+        ("dg_error_offset", DEFAULT_DG_ERROR_OFFSET.to_string(), "offset of the dg energy in the sigmoid 1 / (1 + exp(steepness * (dg + offset)))"),
+        ("dg_error_steepness", DEFAULT_DG_ERROR_STEEPNESS.to_string(), "steepness of the sigmoid of the dg error (> 0)"),
+        ("max_dg_error", DEFAULT_MAX_DG_ERROR.to_string(), "maximum dg error of an Info-DNA"),
+        ("dg_stats", DEFAULT_DG_STATS.to_string(), "true to print the distribution of the dg energies at the end"),
+        ("strand_stats", DEFAULT_STRAND_STATS.to_string(), "true to print the distributions of the GC content, length, and longest homopolymer of the Info-DNAs at the end"),
+        ("resume", DEFAULT_RESUME.to_string(), "true to keep the valid Info-DNAs of info_dna_path and encode only the missing lines"),
+        ("checkpoint_every", DEFAULT_CHECKPOINT_EVERY.to_string(), "number of encoded lines after which their Info-DNAs are appended to info_dna_path.checkpoint (0 = never)"),
+        ("quiet", DEFAULT_QUIET.to_string(), "true to suppress the progress line while encoding, e.g., when logging to a file"),
+        ("dry_run", DEFAULT_DRY_RUN.to_string(), "true to only print the estimated packets, bases, and strands without encoding"),
+        ("approve", DEFAULT_APPROVE.to_string(), "true to ask for confirmation before encoding"),
+        ("strict_args", DEFAULT_STRICT_ARGS.to_string(), "true to abort on unknown arguments, false to ignore them with a warning"),
+        ("threads", num_cpus::get().to_string(), "number of threads for encoding, building the LSH, and checking distances"),
+        ("per_line_timeout_ms", DEFAULT_PER_LINE_TIMEOUT_MS.to_string(), "time limit in milliseconds for encoding a single line, after which the line is skipped (0 = no limit)"),
+        ("seed", String::new(), "seed for reproducible runs (empty = not seeded)")
+    ]
 }
 
-/// A function that computes distances between `seq` and `candidates` (HashSet). Decides to parallelize the checks given candidates.len().
-fn pooled_dist_check_set(seq: &Arc<BaseSequence>, candidates: HashSet<Arc<BaseSequence>>, min: f64, k: usize, pool: &Arc<RwLock<ThreadPool>>) -> bool {
-    if candidates.len() < DISTANCE_CHECK_POOLING_TRIGGER {
-        for candidate in candidates.iter() {
-            if seq.jaccard_distance_arc(candidate, k) < min  {
-                return false;
-            }
-        }
-        return true
-    }
-    let is_dist_ok = Arc::new(parking_lot::RwLock::new(true));
-    let (tx, rx) = bounded(candidates.len());
-    let seq_arc = Arc::new(seq.clone());
-    let pool_lock = pool.write();
-    for candidate in candidates.iter() {
-        let is_dist_ok_cloned = is_dist_ok.clone();
-        let sender = tx.clone();
-        let s = seq_arc.clone();
-        let can = candidate.clone();
-        pool_lock.spawn(move|| {
-            if *is_dist_ok_cloned.read() {
-                sender.send(s.jaccard_distance_arc(&can, k));
-            }
-        });
+/// A function that prints the usage and every recognized command line option with its default value on the console.
+fn print_help() {
+    println!("RQPAP {}", env!("CARGO_PKG_VERSION"));
+    println!("usage: RQPAP [name=value]...");
+    println!("       RQPAP help | --help | -h");
+    println!("       RQPAP version | --version | -V");
+    println!();
+    println!("{:<23} {:<20} description", "name", "default");
+    for (name, default, description) in cli_options() {
+        println!("{:<23} {:<20} {}", name, default, description);
     }
-    for _ in 0..candidates.len() {
-        if rx.recv().unwrap() < min {
-            *is_dist_ok.write() = false;
-            return false;
-        }
-    }
-    true
 }
 
 /// A function that prints the given parameters on the console.
 #[inline(always)]
 fn print_parameters(lines_path: &str,
                     probes_path: &str,
+                    probes_lsh_cache: &str,
+                    max_iupac_expansions: usize,
                     info_dna_path: &str,
+                    output_format_str: &str,
+                    prefix_adapter: &str,
+                    suffix_adapter: &str,
+                    raptor: &RaptorQ,
                     overhead: usize,
+                    target_redundancy: Option<f64>,
                     max_hp_len: usize,
+                    max_hp_lens: [usize; 4],
+                    min_gc: f64,
+                    max_gc: f64,
+                    gc_window: usize,
+                    min_window_gc: f64,
+                    max_window_gc: f64,
+                    forbidden_motifs: &str,
+                    min_tm: f64,
+                    max_tm: f64,
+                    min_entropy: f64,
+                    max_hairpin_stem: usize,
+                    hairpin_min_loop: usize,
                     read_as_lines: bool,
                     use_dg_server: bool,
+                    dg_host: &str,
+                    dg_channels: usize,
+                    dg_max_inflight: usize,
+                    dg_connect_timeout_ms: u64,
+                    dg_read_timeout_ms: u64,
+                    dg_max_retries: usize,
+                    dg_min_len: usize,
+                    sec_struct_temp: f32,
+                    dg_error_offset: f32,
+                    dg_error_steepness: f32,
+                    max_dg_error: f32,
+                    dg_stats: bool,
+                    strand_stats: bool,
+                    resume: bool,
+                    checkpoint_every: usize,
+                    quiet: bool,
+                    dry_run: bool,
                     encoding_mode_str: &str,
+                    similarity_backend_str: &str,
+                    distance_metric_str: &str,
                     min_dist_to_probes: f64,
                     min_dist_to_seqs: f64,
+                    min_dist_to_seqs_schedule: &MinDistSchedule,
+                    check_revcomp: bool,
                     approve: bool,
+                    strict_args: bool,
+                    threads: usize,
+                    per_line_timeout_ms: u64,
+                    seed: Option<u64>,
                     report: bool,
+                    report_format: usize,
+                    report_format_str: &str,
                     report_path: &str,
+                    report_json_path: &str,
                     append_to_report: bool,
-                    encoding_mode: usize,
+                    probes_backend: usize,
+                    seqs_backend: usize,
                     lsh_k_probes: usize,
                     lsh_r_probes: usize,
                     lsh_b_probes: usize,
                     lsh_k_seqs: usize,
                     lsh_r_seqs: usize,
-                    lsh_b_seqs: usize) {
+                    lsh_b_seqs: usize,
+                    lsh_permutation_str: &str,
+                    lsh_bucketing_str: &str,
+                    lsh_cgk_len: usize,
+                    canonical_k_mers: bool,
+                    minhash_prefilter_margin: f64,
+                    lsh_stats: bool) {
 
     println!("++++++++++++++++++++++++++++++++");
     println!("-> Using following parameters <-");
     println!("++++++++++++++++++++++++++++++++");
     println!("lines_path             = {}", &lines_path);
     println!("probes_path            = {}", &probes_path);
+    if probes_backend != SIMILARITY_BACKEND_APPROX {
+        println!("probes_lsh_cache       = {} [ignored]", probes_lsh_cache);
+    }
+    else if !probes_lsh_cache.is_empty() && Path::new(probes_lsh_cache).exists() {
+        println!("probes_lsh_cache       = {} [cached LSH will be loaded]", probes_lsh_cache);
+    }
+    else {
+        println!("probes_lsh_cache       = {}", probes_lsh_cache);
+    }
+    println!("max_iupac_expansions   = {}", max_iupac_expansions);
     if Path::new(info_dna_path).exists() {
         println!("info_dna_path          = {} [file will be overridden]", info_dna_path);
     }
     else {
         println!("info_dna_path          = {}", info_dna_path);
     }
-    println!("overhead               = {}", overhead);
+    println!("output_format          = {}", output_format_str);
+    println!("prefix_adapter         = {}", prefix_adapter);
+    println!("suffix_adapter         = {}", suffix_adapter);
+    println!("base_mapping           = {}", raptor.mapping());
+    println!("rotate_mapping         = {}", raptor.mapping().is_rotating());
+    println!("rq_symbol_size         = {}", raptor.symbol_size());
+    println!("rq_source_blocks       = {}", raptor.source_blocks());
+    println!("rq_sub_blocks          = {}", raptor.sub_blocks());
+    println!("rq_alignment           = {}", raptor.alignment());
+    match target_redundancy {
+        Some(target_redundancy) => {
+            println!("overhead               = {} [ignored]", overhead);
+            println!("target_redundancy      = {}", target_redundancy);
+        },
+        None => {
+            println!("overhead               = {}", overhead);
+            println!("target_redundancy      = none");
+        }
+    }
     println!("max_hp_len             = {}", max_hp_len);
+    for (base, max) in Base::ALL.iter().zip(max_hp_lens.iter()) {
+        match max {
+            0 => println!("max_hp_{}               = {} (max_hp_len)", base.to_string().to_lowercase(), max_hp_len),
+            _ => println!("max_hp_{}               = {}", base.to_string().to_lowercase(), max)
+        }
+    }
+    println!("min_gc                 = {}", min_gc);
+    println!("max_gc                 = {}", max_gc);
+    println!("gc_window              = {}", gc_window);
+    if gc_window > 0 {
+        println!("min_window_gc          = {}", min_window_gc);
+        println!("max_window_gc          = {}", max_window_gc);
+    }
+    else {
+        println!("min_window_gc          = {} [ignored]", min_window_gc);
+        println!("max_window_gc          = {} [ignored]", max_window_gc);
+    }
+    println!("forbidden_motifs       = {}", forbidden_motifs);
+    println!("min_tm                 = {}", min_tm);
+    println!("max_tm                 = {}", max_tm);
+    println!("min_entropy            = {}", min_entropy);
+    println!("max_hairpin_stem       = {}", max_hairpin_stem);
+    if max_hairpin_stem > 0 {
+        println!("hairpin_min_loop       = {}", hairpin_min_loop);
+    }
+    else {
+        println!("hairpin_min_loop       = {} [ignored]", hairpin_min_loop);
+    }
     println!("read_as_lines          = {}", read_as_lines);
     println!("use_dg_server          = {}", use_dg_server);
+    if use_dg_server {
+        println!("dg_host                = {}", dg_host);
+        println!("dg_channels            = {}", dg_channels);
+        println!("dg_max_inflight        = {}", dg_max_inflight);
+        println!("dg_connect_timeout_ms  = {}", dg_connect_timeout_ms);
+        println!("dg_read_timeout_ms     = {}", dg_read_timeout_ms);
+        println!("dg_max_retries         = {}", dg_max_retries);
+        println!("dg_min_len             = {}", dg_min_len);
+        println!("sec_struct_temp        = {}", sec_struct_temp);
+        println!("dg_error_offset        = {}", dg_error_offset);
+        println!("dg_error_steepness     = {}", dg_error_steepness);
+        println!("max_dg_error           = {}", max_dg_error);
+        println!("dg_stats               = {}", dg_stats);
+    }
+    else {
+        println!("dg_host                = {} [ignored]", dg_host);
+        println!("dg_channels            = {} [ignored]", dg_channels);
+        println!("dg_max_inflight        = {} [ignored]", dg_max_inflight);
+        println!("dg_connect_timeout_ms  = {} [ignored]", dg_connect_timeout_ms);
+        println!("dg_read_timeout_ms     = {} [ignored]", dg_read_timeout_ms);
+        println!("dg_max_retries         = {} [ignored]", dg_max_retries);
+        println!("dg_min_len             = {} [ignored]", dg_min_len);
+        println!("sec_struct_temp        = {} [ignored]", sec_struct_temp);
+        println!("dg_error_offset        = {} [ignored]", dg_error_offset);
+        println!("dg_error_steepness     = {} [ignored]", dg_error_steepness);
+        println!("max_dg_error           = {} [ignored]", max_dg_error);
+        println!("dg_stats               = {} [ignored]", dg_stats);
+    }
+    println!("strand_stats           = {}", strand_stats);
+    println!("resume                 = {}", resume);
+    println!("checkpoint_every       = {}", checkpoint_every);
+    println!("quiet                  = {}", quiet);
+    println!("dry_run                = {}", dry_run);
     println!("encoding_mode          = {}", encoding_mode_str);
+    println!("similarity_backend     = {}", similarity_backend_str);
+    println!("distance_metric        = {}", distance_metric_str);
     println!("min_dist_to_probes     = {}", min_dist_to_probes);
     println!("min_dist_to_seqs       = {}", min_dist_to_seqs);
-    println!("approve                = {}", approve);
+    if min_dist_to_seqs_schedule.is_enabled() {
+        println!("min_dist_to_seqs_decay = {} to {} Info-DNAs, down to {}", min_dist_to_seqs_schedule.start, min_dist_to_seqs_schedule.end, min_dist_to_seqs_schedule.floor);
+    }
+    else {
+        println!("min_dist_to_seqs_decay = none");
+    }
+    println!("check_revcomp          = {}", check_revcomp);
+    if lines_path == LINES_PATH_STDIN {
+        println!("approve                = {} [ignored: lines are read from stdin]", approve);
+    }
+    else {
+        println!("approve                = {}", approve);
+    }
+    println!("strict_args            = {}", strict_args);
+    println!("threads                = {}", threads);
+    println!("per_line_timeout_ms    = {}", per_line_timeout_ms);
+    match seed {
+        Some(seed) => println!("seed                   = {}", seed),
+        None => println!("seed                   = none")
+    }
     println!("report                 = {}", report);
     if report {
+        println!("report_format          = {}", report_format_str);
         println!("append_to_report       = {}", append_to_report);
-        println!("report_path            = {}", report_path);
+        if report_format != REPORT_FORMAT_JSON {
+            println!("report_path            = {}", report_path);
+        }
+        else {
+            println!("report_path            = {} [ignored]", report_path);
+        }
+        if report_format != REPORT_FORMAT_CSV {
+            println!("report_json_path       = {}", report_json_path);
+        }
+        else {
+            println!("report_json_path       = {} [ignored]", report_json_path);
+        }
     }
     else {
+        println!("report_format          = {} [ignored]", report_format_str);
         println!("append_to_report       = {} [ignored]", append_to_report);
         println!("report_path            = {} [ignored]", report_path);
+        println!("report_json_path       = {} [ignored]", report_json_path);
     }
 
-    if encoding_mode == ENCODING_MODE_LSH {
+    if probes_backend == SIMILARITY_BACKEND_APPROX {
         println!("lsh_k_probes           = {}", lsh_k_probes);
         println!("lsh_r_probes           = {}", lsh_r_probes);
         println!("lsh_b_probes           = {}", lsh_b_probes);
+    }
+    else {
+        println!("lsh_k_probes           = {}", lsh_k_probes);
+        println!("lsh_r_probes           = {} [ignored]", lsh_r_probes);
+        println!("lsh_b_probes           = {} [ignored]", lsh_b_probes);
+    }
+    if seqs_backend == SIMILARITY_BACKEND_APPROX {
         println!("lsh_k_seqs             = {}", lsh_k_seqs);
         println!("lsh_r_seqs             = {}", lsh_r_seqs);
         println!("lsh_b_seqs             = {}", lsh_b_seqs);
     }
-    else if encoding_mode == ENCODING_MODE_MIXED {
-        println!("lsh_k_probes           = {}", lsh_k_probes);
-        println!("lsh_r_probes           = {}", lsh_r_probes);
-        println!("lsh_b_probes           = {}", lsh_b_probes);
-        println!("lsh_k_seqs             = {} [ignored]", lsh_k_seqs);
+    else {
+        println!("lsh_k_seqs             = {}", lsh_k_seqs);
         println!("lsh_r_seqs             = {} [ignored]", lsh_r_seqs);
         println!("lsh_b_seqs             = {} [ignored]", lsh_b_seqs);
     }
+    let cgk = lsh_bucketing_str.eq_ignore_ascii_case("cgk");
+    if probes_backend == SIMILARITY_BACKEND_APPROX || seqs_backend == SIMILARITY_BACKEND_APPROX {
+        println!("lsh_bucketing          = {}", lsh_bucketing_str);
+        if cgk {
+            println!("lsh_permutation        = {} [ignored]", lsh_permutation_str);
+            println!("lsh_cgk_len            = {}", lsh_cgk_len);
+        }
+        else {
+            println!("lsh_permutation        = {}", lsh_permutation_str);
+            println!("lsh_cgk_len            = {} [ignored]", lsh_cgk_len);
+        }
+    }
     else {
-        println!("lsh_k_probes           = {} [ignored]", lsh_k_probes);
-        println!("lsh_r_probes           = {} [ignored]", lsh_r_probes);
-        println!("lsh_b_probes           = {} [ignored]", lsh_b_probes);
-        println!("lsh_k_seqs             = {} [ignored]", lsh_k_seqs);
-        println!("lsh_r_seqs             = {} [ignored]", lsh_r_seqs);
-        println!("lsh_b_seqs             = {} [ignored]", lsh_b_seqs);
+        println!("lsh_bucketing          = {} [ignored]", lsh_bucketing_str);
+        println!("lsh_permutation        = {} [ignored]", lsh_permutation_str);
+        println!("lsh_cgk_len            = {} [ignored]", lsh_cgk_len);
+    }
+    println!("canonical_k_mers       = {}", canonical_k_mers);
+    if (probes_backend == SIMILARITY_BACKEND_APPROX || seqs_backend == SIMILARITY_BACKEND_APPROX) && distance_metric_str.eq_ignore_ascii_case("jaccard") && !cgk {
+        println!("minhash_prefilter_margin = {}", minhash_prefilter_margin);
+    }
+    else {
+        println!("minhash_prefilter_margin = {} [ignored]", minhash_prefilter_margin);
+    }
+    if probes_backend == SIMILARITY_BACKEND_APPROX {
+        println!("lsh_stats              = {}", lsh_stats);
+    }
+    else {
+        println!("lsh_stats              = {} [ignored]", lsh_stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rqpap::raptor::{EncodeLimits, EncodeRules};
+
+    #[test]
+    fn percentiles_are_taken_at_the_nearest_rank() {
+        let values = (0..=100).map(|v| v as f64).collect::<Vec<_>>();
+        assert_eq!(percentile_of_sorted(&values, 0_f64), 0_f64);
+        assert_eq!(percentile_of_sorted(&values, 0.05_f64), 5_f64);
+        assert_eq!(percentile_of_sorted(&values, 0.5_f64), 50_f64);
+        assert_eq!(percentile_of_sorted(&values, 1_f64), 100_f64);
+        assert_eq!(percentile_of_sorted(&[-7.5_f64], 0.95_f64), -7.5_f64);
+    }
+
+    #[test]
+    fn histogram_counts_every_value_once() {
+        let values = [-10_f64, -9.5, -8.0, -6.1, -6.0];
+        assert_eq!(histogram_counts(&values, -10_f64, 2_f64, 2_usize), vec![2_usize, 3_usize]);
+        assert_eq!(histogram_counts(&values, -10_f64, 1_f64, 5_usize), vec![2_usize, 0_usize, 1_usize, 1_usize, 1_usize]);
+        assert_eq!(histogram_counts(&values, -10_f64, 0_f64, 3_usize), vec![5_usize, 0_usize, 0_usize]);
+    }
+
+    /// Returns a path in the temporary directory that is unique to this test process.
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("rqpap_main_{}_{}", std::process::id(), name)).to_str().unwrap().to_string()
+    }
+
+    /// Encodes `data` without any constraints and returns the Info-DNA.
+    fn encode(raptor: &RaptorQ, data: &[u8]) -> Arc<BaseSequence> {
+        let mut rng = StdRng::seed_from_u64(7_u64);
+        raptor.encode_to_dna_with_rules(data, EncodeLimits::new(5_usize, 200_usize, 0_usize), &mut rng, &EncodeRules::accept_all()).unwrap().0
+    }
+
+    #[test]
+    fn resumes_only_the_complete_info_dnas_of_their_lines() {
+        let raptor = RaptorQ::default();
+        let adapters = Adapters::from_str("ACGT", "TTGA");
+        let lines = ["first line", "second line", "third line"].iter().map(|line| Arc::new(line.as_bytes().to_vec())).collect::<Vec<_>>();
+        let encoded = lines.iter().map(|line| encode(&raptor, line)).collect::<Vec<_>>();
+        let truncated = Arc::new(BaseSequence::from_slice(&adapters.wrap(&encoded[1]).as_slice()[..encoded[1].len()]));
+        let records = [
+            (1_usize, adapters.wrap(&encoded[0])),
+            (2_usize, truncated),                  // e.g., the last Info-DNA written before the run was interrupted
+            (1_usize, adapters.wrap(&encoded[0])), // a duplicate of line 1
+            (3_usize, adapters.wrap(&encoded[1])), // decodes to another line
+            (9_usize, adapters.wrap(&encoded[2])), // beyond the last line
+            (3_usize, encoded[2].clone())          // without the adapters
+        ];
+        let path = temp_path("resume.fa");
+        let mut file = File::create(path.as_str()).unwrap();
+        for (i, (line_number, seq)) in records.iter().enumerate() {
+            BaseSequence::append_to_fasta_file_with_caption_arc(&mut file, seq, info_dna_caption(*line_number).as_str(), i == 0_usize);
+        }
+        let resumed = read_resumable_info_dnas(path.as_str(), OUTPUT_FORMAT_FASTA, &lines, &raptor, &adapters);
+        assert_eq!(resumed, vec![(0_usize, encoded[0].clone())]);
+
+        // a checkpoint in the binary format resumes the same Info-DNAs
+        let done = vec![(0_usize, encoded[0].clone()), (2_usize, encoded[2].clone())];
+        write_checkpoint(path.as_str(), &done, &adapters);
+        assert_eq!(read_resumable_info_dnas(path.as_str(), OUTPUT_FORMAT_BINARY, &lines, &raptor, &adapters), done);
+        fs::remove_file(path.as_str()).unwrap();
+    }
+
+    #[test]
+    fn captions_and_ids_map_back_to_their_line_numbers() {
+        for line_number in [1_usize, 2, 30, 65_535].iter() {
+            assert_eq!(info_dna_caption(*line_number), format!(">{}", line_number));
+            assert_eq!(line_number_from_caption(&info_dna_caption(*line_number)[1..]), Some(*line_number));
+            assert_eq!(line_number_from_id(info_dna_id(*line_number)), Some(*line_number));
+        }
+        assert_eq!(line_number_from_caption("0"), None);
+        assert_eq!(line_number_from_caption("probe"), None);
+        assert_eq!(line_number_from_id(0_usize), None);
+    }
+
+    #[test]
+    fn checkpoints_append_only_the_new_info_dnas() {
+        let raptor = RaptorQ::default();
+        let adapters = Adapters::from_str("ACGT", "TTGA");
+        let lines = ["first line", "second line", "third line", "fourth line"].iter().map(|line| Arc::new(line.as_bytes().to_vec())).collect::<Vec<_>>();
+        let done = lines.iter().enumerate().map(|(line_id, line)| (line_id, encode(&raptor, line))).collect::<Vec<_>>();
+        let path = temp_path("append.checkpoint");
+
+        write_checkpoint(path.as_str(), &done[..1], &adapters);
+        let first_len = fs::metadata(path.as_str()).unwrap().len();
+        append_to_checkpoint(path.as_str(), &done[..3], 1_usize, &adapters);
+        assert_eq!(fs::metadata(path.as_str()).unwrap().len(), first_len + checkpoint_records(&done[1..3], &adapters).len() as u64);
+        assert_eq!(read_resumable_info_dnas(path.as_str(), OUTPUT_FORMAT_BINARY, &lines, &raptor, &adapters), done[..3].to_vec());
+
+        // a crash while appending leaves a truncated record, which is encoded again
+        let mut file = OpenOptions::new().append(true).open(path.as_str()).unwrap();
+        file.write_all(&checkpoint_records(&done[3..], &adapters)[..10]).unwrap();
+        assert_eq!(read_resumable_info_dnas(path.as_str(), OUTPUT_FORMAT_BINARY, &lines, &raptor, &adapters), done[..3].to_vec());
+
+        // a checkpoint that cannot be appended to is rewritten completely
+        fs::remove_file(path.as_str()).unwrap();
+        append_to_checkpoint(path.as_str(), &done, 3_usize, &adapters);
+        assert_eq!(read_resumable_info_dnas(path.as_str(), OUTPUT_FORMAT_BINARY, &lines, &raptor, &adapters), done);
+        fs::remove_file(path.as_str()).unwrap();
     }
 }
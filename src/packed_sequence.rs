@@ -0,0 +1,165 @@
+use crate::base_sequence::{Base, BaseSequence};
+use std::collections::HashSet;
+
+static BASES_PER_BYTE: usize                  = 4_usize;             // number of DNA bases packed into a byte (2 bits per base)
+
+/// The representation for a DNA sequence with 4 bases per byte, i.e., a quarter of the memory of a BaseSequence. The bases are packed as A=00, C=01, G=10, and T=11, starting with the highest bits of a byte.
+/// Intended for large sets of sequences that are mostly stored and only rarely inspected, e.g., the probes.
+#[derive(Eq, PartialEq, Clone, Debug, Hash)]
+pub struct PackedSequence {
+    bytes: Vec<u8>,
+    len: usize
+}
+
+impl PackedSequence {
+    /// Creates a new PackedSequence by packing `bases`.
+    pub fn from_bases(bases: &[Base]) -> Self {
+        Self {
+            bytes: bases.chunks(BASES_PER_BYTE).map(|chunk| chunk.iter().enumerate().fold(0_u8, |byte, (i, &base)| byte | (base as u8) << Self::shift(i))).collect(),
+            len: bases.len()
+        }
+    }
+
+    /// Creates a new PackedSequence by packing the bases of `seq`.
+    pub fn from_base_sequence(seq: &BaseSequence) -> Self {
+        Self::from_bases(seq.as_slice())
+    }
+
+    /// Creates a new PackedSequence of the first `len` bases of the packed `bytes`, i.e., the inverse of `as_bytes`. Panics if `bytes` contains less than `len` bases.
+    pub fn from_bytes(bytes: &[u8], len: usize) -> Self {
+        let bytes_len = len.div_ceil(BASES_PER_BYTE);
+        if bytes.len() < bytes_len {
+            panic!("cannot unpack {} bases from {} bytes", len, bytes.len());
+        }
+        let mut bytes = bytes[..bytes_len].to_vec();
+        // clear the padding so that equal sequences have equal bytes
+        if let Some(last) = bytes.last_mut() {
+            if !len.is_multiple_of(BASES_PER_BYTE) {
+                *last &= u8::MAX << Self::shift(len % BASES_PER_BYTE - 1);
+            }
+        }
+        Self { bytes, len }
+    }
+
+    /// Returns the packed bytes. The last byte is padded with zeros if the length is not a multiple of 4.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+
+    /// Returns the unpacked BaseSequence.
+    pub fn to_base_sequence(&self) -> BaseSequence {
+        BaseSequence::new(self.iter().collect())
+    }
+
+    /// Returns the number of DNA bases.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the DNA base at position `idx`. Panics if `idx` is out of bounds.
+    #[inline(always)]
+    pub fn base(&self, idx: usize) -> Base {
+        if idx >= self.len {
+            panic!("index {} out of bounds for packed sequence of len {}", idx, self.len);
+        }
+        Base::ALL[(self.bytes[idx / BASES_PER_BYTE] >> Self::shift(idx % BASES_PER_BYTE)) as usize & 0b_11]
+    }
+
+    /// Returns an iterator over the DNA bases.
+    pub fn iter(&self) -> impl Iterator<Item = Base> + '_ {
+        (0..self.len).map(move |idx| self.base(idx))
+    }
+
+    /// Returns the k-mers of length `len`. Unlike `BaseSequence::k_mers`, the k-mers are unpacked copies.
+    pub fn k_mers(&self, len: usize) -> Vec<BaseSequence> {
+        if len > self.len {
+            panic!("cannot create kmers of k={} for seq of len {}", len, self.len);
+        }
+        let bases = self.to_base_sequence();
+        (0..1 + self.len - len).map(|i| bases.sub_sequence(i, i + len)).collect()
+    }
+
+    /// Returns the k-mers as a set, i.e., a k-mer that occurs several times is contained once.
+    pub fn k_mers_set(&self, len: usize) -> HashSet<BaseSequence> {
+        self.k_mers(len).into_iter().collect()
+    }
+
+    /// Returns the GC content, or 0 for an empty sequence.
+    pub fn gc(&self) -> f64 {
+        if self.len == 0_usize {
+            return 0_f64;
+        }
+        self.iter().filter(|base| base.is_c_or_g()).count() as f64 / self.len as f64
+    }
+
+    /// Returns the length of the longest homopolymer, or 0 for an empty sequence.
+    pub fn longest_hp(&self) -> usize {
+        if self.len == 0_usize {
+            return 0_usize;
+        }
+        let mut longest = 1;
+        let mut current = 1;
+        for i in 1..self.len {
+            if self.base(i - 1) == self.base(i) {
+                current += 1;
+            } else {
+                longest = usize::max(current, longest);
+                current = 1;
+            }
+        }
+
+        usize::max(current, longest)
+    }
+
+    /// Returns the shift of the 2 bits of the `i`-th base of a byte.
+    #[inline(always)]
+    fn shift(i: usize) -> usize {
+        2 * (BASES_PER_BYTE - 1 - i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    static SEED: u64 = 7_u64;
+
+    #[test]
+    fn empty_sequence_has_no_gc_and_no_homopolymer() {
+        let packed = PackedSequence::from_bases(&[]);
+        assert_eq!(packed.len(), 0_usize);
+        assert_eq!(packed.gc(), 0_f64);
+        assert_eq!(packed.longest_hp(), 0_usize);
+    }
+
+    #[test]
+    fn statistics_match_the_base_sequence() {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let mut seqs = (1..40_usize).map(|len| BaseSequence::random_with_rng(len, &mut rng)).collect::<Vec<_>>();
+        seqs.extend(["A", "GGGG", "ACGTTTTTGCA", "CCCCCAAAAAAAT"].iter().map(|s| BaseSequence::try_from_str(s).unwrap()));
+        for seq in seqs.iter() {
+            let packed = PackedSequence::from_base_sequence(seq);
+            assert_eq!(packed.to_base_sequence(), *seq);
+            assert_eq!(packed.gc(), seq.gc(), "{}", seq.to_string());
+            assert_eq!(packed.longest_hp(), seq.longest_hp(), "{}", seq.to_string());
+            let k = usize::min(3_usize, seq.len());
+            let k_mers = packed.k_mers_set(k).iter().map(|k_mer| k_mer.as_slice().to_vec()).collect::<HashSet<_>>();
+            assert_eq!(k_mers, seq.k_mers_set(k).into_iter().map(|k_mer| k_mer.to_vec()).collect::<HashSet<_>>(), "{}", seq.to_string());
+        }
+    }
+
+    #[test]
+    fn k_mers_set_removes_repeated_k_mers() {
+        let packed = PackedSequence::from_base_sequence(&BaseSequence::try_from_str("ACACAC").unwrap());
+        assert_eq!(packed.k_mers(2_usize).len(), 5_usize);
+        assert_eq!(packed.k_mers_set(2_usize).len(), 2_usize);
+    }
+}
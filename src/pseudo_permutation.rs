@@ -1,5 +1,9 @@
 use rand::Rng;
 
+pub static PERMUTATION_PSEUDO: usize          = 0_usize;             // permutation kind "pseudo" (PseudoPermutation) is represented as 0
+pub static PERMUTATION_EXACT: usize           = 1_usize;             // permutation kind "exact" (ExactPermutation) is represented as 1
+pub static MAX_EXACT_PERMUTATION_M: usize     = 1_usize << 24;       // the largest m an ExactPermutation supports (4^12, i.e., k-mers up to k = 12)
+
 #[derive(Eq, PartialEq, Clone, Copy, Debug, Hash)]
 pub struct PseudoPermutation {
     m: usize,
@@ -21,6 +25,15 @@ impl PseudoPermutation {
     /// * `m` - The largest index for this instance to permute. For example, if you want to permute a 100 elements vector, m would be 100.
     /// * `p_1` - `p_1` must be greater than or equal to `m`. This LSH will use the next prime number greater than `p_1`.
     pub fn new_from_p(m: usize, p_1: usize) -> Self {
+        Self::new_from_p_seeded(m, p_1, &mut rand::thread_rng())
+    }
+
+    /// Creates a PseudoPermutation instance whose parameters `a` and `b` are drawn from `rng`, i.e., two instances drawn from equally seeded random number generators are identical.
+    /// # Arguments
+    /// * `m` - The largest index for this instance to permute. For example, if you want to permute a 100 elements vector, m would be 100.
+    /// * `p_1` - `p_1` must be greater than or equal to `m`. This LSH will use the next prime number greater than `p_1`.
+    /// * `rng` - The (seeded) random number generator used to draw the parameters.
+    pub fn new_from_p_seeded(m: usize, p_1: usize, rng: &mut impl Rng) -> Self {
         if p_1 < m {
             panic!("p must be >= m");
         }
@@ -28,19 +41,44 @@ impl PseudoPermutation {
         let p = Self::next_prime(p_1);
         PseudoPermutation {
             m,
-            p: Self::next_prime(p_1),
-            a: 1 + rand::thread_rng().gen_range(0..p),
-            b: 1 + rand::thread_rng().gen_range(0..p)
+            p,
+            a: 1 + rng.gen_range(0..p),
+            b: 1 + rng.gen_range(0..p)
+        }
+    }
+
+    /// Restores a PseudoPermutation instance from its parameters, e.g., those of a previously persisted instance.
+    pub fn from_params(m: usize, p: usize, a: usize, b: usize) -> Self {
+        PseudoPermutation {
+            m,
+            p,
+            a,
+            b
         }
     }
 
+    pub fn get_m(&self) -> usize {
+        self.m
+    }
+
     pub fn get_p(&self) -> usize {
         self.p
     }
 
-    /// Permutes index `x` to the permuted index.
+    pub fn get_a(&self) -> usize {
+        self.a
+    }
+
+    pub fn get_b(&self) -> usize {
+        self.b
+    }
+
+    /// Permutes index `x` to the permuted index. Falls back to 128-bit arithmetic if `a * x + b` overflows a usize, i.e., for m beyond about 2^32.
     pub fn apply(&self, x: usize) -> usize {
-        ((self.a * x + self.b) % self.p) % self.m
+        match self.a.checked_mul(x).and_then(|ax| ax.checked_add(self.b)) {
+            Some(ax_b) => (ax_b % self.p) % self.m,
+            None => ((self.a as u128 * x as u128 + self.b as u128) % self.p as u128) as usize % self.m
+        }
     }
 
     fn next_prime(n: usize) -> usize {
@@ -54,7 +92,201 @@ impl PseudoPermutation {
         p
     }
 
+    /// Returns true if the odd number `p` is prime, using the Miller-Rabin test with the first 12 primes as bases, which is deterministic for all 64-bit numbers.
+    /// Unlike trial division, this takes microseconds for the primes beyond 4^k of large k.
     fn is_odd_number_also_prime(p: usize) -> bool {
-        (3..=(p as f64).sqrt() as usize).step_by(2).into_iter().all(|n| (p % n) != 0)
+        let witnesses = [2_u128, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+        let n = p as u128;
+        if n < 2_u128 {
+            return false;
+        }
+        if let Some(&w) = witnesses.iter().find(|&&w| n.is_multiple_of(w)) {
+            return n == w;
+        }
+        let s = (n - 1).trailing_zeros();
+        let d = (n - 1) >> s;
+        let pow_mod = |mut base: u128, mut exp: u128| {
+            let mut result = 1_u128;
+            base %= n;
+            while exp > 0_u128 {
+                if exp & 1_u128 == 1_u128 {
+                    result = result * base % n;
+                }
+                base = base * base % n;
+                exp >>= 1;
+            }
+            result
+        };
+        witnesses.iter().all(|&w| {
+            let mut x = pow_mod(w, d);
+            if x == 1_u128 || x == n - 1 {
+                return true;
+            }
+            for _ in 1..s {
+                x = x * x % n;
+                if x == n - 1 {
+                    return true;
+                }
+            }
+            false
+        })
+    }
+}
+
+/// A real permutation of the indices 0..m that is backed by a table shuffled with Fisher–Yates. Unlike a PseudoPermutation, two different indices are never mapped to the same index.
+/// The table stores m entries of 4 bytes, i.e., an LSH instance with r exact permutations of the k-mers requires r * 4^k * 4 bytes (e.g., 800 KB for k = 5 and r = 200, but 800 MB for k = 10 and r = 200).
+#[derive(Eq, PartialEq, Clone, Debug, Hash)]
+pub struct ExactPermutation {
+    table: Vec<u32>
+}
+
+impl ExactPermutation {
+    /// Creates an ExactPermutation instance whose table is shuffled with `rng`. Use a seeded `rng` to obtain reproducible permutations.
+    /// # Arguments
+    /// * `m` - The number of indices to permute. Must not exceed `MAX_EXACT_PERMUTATION_M`.
+    /// * `rng` - The random number generator used to shuffle the table.
+    pub fn new_with_rng(m: usize, rng: &mut impl Rng) -> Self {
+        if m > MAX_EXACT_PERMUTATION_M {
+            panic!("an exact permutation supports at most {} indices, but {} were requested", MAX_EXACT_PERMUTATION_M, m);
+        }
+
+        let mut table = (0..m as u32).collect::<Vec<_>>();
+        for i in (1..m).rev() {
+            table.swap(i, rng.gen_range(0..=i));
+        }
+        ExactPermutation {
+            table
+        }
+    }
+
+    /// Restores an ExactPermutation instance from its table, e.g., that of a previously persisted instance. Panics if `table` is not a permutation of 0..`table.len()`.
+    pub fn from_table(table: Vec<u32>) -> Self {
+        let mut seen = vec![false; table.len()];
+        for &x in table.iter() {
+            if x as usize >= table.len() || seen[x as usize] {
+                panic!("table is not a permutation");
+            }
+            seen[x as usize] = true;
+        }
+        ExactPermutation {
+            table
+        }
+    }
+
+    pub fn get_m(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn table(&self) -> &[u32] {
+        &self.table
     }
-}
\ No newline at end of file
+
+    /// Permutes index `x` to the permuted index.
+    #[inline]
+    pub fn apply(&self, x: usize) -> usize {
+        self.table[x] as usize
+    }
+}
+
+/// A permutation that is used by LSH to compute the min-hashes, either a PseudoPermutation or an ExactPermutation.
+#[derive(Eq, PartialEq, Clone, Debug, Hash)]
+pub enum Permutation {
+    Pseudo(PseudoPermutation),
+    Exact(ExactPermutation)
+}
+
+impl Permutation {
+    /// Permutes index `x` to the permuted index.
+    #[inline]
+    pub fn apply(&self, x: usize) -> usize {
+        match self {
+            Permutation::Pseudo(p) => p.apply(x),
+            Permutation::Exact(p) => p.apply(x)
+        }
+    }
+
+    /// Returns the kind of this permutation, i.e., `PERMUTATION_PSEUDO` or `PERMUTATION_EXACT`.
+    pub fn kind(&self) -> usize {
+        match self {
+            Permutation::Pseudo(_) => PERMUTATION_PSEUDO,
+            Permutation::Exact(_) => PERMUTATION_EXACT
+        }
+    }
+}
+
+/// Converts the permutation kind's string into a number, i.e., (0="pseudo", 1="exact").
+#[inline(always)]
+pub fn extract_permutation_kind(arg: &str) -> usize {
+    if arg.eq_ignore_ascii_case("pseudo") {
+        PERMUTATION_PSEUDO
+    }
+    else if arg.eq_ignore_ascii_case("exact") {
+        PERMUTATION_EXACT
+    }
+    else {
+        panic!("cannot determine permutation kind: {}", arg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    static SEED: u64 = 7_u64;
+
+    #[test]
+    fn primality_test_agrees_with_trial_division() {
+        let is_prime = |n: usize| n >= 2 && (2..).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d));
+        for n in (1_usize..20_000).step_by(2) {
+            assert_eq!(PseudoPermutation::is_odd_number_also_prime(n), is_prime(n), "{}", n);
+        }
+        assert!(PseudoPermutation::is_odd_number_also_prime(4_611_686_018_427_388_039_usize)); // the smallest prime above 4^31
+        assert!(!PseudoPermutation::is_odd_number_also_prime(4_611_686_018_427_387_905_usize)); // 4^31 + 1 is divisible by 5
+    }
+
+    #[test]
+    fn apply_does_not_overflow_for_large_m() {
+        let m = 4_usize.pow(31);
+        let p = PseudoPermutation::from_params(m, PseudoPermutation::next_prime(m), m - 1, m - 2);
+        for x in [0_usize, 1, m / 2, m - 1].iter() {
+            let expected = ((p.get_a() as u128 * *x as u128 + p.get_b() as u128) % p.get_p() as u128) as usize % m;
+            assert_eq!(p.apply(*x), expected);
+        }
+        let small = PseudoPermutation::from_params(1024_usize, 1031_usize, 5_usize, 7_usize);
+        assert_eq!(small.apply(100_usize), (5 * 100 + 7) % 1031 % 1024);
+    }
+
+    #[test]
+    fn exact_permutation_is_a_seeded_bijection() {
+        let m = 4_usize.pow(5);
+        let p = ExactPermutation::new_with_rng(m, &mut StdRng::seed_from_u64(SEED));
+        let mut images = (0..m).map(|x| p.apply(x)).collect::<Vec<_>>();
+        images.sort_unstable();
+        assert_eq!(images, (0..m).collect::<Vec<_>>());
+        assert_eq!(p, ExactPermutation::new_with_rng(m, &mut StdRng::seed_from_u64(SEED)));
+        assert_ne!(p, ExactPermutation::new_with_rng(m, &mut StdRng::seed_from_u64(SEED + 1_u64)));
+        assert_eq!(ExactPermutation::from_table(p.table().to_vec()), p);
+        assert_eq!(Permutation::Exact(p).kind(), PERMUTATION_EXACT);
+    }
+
+    #[test]
+    #[should_panic(expected = "table is not a permutation")]
+    fn from_table_rejects_duplicate_entries() {
+        ExactPermutation::from_table(vec![0_u32, 2_u32, 2_u32]);
+    }
+
+    #[test]
+    #[should_panic(expected = "an exact permutation supports at most")]
+    fn exact_permutation_rejects_large_m() {
+        ExactPermutation::new_with_rng(MAX_EXACT_PERMUTATION_M + 1_usize, &mut StdRng::seed_from_u64(SEED));
+    }
+
+    #[test]
+    fn extracts_permutation_kinds() {
+        assert_eq!(extract_permutation_kind("Pseudo"), PERMUTATION_PSEUDO);
+        assert_eq!(extract_permutation_kind("exact"), PERMUTATION_EXACT);
+        assert_eq!(Permutation::Pseudo(PseudoPermutation::new(16_usize)).kind(), PERMUTATION_PSEUDO);
+    }
+}
@@ -1,94 +1,423 @@
-use raptorq::{Decoder, Encoder, EncodingPacket, ObjectTransmissionInformation, SourceBlockEncoder};
+use raptorq::{Decoder, Encoder, EncodingPacket, ObjectTransmissionInformation, SourceBlockEncoder, PayloadId, extended_source_block_symbols};
 use crate::dna_rules;
-use crate::base_sequence::{BaseSequence, Base};
+use crate::base_sequence::{BaseSequence, Base, ParseBaseError, SequenceBuilder};
 use std::cmp::{max, min};
 use rand::Rng;
 use std::rc::Rc;
-use rand::rngs::ThreadRng;
 use std::ops::{Range, Add, Sub};
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{SystemTime, Duration};
+use rayon::ThreadPool;
+use rayon::prelude::*;
 
-/// The Enum that represents an encoding status of a final DNA strand resembling an Info-DNA.
+static LEN_HEADER_LEN: usize                  = 8_usize;             // number of DNA bases of the file length in the header (2 bytes)
+static LEGACY_HEADER_LEN: usize               = 12_usize;            // number of DNA bases of the unversioned header (8 bases for the file length, 2 bases for the packets count, and 2 bases for the number of ESI bytes per packet)
+static VERSION_HEADER_LEN: usize              = 2_usize;             // number of DNA bases of the header version (half a byte), which takes the place of the number of ESI bytes per packet of the unversioned header
+static HEADER_LEN: usize                      = 20_usize;            // number of DNA bases of the header (the unversioned header with the header version followed by 8 bases for the CRC-16)
+pub static HEADER_VERSION: u8                 = 1_u8;                // the version of the header written by `encode_to_dna_with_rules` if every packet stores the lowest byte of its ESI (0 is the unversioned header without a CRC)
+pub static HEADER_VERSION_WIDE_ESI: u8        = 2_u8;                // the version of the header written by `encode_to_dna_with_rules` if every packet stores the two lower bytes of its ESI, i.e., if the data object needed more than `SHORT_ESI_REPAIR_SYMBOLS` repair symbols
+
+pub const DEFAULT_SOURCE_BLOCKS: usize        = 1_usize;             // default number of source blocks (only a single source block is supported)
+pub const DEFAULT_SUB_BLOCKS: usize           = 1_usize;             // default number of sub-blocks
+pub const DEFAULT_ALIGNMENT: usize            = 3_usize;             // default symbol alignment in bytes
+pub const DEFAULT_SYMBOL_SIZE: usize          = 6_usize;             // default symbol size in bytes (every byte is mapped to 4 DNA bases)
+pub static MAX_DATA_LEN: usize                = u16::MAX as usize;   // maximum number of bytes of a data object that fits in the header's file length
+static PACKET_GENERATION_POOLING_TRIGGER: usize = 256_usize;        // the number of packets before parallelizing their generation (should be manually adjusted for the target machine)
+static SERIALIZED_PAYLOAD_ID_LEN: usize       = 4_usize;             // number of leading bytes of a serialized packet that identify it (the source block number and 3 bytes of the ESI), of which only the lower `esi_bytes` bytes of the ESI are stored in DNA
+static SHORT_ESI_REPAIR_SYMBOLS: usize        = 256_usize;           // the number of repair symbols that are identified by the lowest byte of their ESI
+static WIDE_ESI_REPAIR_SYMBOLS: usize         = 65536_usize;         // the number of repair symbols that are identified by the two lower bytes of their ESI
+
+/// The Enum that represents an encoding status of a final DNA strand resembling an Info-DNA, together with the number of packets it consists of.
 enum PacketsResult {
-    Found(Arc<BaseSequence>, u8),
-    RulesNotSatisfied(Arc<BaseSequence>, u8),
+    Found(Arc<BaseSequence>, usize),
+    RulesNotSatisfied(Arc<BaseSequence>, usize),
     NotDecodable,
     OverheadTooBig(usize)
 }
 
+/// The result of `encode_to_dna_with_rules`: the Info-DNA, the RQ time, the dg time, the dg energy, the number of packets in the Info-DNA, and the achieved overhead.
+pub type EncodedDna = (Arc<BaseSequence>, Duration, Duration, f32, usize, usize);
+
+/// The type of a dg check passed to `encode_to_dna_with_rules` (see `NO_DG_CHECK`).
+pub type DgCheck = fn(&Arc<BaseSequence>) -> (bool, f32);
+
+/// The dg check of `encode_to_dna_with_rules` if the dg energy is not checked at all, e.g., without a dg server.
+pub const NO_DG_CHECK: Option<DgCheck> = None;
+
+/// The type of a packet or strand check passed to `encode_to_dna_with_rules` that accepts every sequence (see `EncodeRules::accept_all`).
+pub type SeqCheck = fn(&Arc<BaseSequence>) -> bool;
+
+/// The type of a check of the GC content and the longest homopolymer of a strand passed to `encode_to_dna_with_rules` that accepts every strand (see `EncodeRules::accept_all`).
+pub type GcHpCheck = fn(f64, usize) -> bool;
+
+/// The loop limits of `encode_to_dna_with_rules`.
+#[derive(Clone, Copy, Debug)]
+pub struct EncodeLimits {
+    packets_per_block: usize,
+    max_block_encode_loops: usize,
+    overhead: usize,
+    deadline: Option<SystemTime>
+}
+
+impl EncodeLimits {
+    /// Creates new EncodeLimits.
+    /// # Arguments
+    /// * `packets_per_block` - The number of packets that will be generated initially.
+    /// * `max_block_encode_loops` - The number of loops in which we attempt to successfully encode the data object.
+    /// * `overhead` - The overhead ε for RQ.
+    pub fn new(packets_per_block: usize, max_block_encode_loops: usize, overhead: usize) -> Self {
+        Self { packets_per_block, max_block_encode_loops, overhead, deadline: None }
+    }
+
+    /// Returns these limits with the point in time after which no further loop is started (None = no deadline). The encoding then fails as if `max_block_encode_loops` was exceeded.
+    pub fn with_deadline(mut self, deadline: Option<SystemTime>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn packets_per_block(&self) -> usize {
+        self.packets_per_block
+    }
+
+    pub fn max_block_encode_loops(&self) -> usize {
+        self.max_block_encode_loops
+    }
+
+    pub fn overhead(&self) -> usize {
+        self.overhead
+    }
+
+    pub fn deadline(&self) -> Option<SystemTime> {
+        self.deadline
+    }
+}
+
+/// The DNA constraints that `encode_to_dna_with_rules` checks on the packets and the final Info-DNA. Starts from `accept_all` and replaces the checks by the `with_*` methods.
+pub struct EncodeRules<P, G, S, D> {
+    packet_check: P,
+    strand_gc_hp_check: G,
+    strand_check: S,
+    dg_check: Option<D>
+}
+
+impl EncodeRules<SeqCheck, GcHpCheck, SeqCheck, DgCheck> {
+    /// Creates the rules that accept every packet and Info-DNA without a dg check (see `NO_DG_CHECK`).
+    pub fn accept_all() -> Self {
+        Self {
+            packet_check: |_| true,
+            strand_gc_hp_check: |_, _| true,
+            strand_check: |_| true,
+            dg_check: NO_DG_CHECK
+        }
+    }
+}
+
+impl<P, G, S, D> EncodeRules<P, G, S, D> {
+    /// Returns these rules with the function `check` that checks the GC content and homopolymer length requirements for every packet.
+    pub fn with_packet_check<P2: Fn(&Arc<BaseSequence>) -> bool + Sync>(self, check: P2) -> EncodeRules<P2, G, S, D> {
+        EncodeRules { packet_check: check, strand_gc_hp_check: self.strand_gc_hp_check, strand_check: self.strand_check, dg_check: self.dg_check }
+    }
+
+    /// Returns these rules with the function `check` that checks the GC content and the longest homopolymer of the final Info-DNA. Both are maintained while the packets are concatenated, i.e., without scanning the Info-DNA.
+    pub fn with_strand_gc_hp_check<G2: Fn(f64, usize) -> bool>(self, check: G2) -> EncodeRules<P, G2, S, D> {
+        EncodeRules { packet_check: self.packet_check, strand_gc_hp_check: check, strand_check: self.strand_check, dg_check: self.dg_check }
+    }
+
+    /// Returns these rules with the function `check` that checks the remaining constraints on the final Info-DNA (excluding the dg error). Only called if the strand's GC and homopolymer check passed.
+    pub fn with_strand_check<S2: Fn(&Arc<BaseSequence>) -> bool>(self, check: S2) -> EncodeRules<P, G, S2, D> {
+        EncodeRules { packet_check: self.packet_check, strand_gc_hp_check: self.strand_gc_hp_check, strand_check: check, dg_check: self.dg_check }
+    }
+
+    /// Returns these rules with the function `check` that checks the error by the dg server. It returns whether the check passed together with the dg energy of the strand. If None (e.g., `NO_DG_CHECK`), every strand passes without a dg energy (NaN) and the dg time is 0.
+    pub fn with_dg_check<D2: Fn(&Arc<BaseSequence>) -> (bool, f32)>(self, check: Option<D2>) -> EncodeRules<P, G, S, D2> {
+        EncodeRules { packet_check: self.packet_check, strand_gc_hp_check: self.strand_gc_hp_check, strand_check: self.strand_check, dg_check: check }
+    }
+}
+
+/// The error returned when `encode_to_dna_with_rules` did not find an Info-DNA that satisfies all constraints.
+#[derive(Debug)]
+pub enum EncodeError {
+    /// No Info-DNA was found within the maximum number of encode loops. Carries the last decodable candidate (with header, None if no combination of packets was decodable),
+    /// whether it satisfied the constraints except for the dg check, its dg energy (None if it was not computed), and the RQ and dg times spent.
+    MaxLoopsExceeded {
+        best: Option<Arc<BaseSequence>>,
+        satisfies_strand_rules: bool,
+        dg: Option<f32>,
+        rq_time: Duration,
+        dg_time: Duration
+    }
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::MaxLoopsExceeded { best: None, .. } => write!(f, "no decodable combination of packets was found"),
+            EncodeError::MaxLoopsExceeded { satisfies_strand_rules: false, .. } => write!(f, "the best candidate does not satisfy the constraints"),
+            EncodeError::MaxLoopsExceeded { dg: Some(dg), .. } => write!(f, "the best candidate fails the dg check (dg = {})", dg),
+            EncodeError::MaxLoopsExceeded { dg: None, .. } => write!(f, "the best candidate fails the dg check")
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// The table that assigns a DNA base to each 2-bit value, e.g., "ACGT" maps 00 to A, 01 to C, 10 to G, and 11 to T.
+/// If `rotate` is set, the 2-bit value of the i-th base is rotated by i (mod 4) before it is mapped, so that repeating byte patterns do not yield homopolymers.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub struct BaseMapping {
+    bases: [Base; 4],
+    bits: [u8; 4],
+    rotate: bool
+}
+
+impl BaseMapping {
+    /// Creates a new BaseMapping that maps the 2-bit value `i` to `bases[i]`. Panics if `bases` is not a permutation of A, C, G, and T.
+    pub fn new(bases: [Base; 4]) -> Self {
+        Self::try_new(bases).unwrap_or_else(|_| panic!("base mapping must contain every base exactly once: {:?}", bases))
+    }
+
+    /// Creates a new BaseMapping that maps the 2-bit value `i` to `bases[i]`, or returns the first base that occurs twice in `bases`.
+    fn try_new(bases: [Base; 4]) -> Result<Self, ParseBaseMappingError> {
+        let mut bits = [u8::MAX; 4];
+        for (value, base) in bases.iter().enumerate() {
+            if bits[Self::base_index(*base)] != u8::MAX {
+                return Err(ParseBaseMappingError::DuplicateBase(*base));
+            }
+            bits[Self::base_index(*base)] = value as u8;
+        }
+        Ok(Self { bases, bits, rotate: false })
+    }
+
+    /// Returns this BaseMapping with the position-dependent rotation enabled or disabled.
+    pub fn with_rotation(mut self, rotate: bool) -> Self {
+        self.rotate = rotate;
+        self
+    }
+
+    /// Returns true if the 2-bit values are rotated by the position of the base.
+    pub fn is_rotating(&self) -> bool {
+        self.rotate
+    }
+
+    /// Returns the DNA base of the 2-bit value `bits` (the higher bits are ignored).
+    #[inline(always)]
+    pub fn base(&self, bits: u8) -> Base {
+        self.bases[(bits & 0b_0000_0011) as usize]
+    }
+
+    /// Returns the 2-bit value of the DNA base `base`. This is the inverse of `base`.
+    #[inline(always)]
+    pub fn bits(&self, base: Base) -> u8 {
+        self.bits[Self::base_index(base)]
+    }
+
+    /// Returns the DNA base of the 2-bit value `bits` at position `pos` of a sequence (rotated by `pos` if rotating).
+    #[inline(always)]
+    pub fn base_at(&self, bits: u8, pos: usize) -> Base {
+        match self.rotate {
+            true => self.base(bits.wrapping_add(pos as u8)),
+            false => self.base(bits)
+        }
+    }
+
+    /// Returns the 2-bit value of the DNA base `base` at position `pos` of a sequence. This is the inverse of `base_at`.
+    #[inline(always)]
+    pub fn bits_at(&self, base: Base, pos: usize) -> u8 {
+        match self.rotate {
+            true => self.bits(base).wrapping_sub(pos as u8) & 0b_0000_0011,
+            false => self.bits(base)
+        }
+    }
+
+    #[inline(always)]
+    fn base_index(base: Base) -> usize {
+        match base {
+            Base::A => 0,
+            Base::C => 1,
+            Base::G => 2,
+            Base::T => 3
+        }
+    }
+}
+
+impl Default for BaseMapping {
+    fn default() -> Self {
+        Self::new(Base::ALL)
+    }
+}
+
+impl FromStr for BaseMapping {
+    type Err = ParseBaseMappingError;
+
+    /// Parses a string of 4 distinct bases, e.g., "ACGT".
+    fn from_str(mapping: &str) -> Result<Self, Self::Err> {
+        let bases = BaseSequence::try_from_str(mapping).map_err(ParseBaseMappingError::InvalidBase)?;
+        match bases.as_slice() {
+            [b0, b1, b2, b3] => Self::try_new([*b0, *b1, *b2, *b3]),
+            _ => Err(ParseBaseMappingError::WrongLength(bases.len()))
+        }
+    }
+}
+
+/// The error returned when a `BaseMapping` cannot be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseBaseMappingError {
+    /// The mapping contains a character that is not a DNA base.
+    InvalidBase(ParseBaseError),
+    /// The mapping does not consist of exactly 4 bases, but of the given number.
+    WrongLength(usize),
+    /// The mapping contains the given base more than once.
+    DuplicateBase(Base)
+}
+
+impl std::fmt::Display for ParseBaseMappingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseBaseMappingError::InvalidBase(e) => write!(f, "{}", e),
+            ParseBaseMappingError::WrongLength(len) => write!(f, "a base mapping must consist of exactly 4 bases, but has {}", len),
+            ParseBaseMappingError::DuplicateBase(base) => write!(f, "a base mapping must contain every base exactly once, but contains {} twice", base.to_string())
+        }
+    }
+}
+
+impl std::error::Error for ParseBaseMappingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseBaseMappingError::InvalidBase(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl std::fmt::Display for BaseMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.bases.iter().try_for_each(|base| write!(f, "{}", base.to_string()))
+    }
+}
+
 /// RQ's configuration holder.
 pub struct RaptorQ {
     source_blocks: usize,
     sub_blocks: usize,
     alignment: usize,
-    symbol_size: usize
+    symbol_size: usize,
+    mapping: BaseMapping,
+    packet_pool: Option<Arc<ThreadPool>>
 }
 
 impl RaptorQ {
-    /// Creates a new RQ with the given configuration.
-    pub fn new(source_blocks: usize, sub_blocks: usize, alignment: usize, symbol_size: usize) -> Self {
-        Self { source_blocks, sub_blocks, alignment, symbol_size }
+    /// Creates a new RQ with the given configuration. Panics if the configuration cannot be stored in an Info-DNA:
+    /// only a single source block is supported (its number is not stored in DNA), `symbol_size` must be a multiple of `alignment` and fit in 2 bytes, `alignment` must fit in 1 byte,
+    /// and every sub-block must contain at least one aligned unit of a symbol, i.e., `sub_blocks` must be between 1 and `symbol_size / alignment`.
+    pub fn new(source_blocks: usize, sub_blocks: usize, alignment: usize, symbol_size: usize, mapping: BaseMapping) -> Self {
+        if source_blocks != 1 {
+            panic!("only a single RQ source block is supported (rq_source_blocks = {})", source_blocks);
+        }
+        if alignment == 0 || alignment > u8::MAX as usize {
+            panic!("rq_alignment must be between 1 and {} (rq_alignment = {})", u8::MAX, alignment);
+        }
+        if symbol_size == 0 || symbol_size > u16::MAX as usize || !symbol_size.is_multiple_of(alignment) {
+            panic!("rq_symbol_size must be a positive multiple of rq_alignment of at most {} bytes (rq_symbol_size = {}, rq_alignment = {})", u16::MAX, symbol_size, alignment);
+        }
+        if sub_blocks == 0 || sub_blocks > symbol_size / alignment {
+            panic!("rq_sub_blocks must be between 1 and rq_symbol_size / rq_alignment = {} (rq_sub_blocks = {})", symbol_size / alignment, sub_blocks);
+        }
+        Self { source_blocks, sub_blocks, alignment, symbol_size, mapping, packet_pool: None }
     }
     /// Creates a new RQ with the default configuration.
     pub fn default() -> Self {
-        Self { source_blocks: 1, sub_blocks: 1, alignment: 3, symbol_size: 6 }
+        Self::with_mapping(BaseMapping::default())
+    }
+    /// Creates a new RQ with the default configuration that maps bits to DNA bases by `mapping`.
+    pub fn with_mapping(mapping: BaseMapping) -> Self {
+        Self { source_blocks: DEFAULT_SOURCE_BLOCKS, sub_blocks: DEFAULT_SUB_BLOCKS, alignment: DEFAULT_ALIGNMENT, symbol_size: DEFAULT_SYMBOL_SIZE, mapping, packet_pool: None }
+    }
+    /// Returns this RQ with the thread pool `pool` that generates and checks the packets in parallel once at least `PACKET_GENERATION_POOLING_TRIGGER` packets are requested at a time.
+    pub fn with_packet_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.packet_pool = Some(pool);
+        self
     }
     /// The function that encodes a data object (in bytes) into an Info-DNA while fulfilling the given DNA constraints. Returns a DNA sequence (Info-DNA) for the given `data`.
     /// # Arguments
     /// * `data` - The data object that is to be encoded to an Info-DNA.
-    /// * `packets_per_block` - The number of packets that will be generated initially.
-    /// * `max_block_encode_loops` - The number of loops in which we attempt to successfully encode `data`.
-    /// * `overhead` - The overhead ε for RQ.
-    /// * `gc_and_hp_check` - The function that checks the GC content and homopolymer length requirements for the DNA sequence.
-    /// * `strand_rule_no_dg` - The function that checks the constraints on final Info-DNA (excluding the dg error).
-    /// * `dg_check` - The function that checks the error by the dg server.
-    pub fn encode_to_dna_with_rules(&self,
-                                    data: &[u8],
-                                    mut packets_per_block: usize,
-                                    mut max_block_encode_loops: usize,
-                                    overhead: usize,
-                                    gc_and_hp_check: impl Fn(&Arc<BaseSequence>) -> bool,
-                                    strand_rule_no_dg: impl Fn(&Arc<BaseSequence>) -> bool,
-                                    dg_check: impl Fn(&Arc<BaseSequence>) -> bool) -> (Arc<BaseSequence>, Duration, Duration) {
+    /// * `limits` - The number of packets generated initially, the maximum number of encode loops, and the overhead ε for RQ.
+    /// * `rng` - The random number generator that determines the order in which packets are combined.
+    /// * `rules` - The checks of the packets and the final Info-DNA, including the dg check (see `EncodeRules`).
+    ///
+    /// Returns the Info-DNA, the RQ time, the dg time, the dg energy, the number of packets combined into the Info-DNA, and the achieved overhead (i.e., the number of packets beyond the source symbols of `data`), or `EncodeError::MaxLoopsExceeded` if no Info-DNA satisfied all constraints within the maximum number of encode loops.
+    ///
+    /// Every packet stores the lowest byte of its ESI, which identifies the first `SHORT_ESI_REPAIR_SYMBOLS` repair symbols. Once more repair symbols are needed, the encoding restarts with packets that store the two lower bytes of their ESI (see `HEADER_VERSION_WIDE_ESI`),
+    /// and no packet beyond the first `WIDE_ESI_REPAIR_SYMBOLS` repair symbols is generated. Hence, `decode_from_dna` restores the ESI of every packet of a returned Info-DNA.
+    pub fn encode_to_dna_with_rules<P, G, S, D>(&self, data: &[u8], limits: EncodeLimits, rng: &mut impl Rng, rules: &EncodeRules<P, G, S, D>) -> Result<EncodedDna, EncodeError>
+        where P: Fn(&Arc<BaseSequence>) -> bool + Sync,
+              G: Fn(f64, usize) -> bool,
+              S: Fn(&Arc<BaseSequence>) -> bool,
+              D: Fn(&Arc<BaseSequence>) -> (bool, f32) {
+
+        if data.len() > MAX_DATA_LEN {
+            panic!("cannot encode data object of {} bytes (max. {} bytes)", data.len(), MAX_DATA_LEN);
+        }
 
         let start_time = SystemTime::now();
         let mut dg_time = Duration::new(0_u64, 0_u32);
-        let encoder = Encoder::new(&data,ObjectTransmissionInformation::new(
-            data.len() as u64,
-            self.symbol_size as u16,
-            self.source_blocks as u8,
-            self.sub_blocks as u16,
-            self.alignment as u8
-        ));
+        let encoder = Encoder::new(&data, self.transmission_info(data.len()));
 
         let source_block_encoder = &encoder.get_block_encoders()[0];
+        let EncodeLimits { packets_per_block, max_block_encode_loops, overhead, deadline } = limits;
+        let expired = || deadline.is_some_and(|deadline| SystemTime::now() >= deadline);
         let mut packets_count = packets_per_block;
         let mut block_loop_num = 0;
-        let mut rng = ThreadRng::default();
-        let mut last_strand = Arc::new(BaseSequence::empty());
-        let mut packets_count_last = 0_u8;
+        let mut last_strand = None;
+        let mut packets_count_last = 0_usize;
+        let mut dg_last = None;
         let mut from_repair_esi = 0_usize;
         let mut good_packets = vec![];
         let mut last_esi = 0_usize;
-        while block_loop_num < max_block_encode_loops {
+        let mut esi_bytes = 1_usize;
+        while block_loop_num < max_block_encode_loops && !expired() {
+            if from_repair_esi + packets_count > Self::repair_symbols_limit(esi_bytes) {
+                if esi_bytes == 1_usize {
+                    // the lowest byte of the ESI cannot identify the next packets -> restart with the packets generated so far, but storing the two lower bytes of their ESI
+                    esi_bytes = 2_usize;
+                    packets_count += from_repair_esi;
+                    from_repair_esi = 0_usize;
+                    good_packets.clear();
+                    last_strand = None;
+                    dg_last = None;
+                }
+                packets_count = min(packets_count, Self::repair_symbols_limit(esi_bytes).saturating_sub(from_repair_esi));
+                if packets_count == 0_usize {
+                    break; // every repair symbol that can be identified was generated
+                }
+            }
             block_loop_num += 1;
             last_esi = from_repair_esi + packets_count;
-            let fresh_packets = Self::generate_packets(source_block_encoder, packets_count, from_repair_esi, &gc_and_hp_check);
+            let fresh_packets = self.generate_packets(source_block_encoder, packets_count, from_repair_esi, esi_bytes, &rules.packet_check);
             good_packets.extend(fresh_packets);
             for _ in 0..good_packets.len() {
-                match Self::combine_packets_to_strand(&good_packets, Decoder::new(encoder.get_config()), overhead, Self::random_order(0..good_packets.len(), &mut rng).as_slice(), &strand_rule_no_dg) {
+                if expired() {
+                    break;
+                }
+                match Self::combine_packets_to_strand(&good_packets, Decoder::new(encoder.get_config()), overhead, Self::random_order(0..good_packets.len(), rng).as_slice(), &rules.strand_gc_hp_check, &rules.strand_check) {
                     PacketsResult::Found(strand, packets_count) => {
-                        let dg_start_time = SystemTime::now();
-                        let dg_check_result = dg_check(&strand);
-                        dg_time += SystemTime::now().duration_since(dg_start_time).unwrap();
+                        let (dg_check_result, dg) = match &rules.dg_check {
+                            Some(dg_check) => {
+                                let dg_start_time = SystemTime::now();
+                                let checked = dg_check(&strand);
+                                dg_time += SystemTime::now().duration_since(dg_start_time).unwrap();
+                                checked
+                            }
+                            None => (true, f32::NAN)
+                        };
                         if dg_check_result {
                             let rq_time = SystemTime::now().duration_since(start_time).unwrap() - dg_time;
-                            return (Self::finalize_encoding(&strand, data.len() as u8, packets_count), rq_time, dg_time);
+                            return Ok((self.finalize_encoding(&strand, data, packets_count, esi_bytes), rq_time, dg_time, dg, packets_count, packets_count.saturating_sub(self.source_symbols(data.len()))));
                         }
                         else {
-                            last_strand = strand;
+                            last_strand = Some(strand);
                             packets_count_last = packets_count;
+                            dg_last = Some(dg);
                         }
                     }
                     // the packets could be decodable but do not contain the specified overhead -> need more packets
@@ -103,24 +432,129 @@ impl RaptorQ {
                     }
                     // the packets are decodable but do not meet the requirements given by the constraints
                     PacketsResult::RulesNotSatisfied(strand, packets_count) => {
-                        last_strand = strand;
+                        last_strand = Some(strand);
                         packets_count_last = packets_count;
+                        dg_last = None;
                     }
                 }
             }
             from_repair_esi = last_esi + 1;
         }
 
-        (Self::finalize_encoding(&last_strand, data.len() as u8, packets_count_last),
-         SystemTime::now().duration_since(start_time).unwrap() - dg_time,
-         dg_time)
-        //panic!("failed encoding file={:?}", data);
+        Err(EncodeError::MaxLoopsExceeded {
+            best: last_strand.map(|strand| self.finalize_encoding(&strand, data, packets_count_last, esi_bytes)),
+            satisfies_strand_rules: dg_last.is_some(),
+            dg: dg_last,
+            rq_time: SystemTime::now().duration_since(start_time).unwrap() - dg_time,
+            dg_time
+        })
+    }
+
+
+    /// Returns the header version of the Info-DNA `seq`, i.e., `HEADER_VERSION` or `HEADER_VERSION_WIDE_ESI` for a header with a CRC and 0 for an unversioned header written by older releases. Returns None if `seq` has neither.
+    /// The version is read from the header and must match the length of `seq`, since the payload consists of whole packets, whose length depends on the version. An unversioned header is identified by the length of `seq` alone.
+    pub fn header_version(&self, seq: &BaseSequence) -> Option<u8> {
+        self.header_versions(seq).into_iter().next()
+    }
+
+    /// Returns every header version that `seq` may have (see `header_version`), the versioned header first. A strand with an unversioned header may have a versioned header by chance as well, so `decode_from_dna` tries both.
+    fn header_versions(&self, seq: &BaseSequence) -> Vec<u8> {
+        let mut versions = Vec::with_capacity(2);
+        if seq.len() > LEGACY_HEADER_LEN {
+            let version = self.map_bases_to_half_byte(seq.sub_sequence_slice(LEGACY_HEADER_LEN - VERSION_HEADER_LEN, LEGACY_HEADER_LEN));
+            if let Some(esi_bytes) = Self::esi_bytes_of(version) {
+                if seq.len() > HEADER_LEN && (seq.len() - HEADER_LEN).is_multiple_of(self.packet_len(esi_bytes)) {
+                    versions.push(version);
+                }
+                if (seq.len() - LEGACY_HEADER_LEN).is_multiple_of(self.packet_len(esi_bytes)) {
+                    versions.push(0_u8);
+                }
+            }
+        }
+        versions
+    }
+
+    /// Decodes an Info-DNA created by `encode_to_dna_with_rules` back into the data object. Returns None if `seq` is corrupted, the packets it contains are not decodable, or the decoded data does not match the CRC-16 in the header.
+    /// Info-DNAs with the unversioned header (see `header_version`) are decoded as well, but cannot be verified.
+    /// Note that only the lower bytes of the encoding symbol id (ESI) of each packet are stored in DNA, i.e., one byte (two with `HEADER_VERSION_WIDE_ESI`). The full ESI is restored as the smallest repair ESI with these lower bytes,
+    /// which `encode_to_dna_with_rules` guarantees by restricting the packets of an Info-DNA to the first `SHORT_ESI_REPAIR_SYMBOLS` (`WIDE_ESI_REPAIR_SYMBOLS`) repair symbols.
+    pub fn decode_from_dna(&self, seq: &BaseSequence) -> Option<Vec<u8>> {
+        self.header_versions(seq).into_iter().find_map(|version| self.decode_with_version(seq, version))
+    }
+
+    /// Decodes the Info-DNA `seq` with the header version `version` (see `decode_from_dna`).
+    fn decode_with_version(&self, seq: &BaseSequence, version: u8) -> Option<Vec<u8>> {
+        let header_len = if version == 0_u8 { LEGACY_HEADER_LEN } else { HEADER_LEN };
+        let esi_bytes = Self::esi_bytes_of(self.map_bases_to_half_byte(seq.sub_sequence_slice(LEGACY_HEADER_LEN - VERSION_HEADER_LEN, LEGACY_HEADER_LEN)))?;
+        let data_len_bytes = self.map_bases_to_bytes(seq.sub_sequence_slice(0, LEN_HEADER_LEN));
+        let data_len = u16::from_be_bytes([data_len_bytes[0], data_len_bytes[1]]);
+        let packets_count = self.map_bases_to_half_byte(seq.sub_sequence_slice(LEN_HEADER_LEN, LEGACY_HEADER_LEN - VERSION_HEADER_LEN));
+        let crc = match version {
+            0 => None,
+            _ => {
+                let crc_bytes = self.map_bases_to_bytes(seq.sub_sequence_slice(LEGACY_HEADER_LEN, HEADER_LEN));
+                Some(u16::from_be_bytes([crc_bytes[0], crc_bytes[1]]))
+            }
+        };
+        let packet_len = self.packet_len(esi_bytes);
+        let payload = seq.sub_sequence_slice(header_len, seq.len());
+        if (payload.len() / packet_len) as u8 & 0b_0000_1111 != packets_count {
+            return None;
+        }
+
+        let first_repair_esi = extended_source_block_symbols(self.source_symbols(data_len as usize) as u32);
+        let esi_modulus = Self::repair_symbols_limit(esi_bytes) as u32;
+        let mut decoder = Decoder::new(self.transmission_info(data_len as usize));
+        for dna_packet in payload.chunks(packet_len) {
+            let bytes = self.map_bases_to_bytes(dna_packet);
+            let lower_esi = bytes[..esi_bytes].iter().fold(0_u32, |esi, b| (esi << 8) | *b as u32);
+            let esi = first_repair_esi + lower_esi.wrapping_sub(first_repair_esi) % esi_modulus;
+            if let Some(data) = decoder.decode(EncodingPacket::new(PayloadId::new(0_u8, esi), bytes[esi_bytes..].to_vec())) {
+                return match crc {
+                    Some(crc) if crc != Self::crc16(&data) => None,
+                    _ => Some(data)
+                };
+            }
+        }
+
+        None
+    }
+
+    /// Returns the estimated number of packets and the estimated length (in DNA bases, including the header) of the Info-DNA of a data object of `data_len` bytes for the given `overhead`.
+    /// The estimate assumes that the source symbols plus `overhead` repair packets are decodable, which RQ achieves with a high probability, and that the packets store the two lower bytes of their ESI if there are more than `SHORT_ESI_REPAIR_SYMBOLS`. Neither the constraints nor the dg energy are checked.
+    pub fn estimate_encoding(&self, data_len: usize, overhead: usize) -> (usize, usize) {
+        let packets = self.source_symbols(data_len) + overhead;
+        let esi_bytes = if packets > SHORT_ESI_REPAIR_SYMBOLS { 2_usize } else { 1_usize };
+        (packets, HEADER_LEN + packets * self.packet_len(esi_bytes))
     }
 
+    /// Returns the overhead ε for a data object of `data_len` bytes such that its Info-DNA contains at least `redundancy` times its source symbols, i.e., ceil(source symbols * `redundancy`) - source symbols packets beyond the source symbols.
+    pub fn overhead_for_redundancy(&self, data_len: usize, redundancy: f64) -> usize {
+        let source_symbols = self.source_symbols(data_len);
+        ((source_symbols as f64 * redundancy).ceil() as usize).saturating_sub(source_symbols)
+    }
+
+    /// Returns the number of source symbols of a data object of `data_len` bytes.
+    #[inline]
+    fn source_symbols(&self, data_len: usize) -> usize {
+        data_len.div_ceil(self.symbol_size)
+    }
+
+    /// Returns RQ's transmission information for a data object of `data_len` bytes.
+    #[inline]
+    fn transmission_info(&self, data_len: usize) -> ObjectTransmissionInformation {
+        ObjectTransmissionInformation::new(
+            data_len as u64,
+            self.symbol_size as u16,
+            self.source_blocks as u8,
+            self.sub_blocks as u16,
+            self.alignment as u8
+        )
+    }
 
     /// Collects the given `range` into a vector, permutes it by `rng`, and returns the vector.
     #[inline]
-    fn random_order(range: Range<usize>, rng: &mut ThreadRng) -> Vec<usize> {
+    fn random_order(range: Range<usize>, rng: &mut impl Rng) -> Vec<usize> {
         let count = range.len();
         let mut v = range.collect::<Vec<usize>>();
         for _ in 0..count {
@@ -134,12 +568,12 @@ impl RaptorQ {
         v
     }
 
-    /// The function that combines `packets` into a single DNA strand. It will opt to combine as many as needed to be decodable and meet the `overhead` specified. The strand must fulfill `strand_id_ok_func`.
+    /// The function that combines `packets` into a single DNA strand. It will opt to combine as many as needed to be decodable and meet the `overhead` specified. The strand must fulfill `gc_hp_ok_func` (given its GC content and longest homopolymer) and `strand_is_ok_func`.
     #[inline]
-    fn combine_packets_to_strand(packets: &Vec<(Arc<BaseSequence>, Vec<u8>)>, mut decoder: Decoder, overhead: usize, index_order: &[usize], strand_is_ok_func: impl Fn(&Arc<BaseSequence>) -> bool) -> PacketsResult {
-        let mut current_overhead = -1_isize;
+    fn combine_packets_to_strand(packets: &Vec<(Arc<BaseSequence>, Vec<u8>)>, mut decoder: Decoder, overhead: usize, index_order: &[usize], gc_hp_ok_func: impl Fn(f64, usize) -> bool, strand_is_ok_func: impl Fn(&Arc<BaseSequence>) -> bool) -> PacketsResult {
+        let mut decodable_packets = 0_usize; // the packets combined since the packets became decodable (including the packet that made them decodable), i.e., the current overhead plus 1
         let mut decoded = None;
-        let mut dna_strand = BaseSequence::new(vec![]);
+        let mut dna_strand = SequenceBuilder::new();
         let mut packets_used = 0_usize;
         for index in index_order {
             let packet_pair = packets.get(*index).unwrap();
@@ -147,17 +581,19 @@ impl RaptorQ {
             decoded = decoder.decode(EncodingPacket::deserialize(packet_pair.1.as_slice()));
             dna_strand.append_slice(packet_pair.0.as_slice());
             if decoded.is_some() {
-                current_overhead += 1;
-                let missing_packets = (overhead as isize - current_overhead) as isize - (packets.len() - packets_used) as isize;
+                decodable_packets += 1;
+                // the packets that are needed for the overhead beyond the packets that are left
+                let missing_packets = overhead.saturating_add(1_usize).saturating_sub(decodable_packets + packets.len() - packets_used);
                 if missing_packets > 0 {
-                    return PacketsResult::OverheadTooBig(missing_packets as usize);
+                    return PacketsResult::OverheadTooBig(missing_packets);
                 }
-                if current_overhead >= overhead as isize {
-                    let strand_arc = Arc::new(dna_strand);
-                    return if strand_is_ok_func(&strand_arc) {
-                        PacketsResult::Found(strand_arc, packets_used as u8)
+                if decodable_packets > overhead {
+                    let gc_hp_ok = gc_hp_ok_func(dna_strand.gc(), dna_strand.longest_hp());
+                    let strand_arc = Arc::new(dna_strand.into_sequence());
+                    return if gc_hp_ok && strand_is_ok_func(&strand_arc) {
+                        PacketsResult::Found(strand_arc, packets_used)
                     } else {
-                        PacketsResult::RulesNotSatisfied(strand_arc, packets_used as u8)
+                        PacketsResult::RulesNotSatisfied(strand_arc, packets_used)
                     }
                 }
             }
@@ -165,66 +601,108 @@ impl RaptorQ {
         PacketsResult::NotDecodable
     }
 
-    /// Adds a header (containing the RQ configuration) to `seq` that allows a DNA strand to be decoded.
+    /// Adds a header (containing the RQ configuration) to `seq` that allows a DNA strand to be decoded and verified. The header consists of the length of `data` (2 bytes, big endian, i.e., 8 DNA bases),
+    /// the packets count (its lower half byte, i.e., 2 DNA bases), the header version (2 DNA bases) that tells the `esi_bytes` stored per packet, and the CRC-16 of `data` (2 bytes, big endian, i.e., 8 DNA bases).
     #[inline]
-    fn finalize_encoding(seq: &Arc<BaseSequence>, data_len: u8, packets_count: u8) -> Arc<BaseSequence> {
-        let file_len = Self::map_half_byte_to_bases(data_len);
-        let file_packets_count = Self::map_half_byte_to_bases(packets_count);
+    fn finalize_encoding(&self, seq: &Arc<BaseSequence>, data: &[u8], packets_count: usize, esi_bytes: usize) -> Arc<BaseSequence> {
+        let file_len = self.map_bytes_to_base_sequence(&(data.len() as u16).to_be_bytes());
+        let file_packets_count = self.map_half_byte_to_bases(packets_count as u8);
+        let version = if esi_bytes == 1_usize { HEADER_VERSION } else { HEADER_VERSION_WIDE_ESI };
         let mut final_seq = BaseSequence::concat_slice(file_len.as_slice(), file_packets_count.as_slice());
+        final_seq.append_slice(self.map_half_byte_to_bases(version).as_slice());
+        final_seq.append_slice(self.map_bytes_to_base_sequence(&Self::crc16(data).to_be_bytes()).as_slice());
         final_seq.append_slice(seq.as_slice());
         Arc::new(final_seq)
     }
 
-    /// Generates `packets_per_block` packets that satisfy `rules_func`.
+    /// Computes the CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF) of `data`.
+    fn crc16(data: &[u8]) -> u16 {
+        data.iter().fold(0xFFFF_u16, |crc, b| {
+            (0..8).fold(crc ^ ((*b as u16) << 8), |crc, _| if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 })
+        })
+    }
+
+    /// Returns the number of DNA bases of a packet (`esi_bytes` bytes of the ESI and `symbol_size` bytes of the symbol).
     #[inline]
-    pub fn generate_packets(block_encoder: &SourceBlockEncoder, packets_per_block: usize, from_repair_esi: usize, rules_func: impl Fn(&Arc<BaseSequence>) -> bool) -> (Vec<(Arc<BaseSequence>, Vec<u8>)>) {
-        let mut packets = Vec::with_capacity(packets_per_block);
-        for p in Self::next_n_packets(block_encoder, from_repair_esi, packets_per_block).into_iter() {
-            let dna_packet = Arc::new(RaptorQ::map_bytes_to_base_sequence(&p[3..]));
-            if rules_func(&dna_packet) {
-                packets.push((dna_packet, p));
-            }
+    fn packet_len(&self, esi_bytes: usize) -> usize {
+        4 * (esi_bytes + self.symbol_size)
+    }
+
+    /// Returns the number of bytes of the ESI stored in every packet of an Info-DNA whose header stores `version`, i.e., the header version or the number of ESI bytes per packet of the unversioned header, or None if `version` is unknown.
+    #[inline]
+    fn esi_bytes_of(version: u8) -> Option<usize> {
+        match version {
+            1 => Some(1_usize),
+            2 => Some(2_usize),
+            _ => None
         }
+    }
+
+    /// Returns the number of repair symbols that are identified by the lower `esi_bytes` bytes of their ESI.
+    #[inline]
+    fn repair_symbols_limit(esi_bytes: usize) -> usize {
+        if esi_bytes == 1_usize { SHORT_ESI_REPAIR_SYMBOLS } else { WIDE_ESI_REPAIR_SYMBOLS }
+    }
 
-        packets
+    /// Generates `packets_per_block` packets, whose DNA stores the lower `esi_bytes` bytes of their ESI, and returns those that satisfy `rules_func`, ordered by their ESI. Uses the packet pool (if any) when at least `PACKET_GENERATION_POOLING_TRIGGER` packets are generated.
+    #[inline]
+    pub fn generate_packets(&self, block_encoder: &SourceBlockEncoder, packets_per_block: usize, from_repair_esi: usize, esi_bytes: usize, rules_func: impl Fn(&Arc<BaseSequence>) -> bool + Sync) -> (Vec<(Arc<BaseSequence>, Vec<u8>)>) {
+        let to_checked_packet = |p: Vec<u8>| {
+            let dna_packet = Arc::new(self.map_bytes_to_base_sequence(&p[SERIALIZED_PAYLOAD_ID_LEN - esi_bytes..]));
+            if rules_func(&dna_packet) { Some((dna_packet, p)) } else { None }
+        };
+        match &self.packet_pool {
+            Some(pool) if packets_per_block >= PACKET_GENERATION_POOLING_TRIGGER => {
+                // every thread generates a contiguous range of ESIs, and the ranges are collected in order -> the same packets as the serial generation
+                let chunk_size = packets_per_block.div_ceil(pool.current_num_threads());
+                pool.install(|| (0..packets_per_block).step_by(chunk_size).collect::<Vec<_>>().into_par_iter()
+                    .flat_map_iter(|start| Self::next_n_packets(block_encoder, from_repair_esi + start, min(chunk_size, packets_per_block - start)).into_iter().filter_map(&to_checked_packet))
+                    .collect())
+            }
+            _ => Self::next_n_packets(block_encoder, from_repair_esi, packets_per_block).into_iter().filter_map(to_checked_packet).collect()
+        }
     }
 
     /// Maps a byte slice to a BaseSequence.
     #[inline]
-    fn map_bytes_to_base_sequence(slice: &[u8]) -> BaseSequence {
-        BaseSequence::new(slice.iter().flat_map(|b| Self::map_byte_to_bases(*b)).collect())
+    fn map_bytes_to_base_sequence(&self, slice: &[u8]) -> BaseSequence {
+        BaseSequence::new(slice.iter().enumerate().flat_map(|(i, b)| self.map_byte_to_bases(*b, 4 * i)).collect())
     }
 
-    /// Maps a single byte to 4 DNA bases.
+    /// Maps a single byte to 4 DNA bases. `pos` is the position of the first base in the sequence.
     #[inline]
-    fn map_byte_to_bases(b: u8) -> Vec<Base> {
+    fn map_byte_to_bases(&self, b: u8, pos: usize) -> Vec<Base> {
         let mut result = Vec::with_capacity(4);
 
-        result.push(Self::map_byte_to_base((b >> 6) & 0b_0000_0011));
-        result.push(Self::map_byte_to_base((b >> 4) & 0b_0000_0011));
-        result.push(Self::map_byte_to_base((b >> 2) & 0b_0000_0011));
-        result.push(Self::map_byte_to_base(b & 0b_0000_0011));
+        result.push(self.mapping.base_at((b >> 6) & 0b_0000_0011, pos));
+        result.push(self.mapping.base_at((b >> 4) & 0b_0000_0011, pos + 1));
+        result.push(self.mapping.base_at((b >> 2) & 0b_0000_0011, pos + 2));
+        result.push(self.mapping.base_at(b & 0b_0000_0011, pos + 3));
 
         result
     }
 
     /// Maps a half byte to 2 DNA bases.
     #[inline]
-    fn map_half_byte_to_bases(b: u8) -> Vec<Base> {
+    fn map_half_byte_to_bases(&self, b: u8) -> Vec<Base> {
         let mut result = Vec::with_capacity(2);
-        result.push(Self::map_byte_to_base((b >> 2) & 0b_0000_0011));
-        result.push(Self::map_byte_to_base(b & 0b_0000_0011));
+        result.push(self.mapping.base_at((b >> 2) & 0b_0000_0011, 0));
+        result.push(self.mapping.base_at(b & 0b_0000_0011, 1));
 
         result
     }
 
 
-    /// Converts a byte to a single DNA base.
+    /// Maps a slice of DNA bases (4 bases per byte) back to bytes. This is the inverse of `map_bytes_to_base_sequence`.
     #[inline]
-    fn map_byte_to_base(bits: u8) -> Base {
-        unsafe {
-            std::mem::transmute(bits)
-        }
+    fn map_bases_to_bytes(&self, bases: &[Base]) -> Vec<u8> {
+        bases.chunks(4).enumerate().map(|(i, chunk)| chunk.iter().enumerate().fold(0_u8, |b, (j, base)| (b << 2) | self.mapping.bits_at(*base, 4 * i + j))).collect()
+    }
+
+    /// Maps 2 DNA bases back to a half byte. This is the inverse of `map_half_byte_to_bases`.
+    #[inline]
+    fn map_bases_to_half_byte(&self, bases: &[Base]) -> u8 {
+        (self.mapping.bits_at(bases[0], 0) << 2) | self.mapping.bits_at(bases[1], 1)
     }
 
     /// Computes and returns the next `count` repair packets starting from the encoding symbol id (ESI) `from_repair_esi`.
@@ -244,4 +722,338 @@ impl RaptorQ {
     pub fn symbol_size(&self) -> usize {
         self.symbol_size
     }
-}
\ No newline at end of file
+    pub fn mapping(&self) -> BaseMapping {
+        self.mapping
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    static SEED: u64 = 7_u64;
+
+    /// Returns the RQ configurations under test: the default one, a rotating non-default mapping, and a larger symbol with several sub-blocks.
+    fn raptors() -> Vec<RaptorQ> {
+        vec![
+            RaptorQ::default(),
+            RaptorQ::with_mapping(BaseMapping::from_str("GTAC").unwrap().with_rotation(true)),
+            RaptorQ::new(1_usize, 2_usize, 4_usize, 16_usize, BaseMapping::from_str("CATG").unwrap())
+        ]
+    }
+
+    /// Encodes `data` without any constraints and returns the Info-DNA.
+    fn encode(raptor: &RaptorQ, data: &[u8], overhead: usize) -> Arc<BaseSequence> {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        match raptor.encode_to_dna_with_rules(data, EncodeLimits::new(5_usize, 200_usize, overhead), &mut rng, &EncodeRules::accept_all()) {
+            Ok((seq, ..)) => seq,
+            Err(e) => panic!("cannot encode {} bytes: {}", data.len(), e)
+        }
+    }
+
+    /// Encodes `data` under the default GC content and homopolymer constraints of the encoder, checked on every packet and on the Info-DNA, and returns the Info-DNA.
+    fn encode_with_gc_hp_rules(raptor: &RaptorQ, data: &[u8]) -> Arc<BaseSequence> {
+        let gc_hp_rules = dna_rules::GcHpRules::new(0.4_f64, 0.6_f64, 5_usize);
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let rules = EncodeRules::accept_all()
+            .with_packet_check(|seq: &Arc<BaseSequence>| gc_hp_rules.satisfies(seq))
+            .with_strand_gc_hp_check(|gc, longest_hp| gc_hp_rules.satisfies_stats(gc, longest_hp));
+        match raptor.encode_to_dna_with_rules(data, EncodeLimits::new(5_usize, 200_usize, 0_usize), &mut rng, &rules) {
+            Ok((seq, ..)) => {
+                assert!(gc_hp_rules.satisfies(&Arc::new(BaseSequence::new(seq.sub_sequence_slice(HEADER_LEN, seq.len()).to_vec()))));
+                seq
+            }
+            Err(e) => panic!("cannot encode {} bytes: {}", data.len(), e)
+        }
+    }
+
+    fn data_of_len(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn base_mapping_is_invertible() {
+        for mapping in ["ACGT", "TGCA", "GTAC"].iter() {
+            for &rotate in [false, true].iter() {
+                let mapping = BaseMapping::from_str(mapping).unwrap().with_rotation(rotate);
+                for pos in 0..8 {
+                    for bits in 0..4_u8 {
+                        assert_eq!(mapping.bits_at(mapping.base_at(bits, pos), pos), bits);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn base_mapping_rejects_invalid_mappings() {
+        assert_eq!(BaseMapping::from_str("AACG"), Err(ParseBaseMappingError::DuplicateBase(Base::A)));
+        assert_eq!(BaseMapping::from_str("ACG"), Err(ParseBaseMappingError::WrongLength(3_usize)));
+        assert_eq!(BaseMapping::from_str("ACGTA"), Err(ParseBaseMappingError::WrongLength(5_usize)));
+        assert!(matches!(BaseMapping::from_str("ACXT"), Err(ParseBaseMappingError::InvalidBase(ParseBaseError { byte: b'X', position: 2_usize }))));
+        assert_eq!("tgca".parse::<BaseMapping>().map(|mapping| mapping.to_string()), Ok("TGCA".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "every base exactly once")]
+    fn base_mapping_new_panics_for_duplicate_bases() {
+        BaseMapping::new([Base::A, Base::C, Base::C, Base::T]);
+    }
+
+    #[test]
+    fn byte_to_bases_round_trip() {
+        for raptor in raptors() {
+            for b in 0..=u8::MAX {
+                for &pos in [0_usize, 1, 2, 3, 4, 13].iter() {
+                    let bases = raptor.map_byte_to_bases(b, pos);
+                    assert_eq!(bases.len(), 4);
+                    let decoded = bases.iter().enumerate().fold(0_u8, |byte, (i, base)| (byte << 2) | raptor.mapping().bits_at(*base, pos + i));
+                    assert_eq!(decoded, b);
+                }
+            }
+            let bytes = (0..=u8::MAX).collect::<Vec<_>>();
+            let seq = raptor.map_bytes_to_base_sequence(&bytes);
+            assert_eq!(seq.len(), 4 * bytes.len());
+            assert_eq!(raptor.map_bases_to_bytes(seq.as_slice()), bytes);
+        }
+    }
+
+    #[test]
+    fn default_mapping_maps_bits_in_order() {
+        let raptor = RaptorQ::default();
+        assert_eq!(raptor.map_byte_to_bases(0b_00_01_10_11, 0), vec![Base::A, Base::C, Base::G, Base::T]);
+        assert_eq!(raptor.map_byte_to_bases(0b_11_11_00_00, 0), vec![Base::T, Base::T, Base::A, Base::A]);
+    }
+
+    #[test]
+    fn rotation_breaks_homopolymers_of_repeated_bytes() {
+        let raptor = RaptorQ::with_mapping(BaseMapping::default().with_rotation(true));
+        let seq = raptor.map_bytes_to_base_sequence(&[0_u8; 8]);
+        assert!(seq.longest_hp() < 2_usize);
+        assert_eq!(raptor.map_bases_to_bytes(seq.as_slice()), vec![0_u8; 8]);
+    }
+
+    #[test]
+    fn half_byte_round_trip() {
+        for raptor in raptors() {
+            for b in 0..16_u8 {
+                let bases = raptor.map_half_byte_to_bases(b);
+                assert_eq!(bases.len(), 2);
+                assert_eq!(raptor.map_bases_to_half_byte(&bases), b);
+            }
+        }
+    }
+
+    #[test]
+    fn crc16_matches_the_check_value() {
+        assert_eq!(RaptorQ::crc16(b"123456789"), 0x29B1_u16);
+        assert_eq!(RaptorQ::crc16(&[]), 0xFFFF_u16);
+    }
+
+    #[test]
+    fn header_round_trip() {
+        for raptor in raptors() {
+            let payload = Arc::new(raptor.map_bytes_to_base_sequence(&vec![0xA5_u8; 3 * (1 + raptor.symbol_size())]));
+            // lengths beyond a byte would be truncated by a 1-byte length field
+            for &len in [0_usize, 1, 255, 256, 300, 4097, MAX_DATA_LEN].iter() {
+                let data = data_of_len(len);
+                let seq = raptor.finalize_encoding(&payload, &data, 3_usize, 1_usize);
+                assert_eq!(seq.len(), HEADER_LEN + payload.len());
+                assert_eq!(raptor.header_version(&seq), Some(HEADER_VERSION));
+
+                let len_bytes = raptor.map_bases_to_bytes(seq.sub_sequence_slice(0, LEN_HEADER_LEN));
+                assert_eq!(u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize, len);
+                assert_eq!(raptor.map_bases_to_half_byte(seq.sub_sequence_slice(LEN_HEADER_LEN, LEGACY_HEADER_LEN - VERSION_HEADER_LEN)), 3_u8);
+                let crc_bytes = raptor.map_bases_to_bytes(seq.sub_sequence_slice(LEGACY_HEADER_LEN, HEADER_LEN));
+                assert_eq!(u16::from_be_bytes([crc_bytes[0], crc_bytes[1]]), RaptorQ::crc16(&data));
+                assert_eq!(seq.sub_sequence_slice(HEADER_LEN, seq.len()), payload.as_slice());
+            }
+        }
+    }
+
+    #[test]
+    fn combine_packets_meets_the_overhead() {
+        let raptor = RaptorQ::default();
+        let data = data_of_len(60);
+        let encoder = Encoder::new(&data, raptor.transmission_info(data.len()));
+        let packets = raptor.generate_packets(&encoder.get_block_encoders()[0], 20_usize, 0_usize, 1_usize, |_| true);
+        assert_eq!(packets.len(), 20_usize);
+        let order = (0..packets.len()).collect::<Vec<_>>();
+        for &overhead in [0_usize, 2, 5].iter() {
+            match RaptorQ::combine_packets_to_strand(&packets, Decoder::new(encoder.get_config()), overhead, &order, |_, _| true, |_| true) {
+                PacketsResult::Found(strand, packets_used) => {
+                    assert!(packets_used >= raptor.source_symbols(data.len()) + overhead);
+                    assert_eq!(strand.len(), packets_used * raptor.packet_len(1_usize));
+                }
+                _ => panic!("no strand found for overhead {}", overhead)
+            }
+        }
+        assert!(matches!(RaptorQ::combine_packets_to_strand(&packets[..3].to_vec(), Decoder::new(encoder.get_config()), 0_usize, &[0, 1, 2], |_, _| true, |_| true), PacketsResult::NotDecodable));
+        assert!(matches!(RaptorQ::combine_packets_to_strand(&packets, Decoder::new(encoder.get_config()), 0_usize, &order, |_, _| false, |_| true), PacketsResult::RulesNotSatisfied(..)));
+    }
+
+    #[test]
+    fn combine_packets_reports_the_missing_packets() {
+        let raptor = RaptorQ::default();
+        let data = data_of_len(60);
+        let encoder = Encoder::new(&data, raptor.transmission_info(data.len()));
+        let packets = raptor.generate_packets(&encoder.get_block_encoders()[0], 20_usize, 0_usize, 1_usize, |_| true);
+        let order = (0..packets.len()).collect::<Vec<_>>();
+        let overhead = 30_usize;
+        match RaptorQ::combine_packets_to_strand(&packets, Decoder::new(encoder.get_config()), overhead, &order, |_, _| true, |_| true) {
+            PacketsResult::OverheadTooBig(missing) => assert!(missing >= raptor.source_symbols(data.len()) + overhead - packets.len()),
+            _ => panic!("the overhead of {} packets cannot be met by {} packets", overhead, packets.len())
+        }
+        assert!(matches!(RaptorQ::combine_packets_to_strand(&packets, Decoder::new(encoder.get_config()), usize::MAX, &order, |_, _| true, |_| true), PacketsResult::OverheadTooBig(_)));
+    }
+
+    #[test]
+    fn returns_the_dg_energy_of_the_info_dna() {
+        let raptor = RaptorQ::default();
+        let data = data_of_len(30);
+        let rules = EncodeRules::accept_all().with_dg_check(Some(|_: &Arc<BaseSequence>| (true, -3.5_f32)));
+        match raptor.encode_to_dna_with_rules(&data, EncodeLimits::new(5_usize, 200_usize, 0_usize), &mut StdRng::seed_from_u64(SEED), &rules) {
+            Ok((seq, _, _, dg, ..)) => {
+                assert_eq!(dg, -3.5_f32);
+                assert_eq!(raptor.decode_from_dna(&seq), Some(data.clone()));
+            }
+            Err(e) => panic!("cannot encode {} bytes: {}", data.len(), e)
+        }
+        match raptor.encode_to_dna_with_rules(&data, EncodeLimits::new(5_usize, 200_usize, 0_usize), &mut StdRng::seed_from_u64(SEED), &EncodeRules::accept_all()) {
+            Ok((_, _, dg_time, dg, ..)) => {
+                assert!(dg.is_nan());
+                assert_eq!(dg_time, Duration::new(0_u64, 0_u32));
+            }
+            Err(e) => panic!("cannot encode {} bytes: {}", data.len(), e)
+        }
+    }
+
+    #[test]
+    fn failing_dg_check_exceeds_the_loops() {
+        let raptor = RaptorQ::default();
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let rules = EncodeRules::accept_all().with_dg_check(Some(|_: &Arc<BaseSequence>| (false, -1_f32)));
+        match raptor.encode_to_dna_with_rules(&data_of_len(30), EncodeLimits::new(5_usize, 3_usize, 0_usize), &mut rng, &rules) {
+            Err(EncodeError::MaxLoopsExceeded { best, satisfies_strand_rules, dg, .. }) => {
+                assert!(best.is_some());
+                assert!(satisfies_strand_rules);
+                assert_eq!(dg, Some(-1_f32));
+            }
+            Ok(_) => panic!("a strand passed the failing dg check")
+        }
+    }
+
+    #[test]
+    fn strand_rules_are_applied_to_the_info_dna() {
+        let raptor = RaptorQ::default();
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let rules = EncodeRules::accept_all().with_strand_check(|_: &Arc<BaseSequence>| false);
+        match raptor.encode_to_dna_with_rules(&data_of_len(30), EncodeLimits::new(5_usize, 3_usize, 0_usize), &mut rng, &rules) {
+            Err(EncodeError::MaxLoopsExceeded { best, satisfies_strand_rules, dg, .. }) => {
+                assert!(best.is_some());
+                assert!(!satisfies_strand_rules);
+                assert_eq!(dg, None);
+            }
+            Ok(_) => panic!("a strand passed the failing strand check")
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for raptor in raptors() {
+            for &len in [1_usize, 6, 7, 64, 255, 256, 300].iter() {
+                let data = data_of_len(len);
+                let seq = encode(&raptor, &data, 0_usize);
+                assert_eq!(raptor.decode_from_dna(&seq), Some(data), "{} bytes, mapping {}", len, raptor.mapping());
+            }
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trip_with_overhead() {
+        let raptor = RaptorQ::default();
+        let data = data_of_len(100);
+        let seq = encode(&raptor, &data, 4_usize);
+        let (packets, len) = raptor.estimate_encoding(data.len(), 4_usize);
+        assert!(seq.len() >= len);
+        assert_eq!((seq.len() - HEADER_LEN) % raptor.packet_len(1_usize), 0_usize);
+        assert!((seq.len() - HEADER_LEN) / raptor.packet_len(1_usize) >= packets);
+        assert_eq!(raptor.decode_from_dna(&seq), Some(data));
+    }
+
+    #[test]
+    fn encode_decode_round_trip_with_gc_hp_rules() {
+        let raptor = RaptorQ::default();
+        for &len in [100_usize, 300].iter() {
+            let data = data_of_len(len);
+            let seq = encode_with_gc_hp_rules(&raptor, &data);
+            assert_eq!(raptor.header_version(&seq), Some(HEADER_VERSION));
+            assert_eq!(raptor.decode_from_dna(&seq), Some(data));
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trip_beyond_short_esis() {
+        // more than SHORT_ESI_REPAIR_SYMBOLS repair symbols are generated for these lengths under the constraints, so the packets store the two lower bytes of their ESI
+        let raptor = RaptorQ::default();
+        for &len in [1000_usize, 2000].iter() {
+            let data = data_of_len(len);
+            let seq = encode_with_gc_hp_rules(&raptor, &data);
+            assert_eq!(raptor.header_version(&seq), Some(HEADER_VERSION_WIDE_ESI));
+            assert_eq!((seq.len() - HEADER_LEN) % raptor.packet_len(2_usize), 0_usize);
+            let decoded = raptor.decode_from_dna(&seq);
+            assert_eq!(decoded.as_ref().map(|decoded| decoded.len()), Some(len));
+            assert_eq!(decoded, Some(data));
+        }
+    }
+
+    #[test]
+    fn header_round_trip_with_wide_esis() {
+        let raptor = RaptorQ::default();
+        let payload = Arc::new(raptor.map_bytes_to_base_sequence(&vec![0x5A_u8; 3 * (2 + raptor.symbol_size())]));
+        let data = data_of_len(1000);
+        let seq = raptor.finalize_encoding(&payload, &data, 3_usize, 2_usize);
+        assert_eq!(raptor.header_version(&seq), Some(HEADER_VERSION_WIDE_ESI));
+        assert_eq!(raptor.header_versions(&seq), vec![HEADER_VERSION_WIDE_ESI]);
+        assert_eq!(raptor.header_version(&raptor.finalize_encoding(&payload, &data, 3_usize, 1_usize)), None); // the payload does not consist of packets with 1-byte ESIs
+    }
+
+    #[test]
+    fn decodes_the_unversioned_header() {
+        let raptor = RaptorQ::default();
+        let data = data_of_len(50);
+        let seq = encode(&raptor, &data, 0_usize);
+        let legacy = BaseSequence::concat_slice(seq.sub_sequence_slice(0, LEGACY_HEADER_LEN), seq.sub_sequence_slice(HEADER_LEN, seq.len()));
+        assert_eq!(raptor.header_version(&legacy), Some(0_u8));
+        assert_eq!(raptor.decode_from_dna(&legacy), Some(data));
+    }
+
+    #[test]
+    fn decode_rejects_corrupted_strands() {
+        let raptor = RaptorQ::default();
+        let data = data_of_len(120);
+        let seq = encode(&raptor, &data, 0_usize);
+
+        let truncated = BaseSequence::new(seq.sub_sequence_slice(0, seq.len() - 1).to_vec());
+        assert_eq!(raptor.header_version(&truncated), None);
+        assert_eq!(raptor.decode_from_dna(&truncated), None);
+
+        let mut bases = seq.as_slice().to_vec();
+        let i = HEADER_LEN + 4 * 3;
+        bases[i] = if bases[i] == Base::A { Base::C } else { Base::A };
+        assert_eq!(raptor.decode_from_dna(&BaseSequence::new(bases)), None);
+    }
+
+    #[test]
+    fn expired_deadline_exceeds_the_loops() {
+        let raptor = RaptorQ::default();
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let limits = EncodeLimits::new(5_usize, usize::MAX, 0_usize).with_deadline(Some(SystemTime::now()));
+        match raptor.encode_to_dna_with_rules(&data_of_len(30), limits, &mut rng, &EncodeRules::accept_all()) {
+            Err(EncodeError::MaxLoopsExceeded { best, .. }) => assert!(best.is_none()),
+            Ok(_) => panic!("a loop was started after the deadline")
+        }
+        assert!(raptor.encode_to_dna_with_rules(&data_of_len(30), limits.with_deadline(None), &mut rng, &EncodeRules::accept_all()).is_ok());
+    }
+}
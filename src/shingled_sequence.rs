@@ -0,0 +1,76 @@
+use crate::base_sequence::BaseSequence;
+use crate::lsh::{LSH, MAX_K};
+use std::collections::HashSet;
+
+/// The shingles of a sequence, i.e., the row-ids (see `LSH::row_ids`) of its k-mers or canonical k-mers, which are computed once.
+/// Intended for a query that is compared to many candidates with the Jaccard distance, e.g., an Info-DNA checked against the probes, so that only the shingles of the candidates are computed per comparison.
+#[derive(Clone, Debug)]
+pub struct ShingledSequence {
+    k: usize,
+    canonical: bool,
+    shingles: HashSet<usize>
+}
+
+impl ShingledSequence {
+    /// Creates a new ShingledSequence of `seq` with the k-mers of length `k`, or the canonical k-mers if `canonical` is true. Panics if `k` exceeds `MAX_K` or the length of `seq`.
+    pub fn new(seq: &BaseSequence, k: usize, canonical: bool) -> Self {
+        if k > MAX_K {
+            panic!("shingles are only supported for k-mers up to k = {}, but k = {} was requested", MAX_K, k);
+        }
+        Self {
+            k,
+            canonical,
+            shingles: Self::shingles_of(seq, k, canonical)
+        }
+    }
+
+    /// Calculates the Jaccard distance to `to`, i.e., the same value as `BaseSequence::jaccard_distance_arc` (or `canonical_jaccard_distance_arc`), but computes only the shingles of `to`.
+    pub fn jaccard_distance(&self, to: &BaseSequence) -> f64 {
+        Self::jaccard_distance_of(&self.shingles, &Self::shingles_of(to, self.k, self.canonical))
+    }
+
+    fn shingles_of(seq: &BaseSequence, k: usize, canonical: bool) -> HashSet<usize> {
+        LSH::row_ids(seq.as_slice(), k, canonical).into_iter().collect()
+    }
+
+    /// Calculates the Jaccard distance of the shingles `a` and `b`, which is 1 if either of them is empty (as for `LSH::estimated_jaccard`), instead of dividing by an empty union.
+    fn jaccard_distance_of(a: &HashSet<usize>, b: &HashSet<usize>) -> f64 {
+        if a.is_empty() || b.is_empty() {
+            return 1_f64;
+        }
+        let intersection_size = a.intersection(b).count();
+        let union_size = a.len() + b.len() - intersection_size;
+        1_f64 - (intersection_size as f64 / union_size as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn seq(s: &str) -> BaseSequence {
+        BaseSequence::try_from_str(s).unwrap()
+    }
+
+    #[test]
+    fn jaccard_distance_matches_the_distance_of_the_sequences() {
+        let a = seq("ACGTTGCAACGGTTCCAAGG");
+        let b = Arc::new(seq("ACGTTGCAACGGTTCCTTGC"));
+        let shingled = ShingledSequence::new(&a, 4_usize, false);
+        assert_eq!(shingled.jaccard_distance(&b), a.jaccard_distance_arc(&b, 4_usize));
+        let shingled = ShingledSequence::new(&a, 4_usize, true);
+        assert_eq!(shingled.jaccard_distance(&b), a.canonical_jaccard_distance_arc(&b, 4_usize));
+        assert_eq!(shingled.jaccard_distance(&a), 0_f64);
+    }
+
+    #[test]
+    fn jaccard_distance_of_empty_shingles_is_1() {
+        let empty = HashSet::new();
+        let non_empty = [1_usize, 2_usize].iter().copied().collect::<HashSet<_>>();
+        assert_eq!(ShingledSequence::jaccard_distance_of(&empty, &empty), 1_f64);
+        assert_eq!(ShingledSequence::jaccard_distance_of(&empty, &non_empty), 1_f64);
+        assert_eq!(ShingledSequence::jaccard_distance_of(&non_empty, &empty), 1_f64);
+        assert!(!ShingledSequence::jaccard_distance_of(&empty, &empty).is_nan());
+    }
+}
@@ -0,0 +1,429 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashSet;
+use parking_lot::RwLock;
+use rayon::ThreadPool;
+use rayon::prelude::*;
+use crate::base_sequence::BaseSequence;
+use crate::lsh::{LSH, LSH_BUCKETING_MINHASH, MAX_K};
+use crate::shingled_sequence::ShingledSequence;
+
+static DISTANCE_CHECK_POOLING_TRIGGER: usize  = 2000_usize;          // the number of distance checks before parallelizing the computations (should be manually adjusted for the target machine)
+
+pub static SIMILARITY_BACKEND_EXACT: usize    = 0_usize;             // similarity backend "exact" is represented as 0
+pub static SIMILARITY_BACKEND_APPROX: usize   = 1_usize;             // similarity backend "approx" is represented as 1
+
+pub static DISTANCE_METRIC_JACCARD: usize     = 0_usize;             // distance metric "jaccard" is represented as 0
+pub static DISTANCE_METRIC_EDIT: usize        = 1_usize;             // distance metric "edit" is represented as 1
+pub static DISTANCE_METRIC_HAMMING: usize     = 2_usize;             // distance metric "hamming" is represented as 2
+pub static DISTANCE_METRIC_WEIGHTED_JACCARD: usize = 3_usize;        // distance metric "weighted_jaccard" is represented as 3
+
+/// The interface for a pool of sequences that decides whether a sequence is far enough from all the sequences in that pool.
+pub trait SimilarityBackend: Send + Sync {
+    /// Returns true if `seq` has a distance of at least `min` to every sequence in the pool. If `check_revcomp` is true, the reverse complement of `seq` must have a distance of at least `min` to every sequence in the pool, too.
+    fn is_far_enough(&self, seq: &Arc<BaseSequence>, min: f64, check_revcomp: bool) -> bool;
+
+    /// Inserts `seq` into the pool if it has a distance of at least `min` to every sequence in the pool, including the sequences inserted concurrently by other threads. If `check_revcomp` is true, the same applies to the reverse complement of `seq`. Returns true if `seq` was inserted.
+    fn insert_if_far_enough(&self, seq: &Arc<BaseSequence>, min: f64, check_revcomp: bool) -> bool;
+
+    /// Inserts `seq` into the pool without checking its distance, e.g., an Info-DNA of a resumed run.
+    fn insert(&self, seq: &Arc<BaseSequence>);
+}
+
+/// The backend that computes the distance of a sequence to every sequence in the pool (full pairwise checks).
+pub struct ExactBackend {
+    seqs: RwLock<Vec<Arc<BaseSequence>>>,
+    k: usize,
+    metric: usize,
+    canonical: bool,
+    dist_pool: Arc<ThreadPool>
+}
+
+impl ExactBackend {
+    /// Creates a new ExactBackend.
+    /// # Arguments
+    /// * `seqs` - The sequences initially contained in the pool.
+    /// * `k` - The length of the k-mers used for the Jaccard distance.
+    /// * `metric` - The distance metric represented as a number (0=jaccard, 1=edit, 2=hamming, 3=weighted_jaccard).
+    /// * `dist_pool` - The thread pool for parallelizing distance checks.
+    pub fn new(seqs: Vec<Arc<BaseSequence>>, k: usize, metric: usize, dist_pool: Arc<ThreadPool>) -> Self {
+        Self {
+            seqs: RwLock::new(seqs),
+            k,
+            metric,
+            canonical: false,
+            dist_pool
+        }
+    }
+
+    /// Sets whether the Jaccard distance is computed from the canonical k-mers (see `BaseSequence::canonical_k_mers_set`) instead of the k-mers.
+    pub fn with_canonical_k_mers(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// Returns the parameters of the distance checks of this backend, which has no MinHash pre-filter.
+    #[inline(always)]
+    fn params(&self) -> DistanceParams<'static> {
+        DistanceParams {
+            k: self.k,
+            metric: self.metric,
+            canonical: self.canonical,
+            prefilter: None
+        }
+    }
+}
+
+impl SimilarityBackend for ExactBackend {
+    fn is_far_enough(&self, seq: &Arc<BaseSequence>, min: f64, check_revcomp: bool) -> bool {
+        let read_lock = self.seqs.read();
+        orientations(seq, check_revcomp).iter().all(|s| pooled_dist_check(s, read_lock.as_slice(), min, &self.params(), &self.dist_pool))
+    }
+
+    fn insert_if_far_enough(&self, seq: &Arc<BaseSequence>, min: f64, check_revcomp: bool) -> bool {
+        let queries = orientations(seq, check_revcomp);
+        let mut checked = 0_usize; // the sequences before this index are known to be far enough
+        loop {
+            // the distances are computed under the read lock only, so that concurrent checks do not block each other
+            let read_lock = self.seqs.read();
+            let len = read_lock.len();
+            if !queries.iter().all(|s| pooled_dist_check(s, &read_lock[checked..len], min, &self.params(), &self.dist_pool)) {
+                return false;
+            }
+            checked = len;
+            drop(read_lock);
+
+            // insert only if no sequence was inserted in the meantime, otherwise check the sequences inserted in the meantime
+            let mut write_lock = self.seqs.write();
+            if write_lock.len() == checked {
+                write_lock.push(seq.clone());
+                return true;
+            }
+        }
+    }
+
+    fn insert(&self, seq: &Arc<BaseSequence>) {
+        self.seqs.write().push(seq.clone());
+    }
+}
+
+/// The backend that computes the distance of a sequence only to the similar candidates returned by an LSH instance.
+/// The LSH instance is only modified under the write lock of `lsh`, so a query under the read lock never observes a partial insertion (see `LSH::insert`).
+pub struct ApproxBackend {
+    lsh: RwLock<LSH>,
+    inserted: AtomicUsize, // the number of sequences inserted by this backend, which is only modified under the write lock of `lsh`
+    metric: usize,
+    prefilter_margin: Option<f64>, // candidates whose estimated Jaccard distance is at least the minimum distance plus this margin are not checked exactly (None = disabled)
+    dist_pool: Arc<ThreadPool>
+}
+
+impl ApproxBackend {
+    /// Creates a new ApproxBackend from the LSH instance `lsh`, which may already contain sequences. The candidates are compared using the distance `metric` (0=jaccard, 1=edit, 2=hamming, 3=weighted_jaccard), where the (weighted) Jaccard distance uses the canonical k-mers if `lsh` does.
+    pub fn new(lsh: LSH, metric: usize, dist_pool: Arc<ThreadPool>) -> Self {
+        Self {
+            lsh: RwLock::new(lsh),
+            inserted: AtomicUsize::new(0_usize),
+            metric,
+            prefilter_margin: None,
+            dist_pool
+        }
+    }
+
+    /// Enables the MinHash pre-filter for the Jaccard distance and the min-hash cache of the LSH instance (see `LSH::with_min_hash_cache`): a candidate whose Jaccard distance estimated from the min-hashes (see `LSH::estimated_jaccard`) is at least the minimum distance plus `margin` is considered far enough without computing its exact distance.
+    /// This saves building the k-mer sets of the candidate, but a candidate that is closer than the minimum distance passes if its estimate is off by more than `margin`. Ignored for other distance metrics.
+    pub fn with_min_hash_prefilter(mut self, margin: f64) -> Self {
+        self.lsh = RwLock::new(self.lsh.into_inner().with_min_hash_cache(true));
+        self.prefilter_margin = Some(margin);
+        self
+    }
+
+    /// Returns the parameters of the distance checks against the candidates of `lsh`, where the MinHash pre-filter only applies to the Jaccard distance and MinHash bucketing.
+    #[inline(always)]
+    fn params<'a>(&self, lsh: &'a LSH) -> DistanceParams<'a> {
+        DistanceParams {
+            k: lsh.k(),
+            metric: self.metric,
+            canonical: lsh.is_canonical(),
+            prefilter: self.prefilter_margin.filter(|_| self.metric == DISTANCE_METRIC_JACCARD && lsh.bucketing() == LSH_BUCKETING_MINHASH).map(|margin| (lsh, margin))
+        }
+    }
+}
+
+impl SimilarityBackend for ApproxBackend {
+    fn is_far_enough(&self, seq: &Arc<BaseSequence>, min: f64, check_revcomp: bool) -> bool {
+        let lsh = self.lsh.read();
+        orientations(seq, check_revcomp).iter().all(|s| pooled_dist_check_ranked(s, ranked_candidates(&lsh, s), min, &self.params(&lsh), &self.dist_pool))
+    }
+
+    fn insert_if_far_enough(&self, seq: &Arc<BaseSequence>, min: f64, check_revcomp: bool) -> bool {
+        let queries = orientations(seq, check_revcomp);
+        let mut checked = vec![HashSet::new(); queries.len()]; // the candidates of each orientation that are known to be far enough
+        loop {
+            // the distances are computed under the read lock only, so that concurrent checks do not block each other
+            let lsh = self.lsh.read();
+            let inserted = self.inserted.load(Ordering::Acquire);
+            for (s, checked) in queries.iter().zip(checked.iter_mut()) {
+                let candidates = ranked_candidates(&lsh, s).into_iter().filter(|candidate| !checked.contains(candidate)).collect::<Vec<_>>();
+                if !pooled_dist_check_ranked(s, candidates.clone(), min, &self.params(&lsh), &self.dist_pool) {
+                    return false;
+                }
+                checked.extend(candidates);
+            }
+            drop(lsh);
+
+            // insert only if no sequence was inserted in the meantime, otherwise check the new candidates
+            let lsh = self.lsh.write();
+            if self.inserted.load(Ordering::Acquire) == inserted {
+                lsh.insert(seq);
+                self.inserted.store(inserted + 1_usize, Ordering::Release);
+                return true;
+            }
+        }
+    }
+
+    fn insert(&self, seq: &Arc<BaseSequence>) {
+        let lsh = self.lsh.write();
+        lsh.insert(seq);
+        self.inserted.fetch_add(1_usize, Ordering::AcqRel);
+    }
+}
+
+/// Converts the similarity backend's string into a number, i.e., (0="exact", 1="approx").
+#[inline(always)]
+pub fn extract_similarity_backend(arg: &str) -> usize {
+    if arg.eq_ignore_ascii_case("exact") {
+        SIMILARITY_BACKEND_EXACT
+    }
+    else if arg.eq_ignore_ascii_case("approx") {
+        SIMILARITY_BACKEND_APPROX
+    }
+    else {
+        panic!("cannot determine similarity backend: {}", arg);
+    }
+}
+
+/// Returns the orientations of `seq` that have to be checked, i.e., `seq` and, if `check_revcomp` is true, its reverse complement.
+#[inline(always)]
+fn orientations(seq: &Arc<BaseSequence>, check_revcomp: bool) -> Vec<Arc<BaseSequence>> {
+    if check_revcomp {
+        vec![seq.clone(), Arc::new(seq.reverse_complement())]
+    }
+    else {
+        vec![seq.clone()]
+    }
+}
+
+/// Converts the distance metric's string into a number, i.e., (0="jaccard", 1="edit", 2="hamming", 3="weighted_jaccard").
+#[inline(always)]
+pub fn extract_distance_metric(arg: &str) -> usize {
+    if arg.eq_ignore_ascii_case("jaccard") {
+        DISTANCE_METRIC_JACCARD
+    }
+    else if arg.eq_ignore_ascii_case("edit") {
+        DISTANCE_METRIC_EDIT
+    }
+    else if arg.eq_ignore_ascii_case("hamming") {
+        DISTANCE_METRIC_HAMMING
+    }
+    else if arg.eq_ignore_ascii_case("weighted_jaccard") {
+        DISTANCE_METRIC_WEIGHTED_JACCARD
+    }
+    else {
+        panic!("cannot determine distance metric: {}", arg);
+    }
+}
+
+/// The parameters of a distance check between a sequence and its candidates.
+#[derive(Clone, Copy)]
+struct DistanceParams<'a> {
+    k: usize,                            // the k-mer length for the (weighted) Jaccard distance
+    metric: usize,                       // the distance metric represented as a number (0=jaccard, 1=edit, 2=hamming, 3=weighted_jaccard)
+    canonical: bool,                     // whether the (weighted) Jaccard distance uses the canonical k-mers
+    prefilter: Option<(&'a LSH, f64)>    // the LSH instance and the margin of the MinHash pre-filter (None = disabled)
+}
+
+/// Returns the shingles of `seq` for the Jaccard distance, which are computed once per check instead of once per candidate. Returns None for the other metrics and for k-mers longer than `MAX_K`.
+#[inline(always)]
+fn shingles(seq: &Arc<BaseSequence>, params: &DistanceParams) -> Option<ShingledSequence> {
+    (params.metric == DISTANCE_METRIC_JACCARD && params.k <= MAX_K).then(|| ShingledSequence::new(seq, params.k, params.canonical))
+}
+
+/// Computes the normalized distance between `seq` and `candidate` as specified by `params`, using the precomputed `shingled` query if given. The Hamming distance of sequences with different lengths is 1.
+#[inline(always)]
+fn distance(seq: &Arc<BaseSequence>, shingled: Option<&ShingledSequence>, candidate: &Arc<BaseSequence>, params: &DistanceParams) -> f64 {
+    let DistanceParams { k, metric, canonical, .. } = *params;
+    if let Some(shingled) = shingled {
+        shingled.jaccard_distance(candidate)
+    }
+    else if metric == DISTANCE_METRIC_JACCARD && canonical {
+        seq.canonical_jaccard_distance_arc(candidate, k)
+    }
+    else if metric == DISTANCE_METRIC_JACCARD {
+        seq.jaccard_distance_arc(candidate, k)
+    }
+    else if metric == DISTANCE_METRIC_WEIGHTED_JACCARD && canonical {
+        seq.canonical_weighted_jaccard_distance_arc(candidate, k)
+    }
+    else if metric == DISTANCE_METRIC_WEIGHTED_JACCARD {
+        seq.weighted_jaccard_distance_arc(candidate, k)
+    }
+    else if metric == DISTANCE_METRIC_EDIT {
+        seq.edit_distance_arc(candidate)
+    }
+    else {
+        seq.normalized_hamming_distance_arc(candidate).unwrap_or(1_f64)
+    }
+}
+
+/// A function that computes distances between `seq` and `candidates` (slice). Decides to parallelize the checks given candidates.len().
+#[inline(always)]
+fn pooled_dist_check(seq: &Arc<BaseSequence>, candidates: &[Arc<BaseSequence>], min: f64, params: &DistanceParams, pool: &Arc<ThreadPool>) -> bool {
+    if candidates.is_empty() {
+        return true;
+    }
+    let shingled = shingles(seq, params);
+    if candidates.len() < DISTANCE_CHECK_POOLING_TRIGGER {
+        for candidate in candidates.iter() {
+            if distance(seq, shingled.as_ref(), candidate, params) < min  {
+                return false;
+            }
+        }
+        return true
+    }
+    parallel_dist_check(seq, shingled.as_ref(), candidates.par_iter(), min, params, pool)
+}
+
+/// Returns the candidates of `seq` in `lsh` with the ones that share the most buckets with `seq` first (see `LSH::similar_seqs_ranked`).
+#[inline(always)]
+fn ranked_candidates(lsh: &LSH, seq: &Arc<BaseSequence>) -> Vec<Arc<BaseSequence>> {
+    lsh.similar_seqs_ranked(seq).into_iter().map(|(candidate, _)| candidate).collect()
+}
+
+/// A function that computes distances between `seq` and `candidates` (ranked, see `ranked_candidates`), so that the candidates most likely to be too close are checked first. Decides to parallelize the checks given candidates.len().
+/// If the pre-filter of `params` is set, the candidates whose Jaccard distance estimated by the LSH instance is at least `min` plus the margin are skipped.
+fn pooled_dist_check_ranked(seq: &Arc<BaseSequence>, candidates: Vec<Arc<BaseSequence>>, min: f64, params: &DistanceParams, pool: &Arc<ThreadPool>) -> bool {
+    // skip the candidates that are far enough according to their estimated Jaccard distance
+    let candidates = match params.prefilter {
+        Some((lsh, margin)) if !candidates.is_empty() => {
+            let min_hashes = lsh.min_hashes(seq);
+            candidates.into_iter().filter(|candidate| 1_f64 - lsh.estimated_jaccard_to(&min_hashes, candidate) < min + margin).collect::<Vec<_>>()
+        }
+        _ => candidates
+    };
+    pooled_dist_check(seq, &candidates, min, params, pool)
+}
+
+/// Computes the distances between `seq` and the `candidates` in `pool` and returns true if all of them are at least `min`. Stops computing distances as soon as a distance below `min` is found.
+fn parallel_dist_check<'a>(seq: &Arc<BaseSequence>, shingled: Option<&ShingledSequence>, candidates: impl ParallelIterator<Item = &'a Arc<BaseSequence>>, min: f64, params: &DistanceParams, pool: &Arc<ThreadPool>) -> bool {
+    !pool.install(|| candidates.any(|candidate| distance(seq, shingled, candidate, params) < min))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rayon::ThreadPoolBuilder;
+
+    static SEED: u64 = 7_u64;
+
+    fn pool() -> Arc<ThreadPool> {
+        Arc::new(ThreadPoolBuilder::new().num_threads(2_usize).build().unwrap())
+    }
+
+    fn random_seqs(n: usize) -> Vec<Arc<BaseSequence>> {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        (0..n).map(|_| Arc::new(BaseSequence::random_with_rng(60_usize, &mut rng))).collect()
+    }
+
+    fn backends(seqs: &[Arc<BaseSequence>], metric: usize) -> Vec<Box<dyn SimilarityBackend>> {
+        let lsh = LSH::new(6_usize, 40_usize, 10_usize, Some(SEED));
+        seqs.iter().for_each(|seq| { lsh.insert(seq); });
+        vec![
+            Box::new(ExactBackend::new(seqs.to_vec(), 6_usize, metric, pool())),
+            Box::new(ApproxBackend::new(lsh, metric, pool()))
+        ]
+    }
+
+    #[test]
+    fn backends_reject_sequences_close_to_the_pool() {
+        let seqs = random_seqs(20_usize);
+        for metric in [DISTANCE_METRIC_JACCARD, DISTANCE_METRIC_EDIT, DISTANCE_METRIC_HAMMING, DISTANCE_METRIC_WEIGHTED_JACCARD].iter() {
+            for backend in backends(&seqs, *metric) {
+                assert!(!backend.is_far_enough(&seqs[3], 0.1_f64, false));
+                assert!(!backend.insert_if_far_enough(&seqs[3], 0.1_f64, false));
+                let far = Arc::new(BaseSequence::random_with_rng(60_usize, &mut StdRng::seed_from_u64(SEED + 1_u64)));
+                assert!(backend.is_far_enough(&far, 0.1_f64, false));
+            }
+        }
+    }
+
+    #[test]
+    fn backends_check_the_reverse_complement_if_requested() {
+        let seqs = random_seqs(20_usize);
+        let revcomp = Arc::new(seqs[5].reverse_complement());
+        for backend in backends(&seqs, DISTANCE_METRIC_JACCARD) {
+            assert!(backend.is_far_enough(&revcomp, 0.3_f64, false));
+            assert!(!backend.is_far_enough(&revcomp, 0.3_f64, true));
+        }
+    }
+
+    #[test]
+    fn insert_if_far_enough_rejects_sequences_close_to_inserted_ones() {
+        let seqs = random_seqs(2_usize);
+        for backend in backends(&[], DISTANCE_METRIC_JACCARD) {
+            assert!(backend.insert_if_far_enough(&seqs[0], 0.3_f64, false));
+            assert!(!backend.insert_if_far_enough(&seqs[0], 0.3_f64, false));
+            assert!(backend.insert_if_far_enough(&seqs[1], 0.3_f64, false));
+            backend.insert(&seqs[0]);
+            assert!(!backend.is_far_enough(&seqs[1], 0.3_f64, false));
+        }
+    }
+
+    #[test]
+    fn min_hash_prefilter_does_not_reject_far_sequences() {
+        let seqs = random_seqs(20_usize);
+        let lsh = LSH::new(6_usize, 40_usize, 10_usize, Some(SEED));
+        seqs.iter().for_each(|seq| { lsh.insert(seq); });
+        let backend = ApproxBackend::new(lsh, DISTANCE_METRIC_JACCARD, pool()).with_min_hash_prefilter(0.1_f64);
+        assert!(!backend.is_far_enough(&seqs[0], 0.3_f64, false));
+        let far = Arc::new(BaseSequence::random_with_rng(60_usize, &mut StdRng::seed_from_u64(SEED + 1_u64)));
+        assert!(backend.is_far_enough(&far, 0.3_f64, false));
+    }
+
+    #[test]
+    fn extracts_backends_and_metrics() {
+        assert_eq!(extract_similarity_backend("EXACT"), SIMILARITY_BACKEND_EXACT);
+        assert_eq!(extract_similarity_backend("approx"), SIMILARITY_BACKEND_APPROX);
+        assert_eq!(extract_distance_metric("Jaccard"), DISTANCE_METRIC_JACCARD);
+        assert_eq!(extract_distance_metric("edit"), DISTANCE_METRIC_EDIT);
+        assert_eq!(extract_distance_metric("hamming"), DISTANCE_METRIC_HAMMING);
+        assert_eq!(extract_distance_metric("weighted_jaccard"), DISTANCE_METRIC_WEIGHTED_JACCARD);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot determine similarity backend")]
+    fn rejects_unknown_backends() {
+        extract_similarity_backend("fuzzy");
+    }
+
+    #[test]
+    fn distance_selects_the_weighted_jaccard_metric() {
+        let repetitive = Arc::new(BaseSequence::try_from_str("ACACACACAC").unwrap());
+        let short = Arc::new(BaseSequence::try_from_str("ACAC").unwrap());
+        let jaccard = DistanceParams { k: 2_usize, metric: DISTANCE_METRIC_JACCARD, canonical: false, prefilter: None };
+        let weighted = DistanceParams { metric: DISTANCE_METRIC_WEIGHTED_JACCARD, ..jaccard };
+        assert_eq!(distance(&repetitive, shingles(&repetitive, &jaccard).as_ref(), &short, &jaccard), 0_f64);
+        assert!(shingles(&repetitive, &weighted).is_none());
+        assert_eq!(distance(&repetitive, None, &short, &weighted), repetitive.weighted_jaccard_distance_arc(&short, 2_usize));
+        let canonical = DistanceParams { canonical: true, ..weighted };
+        let revcomp = Arc::new(repetitive.reverse_complement());
+        assert_eq!(distance(&repetitive, None, &revcomp, &canonical), 0_f64);
+        assert!(distance(&repetitive, None, &revcomp, &weighted) > 0_f64);
+
+        let backend = ExactBackend::new(vec![short], 2_usize, DISTANCE_METRIC_WEIGHTED_JACCARD, pool());
+        assert!(backend.is_far_enough(&repetitive, 0.5_f64, false));
+        let backend = ExactBackend::new(vec![Arc::new(BaseSequence::try_from_str("ACAC").unwrap())], 2_usize, DISTANCE_METRIC_JACCARD, pool());
+        assert!(!backend.is_far_enough(&repetitive, 0.5_f64, false));
+    }
+}